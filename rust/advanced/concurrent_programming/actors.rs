@@ -0,0 +1,644 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full"] }
+//! ```
+
+// Actors: typed mailboxes, request/response, supervision
+//
+// message_passing.rs wires channels by hand: make a channel, move the receiver
+// into a thread, write the receive loop, invent a reply channel per request.
+// An actor packages that up:
+//
+//   trait Actor { type Msg; fn handle(&mut self, msg: Self::Msg); }
+//
+//   - state is owned by one thread/task, so no Mutex is needed
+//   - ActorRef<Msg> is the only way in: cheap to clone, typed, bounded
+//   - request/response: the message carries a oneshot Reply<T>
+//       let n = counter.ask(CounterMsg::Get)?;
+//   - a full mailbox blocks send() (backpressure) or fails try_send()
+//
+// Supervision: a panic in handle() is caught, and the supervisor applies the
+// actor's strategy:
+//   Stop     — the actor ends; later sends fail with ActorError::Stopped
+//   Resume   — drop the bad message, keep the state
+//   Restart  — build fresh state from the factory; same mailbox, same ActorRefs
+// Intensity limit: more than `max_restarts` within `within` → give up and stop
+// (a crash loop is a bug to surface, not to hide).
+//
+// Two backends, same Actor trait:
+//   threaded — one OS thread per actor, std::sync::mpsc::sync_channel mailbox
+//   tasks    — one tokio task per actor, tokio::sync::mpsc mailbox, async send/ask
+// handle() is synchronous in both: keep it short and non-blocking under tokio.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+// --- Actor trait and shared types ---
+
+trait Actor: Send + 'static {
+    type Msg: Send + 'static;
+
+    fn handle(&mut self, msg: Self::Msg);
+
+    fn started(&mut self) {}
+    fn stopped(&mut self) {}
+}
+
+// Reply slot carried inside a request message
+type Reply<T> = oneshot::Sender<T>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ActorError {
+    MailboxFull, // try_send only
+    Stopped,     // the actor has exited
+    NoReply,     // the actor dropped the reply (e.g. it panicked mid-request)
+}
+
+impl fmt::Display for ActorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActorError::MailboxFull => write!(f, "mailbox full"),
+            ActorError::Stopped => write!(f, "actor stopped"),
+            ActorError::NoReply => write!(f, "actor dropped the reply"),
+        }
+    }
+}
+
+impl std::error::Error for ActorError {}
+
+// --- Supervision ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RestartStrategy {
+    Stop,
+    Resume,
+    Restart,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Started(String),
+    Resumed { actor: String, reason: String },
+    Restarted { actor: String, reason: String },
+    GaveUp { actor: String, reason: String },
+    Stopped(String),
+}
+
+#[derive(Clone)]
+struct Supervisor {
+    strategy: RestartStrategy,
+    max_restarts: usize,
+    within: Duration,
+    log: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Supervisor {
+    fn new(strategy: RestartStrategy) -> Self {
+        Supervisor {
+            strategy,
+            max_restarts: 3,
+            within: Duration::from_secs(5),
+            log: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn intensity(mut self, max_restarts: usize, within: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.within = within;
+        self
+    }
+
+    fn events(&self) -> Vec<Event> {
+        self.log.lock().unwrap().clone()
+    }
+
+    fn record(&self, event: Event) {
+        self.log.lock().unwrap().push(event);
+    }
+
+    fn supervise<A: Actor>(
+        &self,
+        name: &str,
+        factory: impl Fn() -> A + Send + 'static,
+    ) -> Supervised<A> {
+        let mut actor = factory();
+        actor.started();
+        self.record(Event::Started(name.to_string()));
+        Supervised {
+            name: name.to_string(),
+            actor,
+            factory: Box::new(factory),
+            supervisor: self.clone(),
+            recent: VecDeque::new(),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic>".to_string()
+    }
+}
+
+// One actor plus its supervision state; both backends drive it
+struct Supervised<A: Actor> {
+    name: String,
+    actor: A,
+    factory: Box<dyn Fn() -> A + Send>,
+    supervisor: Supervisor,
+    recent: VecDeque<Instant>, // restart times inside the intensity window
+}
+
+impl<A: Actor> Supervised<A> {
+    // Handle one message; false means the actor must stop
+    fn deliver(&mut self, msg: A::Msg) -> bool {
+        let actor = &mut self.actor;
+        let reason = match panic::catch_unwind(AssertUnwindSafe(|| actor.handle(msg))) {
+            Ok(()) => return true,
+            Err(payload) => panic_message(payload),
+        };
+        let sup = &self.supervisor;
+        let actor = self.name.clone();
+        if sup.strategy == RestartStrategy::Stop {
+            sup.record(Event::GaveUp { actor, reason });
+            return false;
+        }
+
+        let now = Instant::now();
+        while self
+            .recent
+            .front()
+            .is_some_and(|&t| now.duration_since(t) > sup.within)
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= sup.max_restarts {
+            sup.record(Event::GaveUp { actor, reason });
+            return false;
+        }
+        self.recent.push_back(now);
+
+        if sup.strategy == RestartStrategy::Restart {
+            // The old state may be half-updated: replace it, don't stop() it
+            self.actor = (self.factory)();
+            self.actor.started();
+            sup.record(Event::Restarted { actor, reason });
+        } else {
+            sup.record(Event::Resumed { actor, reason });
+        }
+        true
+    }
+
+    fn finish(mut self) {
+        self.actor.stopped();
+        self.supervisor.record(Event::Stopped(self.name));
+    }
+}
+
+// --- Backend 1: one OS thread per actor ---
+
+mod threaded {
+    use super::*;
+    use std::sync::mpsc::{self, SyncSender, TrySendError};
+    use std::thread::{self, JoinHandle};
+
+    pub(super) struct ActorRef<M> {
+        tx: SyncSender<M>,
+    }
+
+    // Manual impl: a derive would require M: Clone
+    impl<M> Clone for ActorRef<M> {
+        fn clone(&self) -> Self {
+            ActorRef {
+                tx: self.tx.clone(),
+            }
+        }
+    }
+
+    impl<M: Send + 'static> ActorRef<M> {
+        // Blocks while the mailbox is full
+        pub(super) fn send(&self, msg: M) -> Result<(), ActorError> {
+            self.tx.send(msg).map_err(|_| ActorError::Stopped)
+        }
+
+        pub(super) fn try_send(&self, msg: M) -> Result<(), ActorError> {
+            self.tx.try_send(msg).map_err(|e| match e {
+                TrySendError::Full(_) => ActorError::MailboxFull,
+                TrySendError::Disconnected(_) => ActorError::Stopped,
+            })
+        }
+
+        // `make` builds the request around a fresh reply slot
+        pub(super) fn ask<R>(&self, make: impl FnOnce(Reply<R>) -> M) -> Result<R, ActorError> {
+            let (reply, answer) = oneshot::channel();
+            self.send(make(reply))?;
+            answer.blocking_recv().map_err(|_| ActorError::NoReply)
+        }
+    }
+
+    impl Supervisor {
+        // The thread exits when every ActorRef is dropped or the supervisor gives up
+        pub(super) fn spawn_thread<A: Actor>(
+            &self,
+            name: &str,
+            capacity: usize,
+            factory: impl Fn() -> A + Send + 'static,
+        ) -> (ActorRef<A::Msg>, JoinHandle<()>) {
+            let (tx, rx) = mpsc::sync_channel(capacity);
+            let sup = self.clone();
+            let name = name.to_string();
+            let handle = thread::Builder::new()
+                .name(name.clone())
+                .spawn(move || {
+                    let mut actor = sup.supervise(&name, factory);
+                    for msg in rx {
+                        if !actor.deliver(msg) {
+                            return; // mailbox dropped here: senders see Stopped
+                        }
+                    }
+                    actor.finish();
+                })
+                .expect("failed to spawn actor thread");
+            (ActorRef { tx }, handle)
+        }
+    }
+}
+
+// --- Backend 2: one tokio task per actor ---
+
+mod tasks {
+    use super::*;
+    use tokio::sync::mpsc::{self, error::TrySendError, Sender};
+    use tokio::task::JoinHandle;
+
+    pub(super) struct ActorRef<M> {
+        tx: Sender<M>,
+    }
+
+    impl<M> Clone for ActorRef<M> {
+        fn clone(&self) -> Self {
+            ActorRef {
+                tx: self.tx.clone(),
+            }
+        }
+    }
+
+    impl<M: Send + 'static> ActorRef<M> {
+        // Waits (without blocking the thread) while the mailbox is full
+        pub(super) async fn send(&self, msg: M) -> Result<(), ActorError> {
+            self.tx.send(msg).await.map_err(|_| ActorError::Stopped)
+        }
+
+        pub(super) fn try_send(&self, msg: M) -> Result<(), ActorError> {
+            self.tx.try_send(msg).map_err(|e| match e {
+                TrySendError::Full(_) => ActorError::MailboxFull,
+                TrySendError::Closed(_) => ActorError::Stopped,
+            })
+        }
+
+        pub(super) async fn ask<R>(
+            &self,
+            make: impl FnOnce(Reply<R>) -> M,
+        ) -> Result<R, ActorError> {
+            let (reply, answer) = oneshot::channel();
+            self.send(make(reply)).await?;
+            answer.await.map_err(|_| ActorError::NoReply)
+        }
+    }
+
+    impl Supervisor {
+        pub(super) fn spawn_task<A: Actor>(
+            &self,
+            name: &str,
+            capacity: usize,
+            factory: impl Fn() -> A + Send + 'static,
+        ) -> (ActorRef<A::Msg>, JoinHandle<()>) {
+            let (tx, mut rx) = mpsc::channel(capacity);
+            let sup = self.clone();
+            let name = name.to_string();
+            let handle = tokio::spawn(async move {
+                let mut actor = sup.supervise(&name, factory);
+                while let Some(msg) = rx.recv().await {
+                    if !actor.deliver(msg) {
+                        return;
+                    }
+                }
+                actor.finish();
+            });
+            (ActorRef { tx }, handle)
+        }
+    }
+}
+
+// --- Example actors ---
+
+enum CounterMsg {
+    Add(i64),
+    Get(Reply<i64>),
+    Crash, // simulates a bug
+}
+
+struct Counter {
+    total: i64,
+}
+
+impl Actor for Counter {
+    type Msg = CounterMsg;
+
+    fn handle(&mut self, msg: CounterMsg) {
+        match msg {
+            CounterMsg::Add(n) => self.total += n,
+            CounterMsg::Get(reply) => {
+                let _ = reply.send(self.total); // asker may have given up
+            }
+            CounterMsg::Crash => panic!("counter corrupted at {}", self.total),
+        }
+    }
+}
+
+// A key-value store; Get replies with an Option
+enum KvMsg {
+    Put(String, String),
+    Get(String, Reply<Option<String>>),
+    Len(Reply<usize>),
+}
+
+#[derive(Default)]
+struct KvStore {
+    map: std::collections::HashMap<String, String>,
+}
+
+impl Actor for KvStore {
+    type Msg = KvMsg;
+
+    fn handle(&mut self, msg: KvMsg) {
+        match msg {
+            KvMsg::Put(k, v) => {
+                self.map.insert(k, v);
+            }
+            KvMsg::Get(k, reply) => {
+                let _ = reply.send(self.map.get(&k).cloned());
+            }
+            KvMsg::Len(reply) => {
+                let _ = reply.send(self.map.len());
+            }
+        }
+    }
+}
+
+fn thread_demo() {
+    // --- Request/response on a thread actor ---
+    let sup = Supervisor::new(RestartStrategy::Restart);
+    let (counter, handle) = sup.spawn_thread("counter", 16, || Counter { total: 0 });
+    for n in 1..=10 {
+        counter.send(CounterMsg::Add(n)).unwrap();
+    }
+    println!("{:?}", counter.ask(CounterMsg::Get)); // Ok(55)
+
+    // Messages are handled one at a time, so clones can share it without locks
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let counter = counter.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    counter.send(CounterMsg::Add(1)).unwrap();
+                }
+            })
+        })
+        .collect();
+    for w in workers {
+        w.join().unwrap();
+    }
+    println!("{:?}", counter.ask(CounterMsg::Get)); // Ok(455)
+
+    // try_send never blocks: Err(MailboxFull) when there's no room
+    println!("{:?}", counter.try_send(CounterMsg::Add(0))); // Ok(())
+
+    // --- Restart: a crash resets state, the ActorRef keeps working ---
+    counter.send(CounterMsg::Crash).unwrap();
+    println!("{:?}", counter.ask(CounterMsg::Get)); // Ok(0)
+
+    drop(counter); // last ref gone: the actor stops
+    handle.join().unwrap();
+    for event in sup.events() {
+        println!("{:?}", event);
+    }
+    // Started("counter")
+    // Restarted { actor: "counter", reason: "counter corrupted at 455" }
+    // Stopped("counter")
+}
+
+async fn tokio_demo() {
+    let sup = Supervisor::new(RestartStrategy::Resume).intensity(2, Duration::from_secs(1));
+    let (kv, _handle) = sup.spawn_task("kv", 8, KvStore::default);
+    kv.send(KvMsg::Put("lang".into(), "rust".into()))
+        .await
+        .unwrap();
+    kv.send(KvMsg::Put("year".into(), "2015".into()))
+        .await
+        .unwrap();
+    let lang = kv.ask(|r| KvMsg::Get("lang".into(), r)).await;
+    println!("{:?}", lang); // Ok(Some("rust"))
+    println!("{:?}", kv.ask(KvMsg::Len).await); // Ok(2)
+    println!("{:?}", kv.try_send(KvMsg::Put("os".into(), "linux".into()))); // Ok(())
+
+    // --- Intensity limit: Resume twice, then give up ---
+    let (counter, handle) = sup.spawn_task("flaky", 8, || Counter { total: 0 });
+    counter.send(CounterMsg::Add(7)).await.unwrap();
+    for _ in 0..3 {
+        counter.send(CounterMsg::Crash).await.unwrap();
+    }
+    handle.await.unwrap();
+    println!("{:?}", counter.ask(CounterMsg::Get).await); // Err(Stopped)
+    let failures = sup
+        .events()
+        .iter()
+        .filter(|e| matches!(e, Event::Resumed { .. } | Event::GaveUp { .. }))
+        .count();
+    println!("{}", failures); // 3  (Resumed, Resumed, GaveUp)
+}
+
+fn main() {
+    // Panics are caught by the supervisor; keep their messages off stderr
+    panic::set_hook(Box::new(|_| {}));
+
+    thread_demo();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(tokio_demo());
+
+    println!("actors done"); // actors done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Silence only the crashes these tests cause on purpose. Tests share one
+    // global hook and run in parallel, so anything else (an assertion failing
+    // in another test) still goes to the default hook.
+    fn quiet() {
+        static ONCE: std::sync::Once = std::sync::Once::new();
+        ONCE.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let msg = info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| info.payload().downcast_ref::<&str>().copied())
+                    .unwrap_or("");
+                if !(msg.starts_with("counter corrupted") || msg == "dropped reply") {
+                    previous(info);
+                }
+            }));
+        });
+    }
+
+    #[test]
+    fn thread_actor_handles_messages_in_order() {
+        enum LogMsg {
+            Push(u32),
+            Dump(Reply<Vec<u32>>),
+        }
+        struct Log(Vec<u32>);
+        impl Actor for Log {
+            type Msg = LogMsg;
+            fn handle(&mut self, msg: LogMsg) {
+                match msg {
+                    LogMsg::Push(n) => self.0.push(n),
+                    LogMsg::Dump(r) => {
+                        let _ = r.send(self.0.clone());
+                    }
+                }
+            }
+        }
+        let sup = Supervisor::new(RestartStrategy::Stop);
+        let (log, handle) = sup.spawn_thread("log", 2, || Log(Vec::new()));
+        for n in 0..50 {
+            log.send(LogMsg::Push(n)).unwrap(); // capacity 2: send waits for room
+        }
+        assert_eq!(log.ask(LogMsg::Dump).unwrap(), (0..50).collect::<Vec<_>>());
+        drop(log);
+        handle.join().unwrap();
+        assert_eq!(
+            sup.events(),
+            vec![Event::Started("log".into()), Event::Stopped("log".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn bounded_mailbox_rejects_try_send_when_full() {
+        let sup = Supervisor::new(RestartStrategy::Stop);
+        let (counter, _h) = sup.spawn_task("c", 2, || Counter { total: 0 });
+        // current_thread runtime: the actor task hasn't run yet
+        assert_eq!(counter.try_send(CounterMsg::Add(1)), Ok(()));
+        assert_eq!(counter.try_send(CounterMsg::Add(2)), Ok(()));
+        assert_eq!(
+            counter.try_send(CounterMsg::Add(3)),
+            Err(ActorError::MailboxFull)
+        );
+        assert_eq!(counter.ask(CounterMsg::Get).await, Ok(3));
+    }
+
+    #[test]
+    fn restart_resets_state_and_keeps_the_ref() {
+        quiet();
+        let sup = Supervisor::new(RestartStrategy::Restart);
+        let (counter, _h) = sup.spawn_thread("c", 8, || Counter { total: 100 });
+        counter.send(CounterMsg::Add(5)).unwrap();
+        counter.send(CounterMsg::Crash).unwrap();
+        assert_eq!(counter.ask(CounterMsg::Get), Ok(100));
+        assert!(sup.events().contains(&Event::Restarted {
+            actor: "c".into(),
+            reason: "counter corrupted at 105".into(),
+        }));
+    }
+
+    #[tokio::test]
+    async fn resume_keeps_state_and_failed_ask_gets_no_reply() {
+        quiet();
+        enum Msg {
+            Add(i64),
+            Risky(Reply<i64>),
+            Get(Reply<i64>),
+        }
+        struct Acc(i64);
+        impl Actor for Acc {
+            type Msg = Msg;
+            fn handle(&mut self, msg: Msg) {
+                match msg {
+                    Msg::Add(n) => self.0 += n,
+                    Msg::Risky(_reply) => panic!("dropped reply"),
+                    Msg::Get(r) => {
+                        let _ = r.send(self.0);
+                    }
+                }
+            }
+        }
+        let sup = Supervisor::new(RestartStrategy::Resume);
+        let (acc, _h) = sup.spawn_task("acc", 4, || Acc(0));
+        acc.send(Msg::Add(40)).await.unwrap();
+        assert_eq!(acc.ask(Msg::Risky).await, Err(ActorError::NoReply));
+        acc.send(Msg::Add(2)).await.unwrap();
+        assert_eq!(acc.ask(Msg::Get).await, Ok(42));
+    }
+
+    #[test]
+    fn intensity_limit_stops_a_crash_loop() {
+        quiet();
+        let sup = Supervisor::new(RestartStrategy::Restart).intensity(2, Duration::from_secs(60));
+        let (counter, handle) = sup.spawn_thread("loop", 8, || Counter { total: 0 });
+        for _ in 0..3 {
+            let _ = counter.send(CounterMsg::Crash);
+        }
+        handle.join().unwrap();
+        assert_eq!(counter.send(CounterMsg::Add(1)), Err(ActorError::Stopped));
+        let events = sup.events();
+        let restarts = events
+            .iter()
+            .filter(|e| matches!(e, Event::Restarted { .. }))
+            .count();
+        assert_eq!(restarts, 2);
+        assert!(matches!(events.last(), Some(Event::GaveUp { .. })));
+    }
+
+    #[test]
+    fn restarts_outside_the_window_do_not_count() {
+        quiet();
+        let sup = Supervisor::new(RestartStrategy::Restart).intensity(1, Duration::from_millis(20));
+        let (counter, _h) = sup.spawn_thread("slow-loop", 8, || Counter { total: 0 });
+        for _ in 0..3 {
+            counter.send(CounterMsg::Crash).unwrap();
+            std::thread::sleep(Duration::from_millis(40));
+        }
+        assert_eq!(counter.ask(CounterMsg::Get), Ok(0));
+    }
+
+    #[tokio::test]
+    async fn stop_strategy_ends_on_first_panic() {
+        quiet();
+        let sup = Supervisor::new(RestartStrategy::Stop);
+        let (counter, handle) = sup.spawn_task("fragile", 4, || Counter { total: 0 });
+        counter.send(CounterMsg::Crash).await.unwrap();
+        handle.await.unwrap();
+        assert_eq!(counter.ask(CounterMsg::Get).await, Err(ActorError::Stopped));
+        assert_eq!(sup.events().len(), 2); // Started, GaveUp — no Stopped hook
+    }
+}
@@ -15,6 +15,9 @@ use std::thread;
 
 fn main() {
     // --- AtomicUsize: lock-free counter ---
+    // (thread_pool.rs shows pool.scope, which avoids both the Arc and the per-job thread)
+    // (every thread hitting one atomic serializes on its cache line: see
+    //  expert/performance/striped_counter.rs for a contention-free counter)
     let counter = Arc::new(AtomicUsize::new(0));
     let mut handles = vec![];
 
@@ -60,8 +63,11 @@ fn main() {
     let result = n.compare_exchange(999, 300, Ordering::SeqCst, Ordering::Relaxed);
     println!("{:?}", result); // Err(200) — failed, returns current value
     println!("{}", n.load(Ordering::Relaxed)); // 200  — unchanged
+    // (CAS on pointers builds lock-free stacks and queues: see expert/unsafe_rust/lock_free.rs)
 
     // --- AtomicBool: flags and signals ---
+    // (a flag can't wake a sleeping thread or reach child tasks: see CancellationToken in
+    //  expert/async_programming/cancellation.rs)
     let running = Arc::new(AtomicBool::new(true));
     let running2 = Arc::clone(&running);
 
@@ -106,6 +112,7 @@ fn main() {
     // thread Y skipped — already initialized  (4 times)
 
     // --- fetch_max / fetch_min ---
+    // (to export values like this to a scraper, see expert/performance/metrics.rs)
     let peak = Arc::new(AtomicI32::new(0));
     let mut handles = vec![];
 
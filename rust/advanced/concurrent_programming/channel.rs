@@ -0,0 +1,652 @@
+// An MPMC channel family with timeouts and select!
+//
+// std::sync::mpsc (message_passing.rs) is multi-producer, *single*-consumer:
+// a worker pool has to share one Receiver behind Arc<Mutex<..>>, and a
+// thread can't wait on two receivers at once. This file builds channels
+// where both ends clone:
+//
+//   unbounded()      send never blocks (memory is the limit)
+//   bounded(cap)     send blocks while `cap` messages are queued (backpressure)
+//
+// Semantics, same as mpsc where it has an equivalent:
+//   - FIFO: messages leave in the order they went in, so one sender's
+//     messages are always received in its send order
+//   - every message is received by exactly one receiver
+//   - all Senders dropped  → receivers drain what's queued, then RecvError
+//   - all Receivers dropped → send returns the value in SendError
+//
+// select! waits on several receivers (of different message types) and runs
+// the branch of whichever is ready first. When several are ready it starts
+// scanning at a rotating position, so a busy channel can't starve the others.
+//
+// The core is one Mutex<VecDeque> with two Condvars per channel. That's
+// simpler than lock-free designs (see lock_free.rs) and plenty for
+// messages that carry real work.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// std's mpsc::SendTimeoutError is unstable, so we have our own
+#[derive(Debug, PartialEq)]
+enum SendTimeoutError<T> {
+    Timeout(T),
+    Disconnected(T),
+}
+
+// --- Shared channel state ---
+
+// Woken by any watched channel that gets a message or disconnects
+#[derive(Default)]
+struct Signal {
+    fired: Mutex<bool>,
+    cv: Condvar,
+}
+
+impl Signal {
+    fn fire(&self) {
+        *self.fired.lock().unwrap() = true;
+        self.cv.notify_all();
+    }
+
+    // false if the deadline passed first
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let mut fired = self.fired.lock().unwrap();
+        while !*fired {
+            match deadline {
+                None => fired = self.cv.wait(fired).unwrap(),
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        return false;
+                    }
+                    fired = self.cv.wait_timeout(fired, d - now).unwrap().0;
+                }
+            }
+        }
+        *fired = false;
+        true
+    }
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receivers: usize,
+    selectors: Vec<Arc<Signal>>, // select! calls waiting on this channel
+}
+
+struct Chan<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    cap: Option<usize>,
+}
+
+// How long a send/recv may block
+#[derive(Clone, Copy)]
+enum Wait {
+    Never,
+    Forever,
+    Until(Instant),
+}
+
+impl<T> Chan<T> {
+    // No user code runs under this lock, so it can't be poisoned
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+
+    // Ok(guard) after waiting, Err(guard) if the wait ran out
+    fn block<'a>(
+        &self,
+        cv: &Condvar,
+        st: MutexGuard<'a, State<T>>,
+        wait: Wait,
+    ) -> Result<MutexGuard<'a, State<T>>, MutexGuard<'a, State<T>>> {
+        match wait {
+            Wait::Never => Err(st),
+            Wait::Forever => Ok(cv.wait(st).unwrap()),
+            Wait::Until(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(st);
+                }
+                Ok(cv.wait_timeout(st, deadline - now).unwrap().0)
+            }
+        }
+    }
+
+    fn send(&self, value: T, wait: Wait) -> Result<(), SendTimeoutError<T>> {
+        let mut st = self.lock();
+        loop {
+            if st.receivers == 0 {
+                return Err(SendTimeoutError::Disconnected(value));
+            }
+            if self.cap.is_none_or(|cap| st.queue.len() < cap) {
+                break;
+            }
+            st = match self.block(&self.not_full, st, wait) {
+                Ok(st) => st,
+                Err(_) => return Err(SendTimeoutError::Timeout(value)),
+            };
+        }
+        st.queue.push_back(value);
+        self.not_empty.notify_one();
+        for s in &st.selectors {
+            s.fire();
+        }
+        Ok(())
+    }
+
+    fn recv(&self, wait: Wait) -> Result<T, RecvTimeoutError> {
+        let mut st = self.lock();
+        loop {
+            // Check the queue before the deadline: a wakeup that races a
+            // timeout still gets the message
+            if let Some(value) = st.queue.pop_front() {
+                self.not_full.notify_one();
+                return Ok(value);
+            }
+            if st.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            st = match self.block(&self.not_empty, st, wait) {
+                Ok(st) => st,
+                Err(_) => return Err(RecvTimeoutError::Timeout),
+            };
+        }
+    }
+}
+
+// --- Sender / Receiver ---
+
+struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+fn channel_with<T>(cap: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            senders: 1,
+            receivers: 1,
+            selectors: Vec::new(),
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        cap,
+    });
+    (
+        Sender {
+            chan: Arc::clone(&chan),
+        },
+        Receiver { chan },
+    )
+}
+
+fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    channel_with(None)
+}
+
+// Panics on 0: a rendezvous channel needs a different handshake
+fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "bounded channel needs a capacity of at least 1");
+    channel_with(Some(cap))
+}
+
+impl<T> Sender<T> {
+    // Blocks while a bounded channel is full
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.send(value, Wait::Forever).map_err(|e| match e {
+            SendTimeoutError::Disconnected(v) | SendTimeoutError::Timeout(v) => SendError(v),
+        })
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan.send(value, Wait::Never).map_err(|e| match e {
+            SendTimeoutError::Timeout(v) => TrySendError::Full(v),
+            SendTimeoutError::Disconnected(v) => TrySendError::Disconnected(v),
+        })
+    }
+
+    fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.chan.send(value, Wait::Until(Instant::now() + timeout))
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.lock().senders += 1;
+        Sender {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut st = self.chan.lock();
+        st.senders -= 1;
+        if st.senders == 0 {
+            // Wake every blocked receiver so it can see the disconnect
+            self.chan.not_empty.notify_all();
+            for s in &st.selectors {
+                s.fire();
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    fn recv(&self) -> Result<T, RecvError> {
+        self.chan.recv(Wait::Forever).map_err(|_| RecvError)
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.chan.recv(Wait::Never).map_err(|e| match e {
+            RecvTimeoutError::Timeout => TryRecvError::Empty,
+            RecvTimeoutError::Disconnected => TryRecvError::Disconnected,
+        })
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.chan.recv(Wait::Until(Instant::now() + timeout))
+    }
+
+    // Blocks for each message; ends once every Sender is gone
+    fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.lock().receivers += 1;
+        Receiver {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut st = self.chan.lock();
+        st.receivers -= 1;
+        if st.receivers == 0 {
+            self.chan.not_full.notify_all();
+        }
+    }
+}
+
+// --- select ---
+
+// Type-erased view of a Receiver, so one select can mix message types
+trait Selectable {
+    fn try_recv_any(&self) -> Result<Box<dyn Any + Send>, TryRecvError>;
+    // Returns true if a recv wouldn't block right now
+    fn watch(&self, signal: &Arc<Signal>) -> bool;
+    fn unwatch(&self, signal: &Arc<Signal>);
+}
+
+impl<T: Send + 'static> Selectable for Receiver<T> {
+    fn try_recv_any(&self) -> Result<Box<dyn Any + Send>, TryRecvError> {
+        self.try_recv().map(|v| Box::new(v) as Box<dyn Any + Send>)
+    }
+
+    fn watch(&self, signal: &Arc<Signal>) -> bool {
+        let mut st = self.chan.lock();
+        st.selectors.push(Arc::clone(signal));
+        !st.queue.is_empty() || st.senders == 0
+    }
+
+    fn unwatch(&self, signal: &Arc<Signal>) {
+        self.chan
+            .lock()
+            .selectors
+            .retain(|s| !Arc::ptr_eq(s, signal));
+    }
+}
+
+type Selected = (usize, Result<Box<dyn Any + Send>, RecvError>);
+
+// Index of the receiver that produced a message (or disconnected), or None
+// if the deadline passed first
+fn select_any(receivers: &[&dyn Selectable], deadline: Option<Instant>) -> Option<Selected> {
+    // Rotating start: with several channels ready, each gets its turn
+    static NEXT_START: AtomicUsize = AtomicUsize::new(0);
+    let start = NEXT_START.fetch_add(1, Ordering::Relaxed);
+    let n = receivers.len();
+    let signal = Arc::new(Signal::default());
+    loop {
+        for k in 0..n {
+            let i = (start + k) % n;
+            match receivers[i].try_recv_any() {
+                Ok(v) => return Some((i, Ok(v))),
+                Err(TryRecvError::Disconnected) => return Some((i, Err(RecvError))),
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        // Watch everything, then sleep unless something arrived between the
+        // scan and the watch. Another receiver may still beat us to a
+        // message after we wake; then we just scan again.
+        let ready = receivers
+            .iter()
+            .fold(false, |ready, r| r.watch(&signal) | ready);
+        let woke = ready || signal.wait(deadline);
+        for r in receivers {
+            r.unwatch(&signal);
+        }
+        if !woke {
+            return None;
+        }
+    }
+}
+
+// Recover the message type erased by select_any
+fn downcast_like<T: 'static>(_: &Receiver<T>, any: Box<dyn Any + Send>) -> T {
+    *any.downcast::<T>().expect("select!: message type mismatch")
+}
+
+// select! {
+//     recv(rx_a) -> msg => { ... },      msg: Result<A, RecvError>
+//     recv(rx_b) -> msg => { ... },      msg: Result<B, RecvError>
+//     timeout(duration) => { ... },      optional
+// }
+// Each `rx` names a Receiver (it's evaluated more than once). Branch
+// bodies run outside any internal loop, so break/continue/return in them
+// act on the caller's code.
+macro_rules! select {
+    ($(recv($rx:expr) -> $res:pat => $body:expr,)+ timeout($dur:expr) => $on_timeout:expr $(,)?) => {
+        select!(@run [$(recv($rx) -> $res => $body;)+] Some(Instant::now() + $dur), $on_timeout)
+    };
+    ($(recv($rx:expr) -> $res:pat => $body:expr),+ $(,)?) => {
+        select!(@run [$(recv($rx) -> $res => $body;)+] None, unreachable!())
+    };
+    (@run [$(recv($rx:expr) -> $res:pat => $body:expr;)+] $deadline:expr, $on_timeout:expr) => {{
+        let receivers: &[&dyn Selectable] = &[$(&$rx),+];
+        match select_any(receivers, $deadline) {
+            None => $on_timeout,
+            Some((ready, result)) => {
+                // `branch` counts the if-conditions evaluated so far
+                let mut branch = 0usize;
+                $(
+                    if { branch += 1; branch - 1 == ready } {
+                        let $res = result.map(|any| downcast_like(&$rx, any));
+                        $body
+                    } else
+                )+
+                { unreachable!() }
+            }
+        }
+    }};
+}
+
+fn main() {
+    use std::thread;
+
+    // --- Unbounded: like mpsc::channel ---
+    let (tx, rx) = unbounded();
+    tx.send("hello").unwrap();
+    tx.send("world").unwrap();
+    println!("{:?} {:?}", rx.recv(), rx.try_recv()); // Ok("hello") Ok("world")
+    println!("{:?}", rx.try_recv()); // Err(Empty)
+
+    // --- Bounded: backpressure ---
+    let (tx, rx) = bounded(2);
+    tx.send(1).unwrap();
+    tx.send(2).unwrap();
+    println!("{:?}", tx.try_send(3)); // Err(TrySendError::Full(..))
+    println!("{:?}", tx.send_timeout(3, Duration::from_millis(10))); // Err(Timeout(3))
+    println!("{:?}", rx.recv()); // Ok(1)
+    println!("{:?}", tx.try_send(3)); // Ok(())
+
+    // Disconnection: receivers drain what's queued, then see the end
+    drop(tx);
+    println!("{:?}", rx.iter().collect::<Vec<_>>()); // [2, 3]
+    println!("{:?}", rx.recv_timeout(Duration::from_millis(10))); // Err(Disconnected)
+
+    // --- Worker pool: no Arc<Mutex<Receiver>> needed ---
+    let (jobs, job_rx) = bounded::<u32>(4);
+    let (results, result_rx) = unbounded();
+    let workers: Vec<_> = (0..3)
+        .map(|id| {
+            let (job_rx, results) = (job_rx.clone(), results.clone());
+            thread::spawn(move || {
+                for n in job_rx.iter() {
+                    results.send((id, n * n)).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop((job_rx, results)); // only the workers hold these now
+    for n in 0..10 {
+        jobs.send(n).unwrap(); // blocks while 4 jobs are waiting
+    }
+    drop(jobs); // workers' iter() ends after the last job
+    for w in workers {
+        w.join().unwrap();
+    }
+    let mut squares: Vec<u32> = result_rx.iter().map(|(_, sq)| sq).collect();
+    squares.sort();
+    println!("{:?}", squares); // [0, 1, 4, 9, 16, 25, 36, 49, 64, 81]
+
+    // --- select!: whichever is ready first ---
+    let (num_tx, num_rx) = unbounded::<i32>();
+    let (text_tx, text_rx) = unbounded::<String>();
+    let producer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        text_tx.send("late text".to_string()).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        num_tx.send(7).unwrap();
+        text_tx // num_tx drops here; text_tx lives on
+    });
+    for _ in 0..3 {
+        select! {
+            recv(num_rx) -> n => println!("number {:?}", n),
+            recv(text_rx) -> s => println!("text {:?}", s),
+            timeout(Duration::from_millis(200)) => println!("timed out"),
+        }
+    }
+    // text Ok("late text")
+    // number Ok(7)
+    // number Err(RecvError)   ← num_tx is gone: the disconnect is "ready" too
+    drop(producer.join().unwrap());
+
+    // Timing out when nothing arrives
+    let (_keep, idle) = unbounded::<()>();
+    select! {
+        recv(idle) -> _ => println!("unexpected"),
+        timeout(Duration::from_millis(10)) => println!("idle timed out"), // idle timed out
+    }
+
+    println!("channel done"); // channel done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::thread;
+
+    #[test]
+    fn single_producer_order_is_preserved() {
+        let (tx, rx) = bounded(3);
+        let producer = thread::spawn(move || {
+            for i in 0..1_000 {
+                tx.send(i).unwrap();
+            }
+        });
+        let got: Vec<i32> = rx.iter().collect();
+        producer.join().unwrap();
+        assert_eq!(got, (0..1_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn mpmc_contention_delivers_each_message_once_in_per_sender_order() {
+        const PRODUCERS: usize = 4;
+        const PER: usize = 5_000;
+        let (tx, rx) = bounded::<(usize, usize)>(8);
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..PER {
+                        tx.send((p, i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        let consumers: Vec<_> = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || {
+                    let mut last = [None; PRODUCERS];
+                    let mut got = Vec::new();
+                    for (p, i) in rx.iter() {
+                        // Each consumer sees one producer's messages in order
+                        assert!(last[p] < Some(i), "out of order: {:?} then {}", last[p], i);
+                        last[p] = Some(i);
+                        got.push((p, i));
+                    }
+                    got
+                })
+            })
+            .collect();
+        drop(rx);
+        for p in producers {
+            p.join().unwrap();
+        }
+        let mut all = HashSet::new();
+        for c in consumers {
+            for msg in c.join().unwrap() {
+                assert!(all.insert(msg), "duplicate {:?}", msg);
+            }
+        }
+        assert_eq!(all.len(), PRODUCERS * PER);
+    }
+
+    #[test]
+    fn bounded_send_blocks_until_space() {
+        let (tx, rx) = bounded(1);
+        tx.send(1).unwrap();
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+        let start = Instant::now();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(30));
+            (rx.recv().unwrap(), rx) // keep rx alive: the sender needs a receiver
+        });
+        tx.send_timeout(2, Duration::from_secs(5)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(25));
+        let (first, rx) = t.join().unwrap();
+        assert_eq!(first, 1);
+        assert_eq!(rx.recv(), Ok(2));
+    }
+
+    #[test]
+    fn timeouts_expire() {
+        let (tx, rx) = bounded::<u8>(1);
+        let start = Instant::now();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(30)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        tx.send(1).unwrap();
+        assert_eq!(
+            tx.send_timeout(2, Duration::from_millis(30)),
+            Err(SendTimeoutError::Timeout(2))
+        );
+        assert!(start.elapsed() >= Duration::from_millis(55));
+    }
+
+    #[test]
+    fn disconnection_from_either_side() {
+        let (tx, rx) = unbounded();
+        let rx2 = rx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        assert_eq!(rx2.recv(), Ok(1)); // queued messages survive the senders
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        let (tx, rx) = bounded(1);
+        drop(rx);
+        assert_eq!(tx.send("lost"), Err(SendError("lost")));
+        assert_eq!(tx.try_send("lost"), Err(TrySendError::Disconnected("lost")));
+
+        // A blocked sender is released by the last receiver going away
+        let (tx, rx) = bounded(1);
+        tx.send(0).unwrap();
+        let t = thread::spawn(move || tx.send(1));
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(t.join().unwrap(), Err(SendError(1)));
+    }
+
+    #[test]
+    fn select_mixes_types_and_wakes_on_send() {
+        let (a_tx, a_rx) = unbounded::<u8>();
+        let (b_tx, b_rx) = unbounded::<&'static str>();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            b_tx.send("b").unwrap();
+            (a_tx, b_tx)
+        });
+        let got = select! {
+            recv(a_rx) -> a => format!("a {:?}", a),
+            recv(b_rx) -> b => format!("b {:?}", b),
+        };
+        assert_eq!(got, "b Ok(\"b\")");
+        let (a_tx, _b_tx) = t.join().unwrap();
+        drop(a_tx);
+        let got = select! {
+            recv(a_rx) -> a => a.is_err(),
+            recv(b_rx) -> _ => false,
+            timeout(Duration::from_secs(5)) => false,
+        };
+        assert!(got, "a disconnected channel is selectable");
+    }
+
+    #[test]
+    fn select_times_out() {
+        let (_tx, rx) = unbounded::<()>();
+        let start = Instant::now();
+        let timed_out = select! {
+            recv(rx) -> _ => false,
+            timeout(Duration::from_millis(30)) => true,
+        };
+        assert!(timed_out);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn select_is_fair_between_busy_channels() {
+        let (a_tx, a_rx) = unbounded();
+        let (b_tx, b_rx) = unbounded();
+        for _ in 0..1_000 {
+            a_tx.send(()).unwrap();
+            b_tx.send(()).unwrap();
+        }
+        let (mut a, mut b) = (0, 0);
+        for _ in 0..1_000 {
+            select! {
+                recv(a_rx) -> _ => a += 1,
+                recv(b_rx) -> _ => b += 1,
+            }
+        }
+        // Always-first scanning would give 1000/0
+        assert!(a > 300 && b > 300, "a={} b={}", a, b);
+    }
+}
@@ -0,0 +1,477 @@
+// Sharded concurrent HashMap
+//
+// Arc<Mutex<HashMap>> works, but every thread queues on the same lock even
+// when they touch different keys. Sharding splits the map into N independent
+// HashMaps, each behind its own RwLock, and picks the shard from the key's hash:
+//
+//   shard = hash(key) → top bits → shards[i]       (16 shards ≈ 16 lanes)
+//
+//   map.insert(k, v) / map.remove(&k)     — lock one shard for writing
+//   map.get(&k) -> Option<Ref<V>>         — read guard; derefs to &V
+//   map.entry(k).or_insert(0)             — atomic upsert under one write lock
+//   map.snapshot() / map.for_each(...)    — each shard seen consistently,
+//                                           but shards are visited one by one
+//   map.retain(|k, v| ...)                — shard by shard
+//
+// Trade-offs:
+//   - len() and snapshot() are not a point-in-time view of the *whole* map
+//   - holding a Ref/RefMut blocks writers to that shard (not the whole map);
+//     don't hold one while calling back into the same map
+//
+// dashmap is the production crate built on this idea.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::{self, HashMap, RandomState};
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct ConcurrentMap<K, V, S = RandomState> {
+    shards: Box<[RwLock<HashMap<K, V, S>>]>,
+    hasher: S,
+    shift: u32, // 64 - log2(shard count): top bits pick the shard
+}
+
+impl<K: Hash + Eq, V> ConcurrentMap<K, V> {
+    fn new() -> Self {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards((cores * 4).max(16))
+    }
+
+    fn with_shards(n: usize) -> Self {
+        let n = n.next_power_of_two();
+        let hasher = RandomState::new();
+        ConcurrentMap {
+            shards: (0..n)
+                .map(|_| RwLock::new(HashMap::with_hasher(hasher.clone())))
+                .collect(),
+            hasher,
+            shift: 64 - n.trailing_zeros(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> ConcurrentMap<K, V, S> {
+    // HashMap uses the low hash bits for buckets; the top bits are independent
+    fn shard_index<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        if self.shift == 64 {
+            return 0; // one shard (a shift by 64 would overflow)
+        }
+        (self.hasher.hash_one(key) >> self.shift) as usize
+    }
+
+    fn read<Q: Hash + ?Sized>(&self, key: &Q) -> RwLockReadGuard<'_, HashMap<K, V, S>> {
+        self.shards[self.shard_index(key)].read().unwrap()
+    }
+
+    fn write<Q: Hash + ?Sized>(&self, key: &Q) -> RwLockWriteGuard<'_, HashMap<K, V, S>> {
+        self.shards[self.shard_index(key)].write().unwrap()
+    }
+
+    fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write(&key).insert(key, value)
+    }
+
+    // Like HashMap, lookups take any borrowed form of the key (&str for String);
+    // Borrow guarantees it hashes the same, so it picks the same shard
+    fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write(key).remove(key)
+    }
+
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read(key).contains_key(key)
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<Ref<'_, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let guard = self.read(key);
+        let value: *const V = guard.get(key)?;
+        Some(Ref {
+            _guard: guard,
+            value,
+        })
+    }
+
+    fn get_mut<Q>(&self, key: &Q) -> Option<RefMut<'_, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut guard = self.write(key);
+        let value: *mut V = guard.get_mut(key)?;
+        Some(RefMut {
+            _guard: guard,
+            value,
+        })
+    }
+
+    // The shard stays write-locked until the Entry (or the RefMut it returns)
+    // is dropped, so check-then-insert can't race with another thread
+    fn entry(&self, key: K) -> Entry<'_, K, V, S> {
+        Entry {
+            guard: self.write(&key),
+            key,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.read().unwrap().len()).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.read().unwrap().is_empty())
+    }
+
+    // Visits every entry; holds one shard's read lock at a time
+    fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in self.shards.iter() {
+            let shard = shard.read().unwrap();
+            for (k, v) in shard.iter() {
+                f(k, v);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut out = Vec::new();
+        self.for_each(|k, v| out.push((k.clone(), v.clone())));
+        out
+    }
+
+    fn retain(&self, mut keep: impl FnMut(&K, &mut V) -> bool) {
+        for shard in self.shards.iter() {
+            shard.write().unwrap().retain(|k, v| keep(k, v));
+        }
+    }
+
+    #[cfg(test)]
+    fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+}
+
+// --- Guards ---
+
+// A read guard on one shard plus a pointer into it
+struct Ref<'a, K, V, S> {
+    _guard: RwLockReadGuard<'a, HashMap<K, V, S>>,
+    value: *const V,
+}
+
+impl<K, V, S> Deref for Ref<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // Safety: `value` points into the shard `_guard` keeps read-locked;
+        // nobody can move or drop it until the guard goes away
+        unsafe { &*self.value }
+    }
+}
+
+struct RefMut<'a, K, V, S> {
+    _guard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    value: *mut V,
+}
+
+impl<K, V, S> Deref for RefMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // Safety: as for Ref, with exclusive access via the write guard
+        unsafe { &*self.value }
+    }
+}
+
+impl<K, V, S> DerefMut for RefMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        unsafe { &mut *self.value }
+    }
+}
+
+struct Entry<'a, K, V, S> {
+    guard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    key: K,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
+    fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Some(v) = self.guard.get_mut(&self.key) {
+            f(v);
+        }
+        self
+    }
+
+    fn or_insert_with(mut self, make: impl FnOnce() -> V) -> RefMut<'a, K, V, S> {
+        let value: *mut V = match self.guard.entry(self.key) {
+            hash_map::Entry::Occupied(e) => e.into_mut(),
+            hash_map::Entry::Vacant(e) => e.insert(make()),
+        };
+        RefMut {
+            _guard: self.guard,
+            value,
+        }
+    }
+
+    fn or_insert(self, value: V) -> RefMut<'a, K, V, S> {
+        self.or_insert_with(|| value)
+    }
+
+    fn or_default(self) -> RefMut<'a, K, V, S>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+// --- Benchmark: one Mutex<HashMap> vs a sharded map ---
+
+const THREADS: usize = 8;
+const OPS: usize = 50_000;
+const KEYS: u64 = 1_000;
+
+// Mostly reads, some writes: the common shape of a shared cache
+fn key_for(thread: usize, i: usize) -> u64 {
+    (thread as u64 * 7919 + i as u64 * 104_729) % KEYS
+}
+
+fn bench_mutex() -> Duration {
+    let map = Arc::new(Mutex::new(HashMap::new()));
+    let start = Instant::now();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let map = Arc::clone(&map);
+            s.spawn(move || {
+                for i in 0..OPS {
+                    let key = key_for(t, i);
+                    let mut m = map.lock().unwrap();
+                    if i % 10 == 0 {
+                        *m.entry(key).or_insert(0u64) += 1;
+                    } else {
+                        std::hint::black_box(m.get(&key));
+                    }
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn bench_sharded() -> Duration {
+    let map = ConcurrentMap::new();
+    let start = Instant::now();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            s.spawn(move || {
+                for i in 0..OPS {
+                    let key = key_for(t, i);
+                    if i % 10 == 0 {
+                        *map.entry(key).or_insert(0u64) += 1;
+                    } else {
+                        std::hint::black_box(map.get(&key).map(|v| *v));
+                    }
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let map: ConcurrentMap<String, u32> = ConcurrentMap::new();
+
+    // --- Basic operations ---
+    map.insert("apples".to_string(), 3);
+    map.insert("pears".to_string(), 5);
+    println!("{:?}", map.insert("apples".to_string(), 4)); // Some(3)
+    println!("{}", *map.get("apples").unwrap()); // 4
+    println!("{}", map.contains_key("kiwis")); // false
+
+    if let Some(mut pears) = map.get_mut("pears") {
+        *pears += 10;
+    }
+    println!("{}", *map.get("pears").unwrap()); // 15
+
+    // --- entry: atomic upsert from many threads ---
+    let words = ["a", "b", "a", "c", "a", "b"];
+    let counts: ConcurrentMap<&str, usize> = ConcurrentMap::new();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for w in words {
+                    counts.entry(w).and_modify(|n| *n += 1).or_insert(1);
+                }
+            });
+        }
+    });
+    let mut snapshot = counts.snapshot();
+    snapshot.sort();
+    println!("{:?}", snapshot); // [("a", 12), ("b", 8), ("c", 4)]
+
+    // --- retain / remove / len ---
+    counts.retain(|_, n| *n > 5);
+    println!("{} {}", counts.len(), counts.is_empty()); // 2 false
+    println!("{:?}", counts.remove(&"a")); // Some(12)
+    *counts.entry("z").or_default() += 1;
+    let mut total = 0;
+    counts.for_each(|_, n| total += n);
+    println!("{}", total); // 9
+
+    // --- Benchmark (timings vary; sharding needs several cores to shine) ---
+    let mutex = bench_mutex();
+    let sharded = bench_sharded();
+    println!(
+        "{} threads × {} ops: Mutex<HashMap> {:?}, ConcurrentMap {:?}",
+        THREADS, OPS, mutex, sharded
+    );
+    // e.g. 8 threads × 50000 ops: Mutex<HashMap> 95ms, ConcurrentMap 18ms
+
+    println!("concurrent map done"); // concurrent map done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn behaves_like_a_hashmap() {
+        let map = ConcurrentMap::with_shards(4);
+        assert_eq!(map.shard_count(), 4);
+        for i in 0..1000 {
+            assert_eq!(map.insert(i, i * 2), None);
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(*map.get(&500).unwrap(), 1000);
+        assert_eq!(map.remove(&500), Some(1000));
+        assert!(map.get(&500).is_none());
+        map.retain(|k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+        assert_eq!(map.len(), 499);
+        assert_eq!(*map.get(&2).unwrap(), 5);
+
+        let single = ConcurrentMap::with_shards(1);
+        single.insert("only", 1);
+        assert_eq!(*single.get(&"only").unwrap(), 1);
+
+        // String keys are looked up by &str, without allocating
+        let names: ConcurrentMap<String, u32> = ConcurrentMap::new();
+        names.insert("ada".to_string(), 1);
+        *names.get_mut("ada").unwrap() += 1;
+        assert_eq!(*names.get("ada").unwrap(), 2);
+        assert!(names.contains_key("ada"));
+        assert_eq!(names.remove("ada"), Some(2));
+    }
+
+    #[test]
+    fn concurrent_upserts_lose_nothing() {
+        let map = ConcurrentMap::new();
+        thread::scope(|s| {
+            for t in 0..8 {
+                let map = &map;
+                s.spawn(move || {
+                    for i in 0..2000 {
+                        *map.entry(i % 50).or_insert(0) += 1;
+                        if i % 100 == 0 {
+                            map.insert(1000 + t * 100 + i / 100, t);
+                        }
+                    }
+                });
+            }
+        });
+        for k in 0..50 {
+            assert_eq!(*map.get(&k).unwrap(), 8 * 2000 / 50);
+        }
+        assert_eq!(map.len(), 50 + 8 * 20);
+    }
+
+    #[test]
+    fn a_held_ref_blocks_only_its_own_shard() {
+        let map = Arc::new(ConcurrentMap::with_shards(16));
+        let a = 0u32;
+        // Find a key that lands in a different shard than `a`
+        let b = (1..)
+            .find(|k| map.shard_index(k) != map.shard_index(&a))
+            .unwrap();
+        let c = (1..)
+            .find(|k| map.shard_index(k) == map.shard_index(&a))
+            .unwrap();
+        map.insert(a, 1);
+
+        let held = map.get(&a).unwrap();
+        let (tx, rx) = mpsc::channel();
+        for key in [b, c] {
+            let (map, tx) = (Arc::clone(&map), tx.clone());
+            thread::spawn(move || {
+                map.insert(key, 2);
+                tx.send(key).unwrap();
+            });
+        }
+        // Other shard: proceeds while we hold the read guard
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(b));
+        // Same shard: waits for the guard
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        assert_eq!(*held, 1);
+        drop(held);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(c));
+    }
+
+    #[test]
+    fn entry_variants() {
+        let map: ConcurrentMap<&str, Vec<u32>> = ConcurrentMap::new();
+        map.entry("x").or_default().push(1);
+        map.entry("x").or_insert_with(|| vec![99]).push(2);
+        map.entry("y").and_modify(|v| v.push(0)).or_insert(vec![7]);
+        map.entry("y").and_modify(|v| v.push(8)).or_insert(vec![0]);
+        assert_eq!(*map.get(&"x").unwrap(), vec![1, 2]);
+        assert_eq!(*map.get(&"y").unwrap(), vec![7, 8]);
+    }
+
+    #[test]
+    fn snapshot_never_sees_a_half_updated_shard() {
+        // Two keys in one shard, both updated in a single retain() pass:
+        // a snapshot must see both old values or both new ones
+        let map = ConcurrentMap::with_shards(1);
+        map.insert("left", 50i32);
+        map.insert("right", 50i32);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..2000 {
+                    let delta = if i % 2 == 0 { 1 } else { -1 };
+                    map.retain(|k, v| {
+                        *v += if *k == "left" { delta } else { -delta };
+                        true
+                    });
+                }
+            });
+            for _ in 0..500 {
+                let total: i32 = map.snapshot().iter().map(|(_, v)| v).sum();
+                assert_eq!(total, 100);
+            }
+        });
+    }
+}
@@ -4,6 +4,8 @@ use std::time::Duration;
 
 // mpsc = multiple producer, single consumer
 // tx = transmitter (sender), rx = receiver
+// actors.rs wraps this wiring in typed actors with request/response and supervision
+// channel.rs adds multi-consumer channels, timeouts and select! over several receivers
 
 fn main() {
     // --- Basic channel ---
@@ -7,6 +7,7 @@ fn main() {
     // Arc<Mutex<T>> is the standard pattern for shared mutable state across threads
 
     let counter = Arc::new(Mutex::new(0));
+    // (spawning a thread per job is fine for a demo: see thread_pool.rs for real workloads)
     let mut handles = vec![];
 
     for _ in 0..10 {
@@ -65,6 +66,7 @@ fn main() {
     }
 
     // --- RwLock<T>: multiple readers OR one writer ---
+    // (one lock per structure serializes unrelated keys: concurrent_map.rs shards it)
     // More efficient than Mutex when reads are frequent and writes are rare
     let data = Arc::new(RwLock::new(vec![1, 2, 3, 4, 5]));
     let mut handles = vec![];
@@ -106,6 +108,7 @@ fn main() {
     // --- Deadlock warning ---
     // Never lock the same Mutex twice in the same thread — it will deadlock
     // Never acquire locks in different orders across threads — it will deadlock
+    // (TrackedMutex in tracked_locks.rs catches inconsistent orders in debug builds)
     // Keep lock scope as small as possible (drop early if needed)
 
     let m = Mutex::new(0);
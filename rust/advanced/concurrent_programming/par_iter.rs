@@ -0,0 +1,468 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dev-dependencies]
+//! proptest = "1"
+//! ```
+
+// Parallel iterators: par_iter().map().filter().collect()
+//
+// map_filter_fold.rs and iterators.rs build pipelines that run on one core.
+// The same pipeline can run on all cores if:
+//   - closures are Fn + Sync (shared between threads, no mutable captures)
+//   - the source can be split: a slice splits at any index for free
+//   - the combining step is associative: (a+b)+c == a+(b+c)
+//
+//   let total: u64 = data.par_iter().map(|x| x * x).filter(|x| x % 3 == 0).sum();
+//   let names: Vec<String> = users.par_iter().map(|u| u.name.clone()).collect();
+//
+// How it splits: the index range is cut into chunks of at least `min_len`
+// (default 1024), about 8 per core. Then about one scoped thread per core
+// (the calling thread is one of them) claims chunk indices from a shared
+// counter until none are left. The work is balanced as it runs: a thread
+// stuck on an expensive chunk just claims fewer, and the others take the rest.
+// So a 100-element Vec never spawns anything, and a huge one uses every core.
+//
+// collect() keeps the original order: each chunk collects its own Vec, stored
+// under its chunk index, and the chunks are concatenated left-to-right.
+// A panic in any chunk re-panics here.
+
+use std::iter::Sum;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const DEFAULT_MIN_LEN: usize = 1024;
+const CHUNKS_PER_CORE: usize = 8;
+
+// --- The trait every stage implements ---
+
+trait ParallelIterator: Sized + Sync {
+    type Item: Send;
+
+    // Length of the underlying slice, and the split threshold
+    fn base_len(&self) -> usize;
+    fn min_len(&self) -> usize;
+
+    // Sequentially feed the items that come from base[range] into `sink`
+    fn drive<S: FnMut(Self::Item)>(&self, range: Range<usize>, sink: &mut S);
+
+    // --- Adaptors ---
+
+    fn map<R: Send, F: Fn(Self::Item) -> R + Sync>(self, f: F) -> Map<Self, F> {
+        Map { inner: self, f }
+    }
+
+    fn filter<F: Fn(&Self::Item) -> bool + Sync>(self, pred: F) -> Filter<Self, F> {
+        Filter { inner: self, pred }
+    }
+
+    // --- Terminal operations ---
+
+    // The general form: `identity` starts each chunk, `fold_op` runs within
+    // a chunk, `combine` merges neighbouring chunks (left, right)
+    fn fold<A, ID, F, C>(&self, identity: ID, fold_op: F, combine: C) -> A
+    where
+        A: Send,
+        ID: Fn() -> A + Sync,
+        F: Fn(A, Self::Item) -> A + Sync,
+        C: Fn(A, A) -> A + Sync,
+    {
+        let leaf = |range: Range<usize>| {
+            let mut acc = Some(identity());
+            self.drive(range, &mut |item| {
+                acc = Some(fold_op(acc.take().unwrap(), item));
+            });
+            acc.unwrap()
+        };
+        run_chunked(self.base_len(), self.min_len(), &leaf, &combine)
+    }
+
+    // `op` must be associative; `identity` must be its neutral element
+    fn reduce<ID, OP>(&self, identity: ID, op: OP) -> Self::Item
+    where
+        ID: Fn() -> Self::Item + Sync,
+        OP: Fn(Self::Item, Self::Item) -> Self::Item + Sync,
+    {
+        self.fold(&identity, &op, &op)
+    }
+
+    fn sum<S>(&self) -> S
+    where
+        S: Sum<Self::Item> + Sum<S> + Send,
+    {
+        self.fold(
+            || std::iter::empty::<Self::Item>().sum(),
+            |acc: S, x| [acc, std::iter::once(x).sum()].into_iter().sum(),
+            |a, b| [a, b].into_iter().sum(),
+        )
+    }
+
+    fn count(&self) -> usize {
+        self.fold(|| 0, |n, _| n + 1, |a, b| a + b)
+    }
+
+    // Order-preserving: same order as the sequential pipeline
+    fn collect_vec(&self) -> Vec<Self::Item> {
+        self.fold(
+            Vec::new,
+            |mut v, x| {
+                v.push(x);
+                v
+            },
+            |mut left, mut right| {
+                left.append(&mut right);
+                left
+            },
+        )
+    }
+
+    fn collect<C: FromIterator<Self::Item>>(&self) -> C {
+        self.collect_vec().into_iter().collect()
+    }
+
+    fn for_each<F: Fn(Self::Item) + Sync>(&self, f: F) {
+        self.fold(|| (), |(), x| f(x), |(), ()| ())
+    }
+}
+
+// Run `leaf` on every chunk of 0..len, spread over the cores, and combine
+// the results in index order
+fn run_chunked<A, L, C>(len: usize, min_len: usize, leaf: &L, combine: &C) -> A
+where
+    A: Send,
+    L: Fn(Range<usize>) -> A + Sync,
+    C: Fn(A, A) -> A + Sync,
+{
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = len.div_ceil(cores * CHUNKS_PER_CORE).max(min_len.max(1));
+    let chunks = len.div_ceil(chunk);
+    let threads = cores.min(chunks);
+    if threads <= 1 {
+        return leaf(0..len);
+    }
+
+    // Each thread claims the next unclaimed chunk until none are left
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut done = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            if i >= chunks {
+                return done;
+            }
+            done.push((i, leaf(i * chunk..((i + 1) * chunk).min(len))));
+        }
+    };
+
+    let mut results: Vec<Option<A>> = (0..chunks).map(|_| None).collect();
+    thread::scope(|s| {
+        let helpers: Vec<_> = (1..threads).map(|_| s.spawn(work)).collect();
+        let mine = work();
+        for (i, a) in helpers
+            .into_iter()
+            // Re-raise the chunk's own panic rather than a generic "thread panicked"
+            .flat_map(|h| h.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+            .chain(mine)
+        {
+            results[i] = Some(a);
+        }
+    });
+    // Whoever ran them, neighbours are combined in input order
+    results
+        .into_iter()
+        .map(Option::unwrap)
+        .reduce(combine)
+        .unwrap()
+}
+
+// --- Source: a borrowed slice ---
+
+struct ParIter<'a, T> {
+    slice: &'a [T],
+    min_len: usize,
+}
+
+impl<T> ParIter<'_, T> {
+    // Pieces smaller than this run sequentially (1 = split as far as possible)
+    fn with_min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len.max(1);
+        self
+    }
+}
+
+impl<'a, T: Sync> ParallelIterator for ParIter<'a, T> {
+    type Item = &'a T;
+
+    fn base_len(&self) -> usize {
+        self.slice.len()
+    }
+
+    fn min_len(&self) -> usize {
+        self.min_len
+    }
+
+    fn drive<S: FnMut(&'a T)>(&self, range: Range<usize>, sink: &mut S) {
+        self.slice[range].iter().for_each(sink);
+    }
+}
+
+// Vec<T> gets par_iter() too, through auto-deref to [T]
+trait IntoParallelRefIterator<T> {
+    fn par_iter(&self) -> ParIter<'_, T>;
+}
+
+impl<T: Sync> IntoParallelRefIterator<T> for [T] {
+    fn par_iter(&self) -> ParIter<'_, T> {
+        ParIter {
+            slice: self,
+            min_len: DEFAULT_MIN_LEN,
+        }
+    }
+}
+
+// --- Adaptors ---
+
+struct Map<P, F> {
+    inner: P,
+    f: F,
+}
+
+impl<P, F, R> ParallelIterator for Map<P, F>
+where
+    P: ParallelIterator,
+    F: Fn(P::Item) -> R + Sync,
+    R: Send,
+{
+    type Item = R;
+
+    fn base_len(&self) -> usize {
+        self.inner.base_len()
+    }
+
+    fn min_len(&self) -> usize {
+        self.inner.min_len()
+    }
+
+    fn drive<S: FnMut(R)>(&self, range: Range<usize>, sink: &mut S) {
+        self.inner.drive(range, &mut |x| sink((self.f)(x)));
+    }
+}
+
+struct Filter<P, F> {
+    inner: P,
+    pred: F,
+}
+
+impl<P, F> ParallelIterator for Filter<P, F>
+where
+    P: ParallelIterator,
+    F: Fn(&P::Item) -> bool + Sync,
+{
+    type Item = P::Item;
+
+    fn base_len(&self) -> usize {
+        self.inner.base_len()
+    }
+
+    fn min_len(&self) -> usize {
+        self.inner.min_len()
+    }
+
+    fn drive<S: FnMut(P::Item)>(&self, range: Range<usize>, sink: &mut S) {
+        self.inner.drive(range, &mut |x| {
+            if (self.pred)(&x) {
+                sink(x)
+            }
+        });
+    }
+}
+
+// --- Example ---
+
+fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    (2..)
+        .take_while(|d| d * d <= n)
+        .all(|d| !n.is_multiple_of(d))
+}
+
+fn main() {
+    let numbers: Vec<u64> = (1..=10).collect();
+
+    // Small input: below min_len, runs sequentially on this thread
+    let doubled: Vec<u64> = numbers.par_iter().map(|&x| x * 2).collect();
+    println!("{:?}", doubled); // [2, 4, 6, 8, 10, 12, 14, 16, 18, 20]
+
+    let evens: Vec<&u64> = numbers.par_iter().filter(|x| **x % 2 == 0).collect();
+    println!("{:?}", evens); // [2, 4, 6, 8, 10]
+
+    let sum: u64 = numbers.par_iter().sum();
+    println!("{}", sum); // 55
+
+    let product = numbers.par_iter().map(|&x| x).reduce(|| 1, |a, b| a * b);
+    println!("{}", product); // 3628800
+
+    // fold with a different accumulator type: (count, total)
+    let (count, total) = numbers.par_iter().fold(
+        || (0usize, 0u64),
+        |(n, t), &x| (n + 1, t + x),
+        |(n1, t1), (n2, t2)| (n1 + n2, t1 + t2),
+    );
+    println!("{} {}", count, total); // 10 55
+
+    // --- Big input: split across cores, same answer as sequential ---
+    let big: Vec<u64> = (0..2_000_000).collect();
+    let start = std::time::Instant::now();
+    let seq: u64 = big
+        .iter()
+        .filter(|&&n| is_prime(n))
+        .map(|&n| n % 1000)
+        .sum();
+    let seq_time = start.elapsed();
+    let start = std::time::Instant::now();
+    let par: u64 = big
+        .par_iter()
+        .filter(|n| is_prime(**n))
+        .map(|&n| n % 1000)
+        .sum();
+    let par_time = start.elapsed();
+    println!("{}", seq == par); // true
+    println!(
+        "primes below 2M: {}",
+        big.par_iter().filter(|n| is_prime(**n)).count()
+    ); // primes below 2M: 148933
+
+    // Order is preserved even when chunks finish out of order
+    let squares: Vec<u64> = big.par_iter().map(|&x| x * x).collect();
+    println!("{}", squares.windows(2).all(|w| w[0] < w[1])); // true
+
+    // for_each for side effects; with_min_len tunes the split threshold
+    let hits = std::sync::atomic::AtomicUsize::new(0);
+    big.par_iter().with_min_len(10_000).for_each(|&n| {
+        if n % 100_000 == 0 {
+            hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    });
+    println!("{}", hits.into_inner()); // 20
+
+    println!("sequential {:?}, parallel {:?}", seq_time, par_time);
+    // e.g. sequential 400ms, parallel 60ms (depends on core count)
+
+    println!("par iter done"); // par iter done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    #[test]
+    fn small_inputs_stay_on_the_calling_thread() {
+        let data: Vec<u32> = (0..100).collect();
+        let seen = Mutex::new(HashSet::new());
+        data.par_iter().for_each(|_| {
+            seen.lock().unwrap().insert(thread::current().id());
+        });
+        assert_eq!(
+            *seen.lock().unwrap(),
+            HashSet::from([thread::current().id()])
+        );
+    }
+
+    #[test]
+    fn large_inputs_use_several_threads() {
+        let data: Vec<u32> = (0..100_000).collect();
+        let seen = Mutex::new(HashSet::new());
+        data.par_iter().with_min_len(1000).for_each(|_| {
+            seen.lock().unwrap().insert(thread::current().id());
+        });
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        let threads = seen.lock().unwrap().len();
+        // ~100 chunks here, but never more threads than cores
+        assert!(threads >= cores.min(2) && threads <= cores, "{}", threads);
+    }
+
+    #[test]
+    fn panics_propagate_with_their_message() {
+        let data: Vec<u32> = (0..10_000).collect();
+        let r = std::panic::catch_unwind(|| {
+            data.par_iter().with_min_len(10).for_each(|&x| {
+                if x == 7777 {
+                    panic!("bad item");
+                }
+            })
+        });
+        assert_eq!(*r.unwrap_err().downcast::<&str>().unwrap(), "bad item");
+    }
+
+    #[test]
+    fn empty_input() {
+        let empty: Vec<i32> = Vec::new();
+        assert_eq!(empty.par_iter().sum::<i32>(), 0);
+        assert_eq!(empty.par_iter().count(), 0);
+        assert!(empty.par_iter().collect_vec().is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn map_filter_collect_matches_sequential(
+            data in proptest::collection::vec(any::<i32>(), 0..5000),
+            min_len in 1usize..300,
+        ) {
+            let seq: Vec<i64> = data
+                .iter()
+                .map(|&x| x as i64 * 3)
+                .filter(|x| x % 2 == 0)
+                .collect();
+            let par: Vec<i64> = data
+                .par_iter()
+                .with_min_len(min_len)
+                .map(|&x| x as i64 * 3)
+                .filter(|x| x % 2 == 0)
+                .collect();
+            prop_assert_eq!(par, seq);
+        }
+
+        #[test]
+        fn sum_count_reduce_match_sequential(
+            data in proptest::collection::vec(any::<i32>(), 0..5000),
+            min_len in 1usize..300,
+        ) {
+            let it = data.par_iter().with_min_len(min_len);
+            let wide = |x: &i32| *x as i64;
+            prop_assert_eq!(it.map(wide).sum::<i64>(), data.iter().map(wide).sum::<i64>());
+
+            let it = data.par_iter().with_min_len(min_len);
+            prop_assert_eq!(it.filter(|x| **x > 0).count(), data.iter().filter(|x| **x > 0).count());
+
+            let it = data.par_iter().with_min_len(min_len);
+            let max = it.map(|&x| x).reduce(|| i32::MIN, i32::max);
+            prop_assert_eq!(max, data.iter().copied().max().unwrap_or(i32::MIN));
+        }
+
+        #[test]
+        fn fold_with_non_commutative_combine_keeps_order(
+            data in proptest::collection::vec(0u8..26, 0..3000),
+            min_len in 1usize..200,
+        ) {
+            // String concatenation is associative but not commutative
+            let seq: String = data.iter().map(|&b| (b'a' + b) as char).collect();
+            let par = data.par_iter().with_min_len(min_len).fold(
+                String::new,
+                |mut s, &b| { s.push((b'a' + b) as char); s },
+                |a, b| a + &b,
+            );
+            prop_assert_eq!(par, seq);
+        }
+    }
+}
@@ -56,6 +56,7 @@ impl SafeCounter {
 }
 
 // SafeCounter is Send + Sync automatically because Mutex<i32> is Send + Sync
+// (for many shared values, a single Mutex becomes a bottleneck: see concurrent_map.rs)
 
 // --- A type that is NOT thread-safe (wraps Rc) ---
 // Can't be sent across threads — compiler enforces this
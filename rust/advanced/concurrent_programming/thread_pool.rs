@@ -0,0 +1,624 @@
+// Work-stealing thread pool: spawn, scope, join
+//
+// thread::spawn per unit of work (threading_basics.rs, mutex_rwlock.rs, ...)
+// costs an OS thread each time: ~10-20µs to create, plus a stack. A pool keeps
+// N threads alive and feeds them jobs.
+//
+// Work stealing: each worker owns a deque.
+//   - a worker pushes and pops its *own* jobs at the back (LIFO: cache-hot,
+//     and recursive join() finishes the newest half first)
+//   - an idle worker steals from the *front* of someone else's deque (FIFO:
+//     the oldest job is usually the biggest remaining chunk)
+//   - jobs spawned from outside the pool go to a shared injector queue
+//
+//   pool.spawn(|| ...)                         — 'static, fire-and-forget
+//   pool.scope(|s| { s.spawn(|| ...) })        — may borrow locals; waits for all
+//   let (a, b) = pool.join(|| left(), || right());  — fork-join recursion
+//
+// A panic inside scope or join is re-raised in the caller once every sibling
+// job has finished. Panics in detached spawn() jobs are counted and the
+// worker keeps going.
+//
+// Deques here are Mutex<VecDeque>: simple and correct. Production pools
+// (rayon, crossbeam-deque) use lock-free Chase-Lev deques for the same design.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+type Panic = Box<dyn Any + Send>;
+
+// Safety: the caller must not return until the job has run (or been dropped),
+// so nothing it borrows can dangle. scope() and join() wait for that.
+unsafe fn erase_lifetime<'a>(job: Box<dyn FnOnce() + Send + 'a>) -> Job {
+    std::mem::transmute::<Box<dyn FnOnce() + Send + 'a>, Job>(job)
+}
+
+// --- CountLatch: "wait until N things are done" ---
+
+struct CountLatch {
+    count: Mutex<usize>,
+    cv: Condvar,
+}
+
+impl CountLatch {
+    fn new(n: usize) -> Self {
+        CountLatch {
+            count: Mutex::new(n),
+            cv: Condvar::new(),
+        }
+    }
+
+    fn increment(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn decrement(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.cv.notify_all();
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        *self.count.lock().unwrap() == 0
+    }
+
+    fn wait_timeout(&self, d: Duration) {
+        let count = self.count.lock().unwrap();
+        drop(self.cv.wait_timeout_while(count, d, |c| *c > 0).unwrap());
+    }
+
+    fn wait(&self) {
+        let count = self.count.lock().unwrap();
+        drop(self.cv.wait_while(count, |c| *c > 0).unwrap());
+    }
+}
+
+// --- Shared pool state ---
+
+struct Registry {
+    injector: Mutex<VecDeque<Job>>,
+    deques: Vec<Mutex<VecDeque<Job>>>,
+    queued: AtomicUsize, // jobs sitting in any queue
+    sleep_lock: Mutex<()>,
+    wake: Condvar,
+    shutdown: AtomicBool,
+    steals: AtomicUsize,
+    detached_panics: AtomicUsize,
+}
+
+thread_local! {
+    // Set on pool threads: which registry, which deque is ours
+    static WORKER: RefCell<Option<(Arc<Registry>, usize)>> = const { RefCell::new(None) };
+}
+
+impl Registry {
+    fn current_index(self: &Arc<Self>) -> Option<usize> {
+        WORKER.with(|w| match &*w.borrow() {
+            Some((reg, index)) if Arc::ptr_eq(reg, self) => Some(*index),
+            _ => None,
+        })
+    }
+
+    // Worker threads push locally; everyone else uses the injector
+    fn push(self: &Arc<Self>, job: Job) {
+        // Count first: a thief that takes the job at once must not underflow
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        match self.current_index() {
+            Some(i) => self.deques[i].lock().unwrap().push_back(job),
+            None => self.injector.lock().unwrap().push_back(job),
+        }
+        // Lock so a worker between "nothing queued" and wait() can't miss this
+        let _g = self.sleep_lock.lock().unwrap();
+        self.wake.notify_one();
+    }
+
+    fn find_job(&self, index: Option<usize>) -> Option<Job> {
+        if let Some(i) = index {
+            if let Some(job) = self.deques[i].lock().unwrap().pop_back() {
+                return self.took(job);
+            }
+        }
+        if let Some(job) = self.injector.lock().unwrap().pop_front() {
+            return self.took(job);
+        }
+        // Steal round-robin, starting after ourselves so victims spread out
+        let n = self.deques.len();
+        let start = index.map_or(0, |i| i + 1);
+        for k in 0..n {
+            let victim = (start + k) % n;
+            if Some(victim) == index {
+                continue;
+            }
+            if let Some(job) = self.deques[victim].lock().unwrap().pop_front() {
+                self.steals.fetch_add(1, Ordering::Relaxed);
+                return self.took(job);
+            }
+        }
+        None
+    }
+
+    fn took(&self, job: Job) -> Option<Job> {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        Some(job)
+    }
+
+    // Block until `latch` is done. Pool threads keep running other jobs
+    // meanwhile — a worker that just blocked could deadlock the pool.
+    fn wait_for(self: &Arc<Self>, latch: &CountLatch) {
+        match self.current_index() {
+            Some(i) => {
+                while !latch.is_done() {
+                    match self.find_job(Some(i)) {
+                        Some(job) => job(),
+                        None => latch.wait_timeout(Duration::from_micros(50)),
+                    }
+                }
+            }
+            None => latch.wait(),
+        }
+    }
+
+    fn worker_loop(self: Arc<Self>, index: usize) {
+        WORKER.with(|w| *w.borrow_mut() = Some((Arc::clone(&self), index)));
+        loop {
+            if let Some(job) = self.find_job(Some(index)) {
+                job();
+                continue;
+            }
+            let guard = self.sleep_lock.lock().unwrap();
+            if self.queued.load(Ordering::SeqCst) > 0 {
+                continue; // something arrived while we searched
+            }
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            drop(self.wake.wait(guard).unwrap());
+        }
+        WORKER.with(|w| *w.borrow_mut() = None); // drop our Arc before exit
+    }
+}
+
+// --- ThreadPool ---
+
+struct ThreadPool {
+    registry: Arc<Registry>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    fn new(size: usize) -> Self {
+        assert!(size > 0, "pool needs at least one thread");
+        let registry = Arc::new(Registry {
+            injector: Mutex::new(VecDeque::new()),
+            deques: (0..size).map(|_| Mutex::new(VecDeque::new())).collect(),
+            queued: AtomicUsize::new(0),
+            sleep_lock: Mutex::new(()),
+            wake: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+            steals: AtomicUsize::new(0),
+            detached_panics: AtomicUsize::new(0),
+        });
+        let threads = (0..size)
+            .map(|i| {
+                let registry = Arc::clone(&registry);
+                thread::Builder::new()
+                    .name(format!("pool-worker-{}", i))
+                    .spawn(move || registry.worker_loop(i))
+                    .expect("failed to spawn pool thread")
+            })
+            .collect();
+        ThreadPool { registry, threads }
+    }
+
+    fn size(&self) -> usize {
+        self.threads.len()
+    }
+
+    fn steals(&self) -> usize {
+        self.registry.steals.load(Ordering::Relaxed)
+    }
+
+    fn detached_panics(&self) -> usize {
+        self.registry.detached_panics.load(Ordering::Relaxed)
+    }
+
+    fn spawn(&self, f: impl FnOnce() + Send + 'static) {
+        let registry = Arc::clone(&self.registry);
+        self.registry.push(Box::new(move || {
+            if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+                registry.detached_panics.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+    }
+
+    // Runs `b` on the pool (or inline, if nobody steals it) while this thread runs `a`
+    fn join<A, B, RA, RB>(&self, a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        let latch = Arc::new(CountLatch::new(1));
+        let slot: Arc<Mutex<Option<thread::Result<RB>>>> = Arc::new(Mutex::new(None));
+        let job = {
+            let (latch, slot) = (Arc::clone(&latch), Arc::clone(&slot));
+            Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(b));
+                *slot.lock().unwrap() = Some(result);
+                latch.decrement();
+            })
+        };
+        // Safety: we wait on `latch` below before returning, even if `a` panics
+        self.registry.push(unsafe { erase_lifetime(job) });
+
+        let ra = panic::catch_unwind(AssertUnwindSafe(a));
+        self.registry.wait_for(&latch);
+        let rb = slot
+            .lock()
+            .unwrap()
+            .take()
+            .expect("join job finished without a result");
+        match (ra, rb) {
+            (Ok(ra), Ok(rb)) => (ra, rb),
+            (Err(p), _) | (_, Err(p)) => panic::resume_unwind(p),
+        }
+    }
+
+    // Jobs spawned on the scope may borrow anything that outlives the call
+    fn scope<'env, R>(&self, f: impl FnOnce(&Scope<'env>) -> R) -> R {
+        let scope = Scope {
+            registry: Arc::clone(&self.registry),
+            state: Arc::new(ScopeState {
+                pending: CountLatch::new(0),
+                panic: Mutex::new(None),
+            }),
+            _env: PhantomData,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        // Wait even if `f` panicked: jobs may still borrow from 'env
+        self.registry.wait_for(&scope.state.pending);
+        let job_panic = scope.state.panic.lock().unwrap().take();
+        match (result, job_panic) {
+            (Err(p), _) | (Ok(_), Some(p)) => panic::resume_unwind(p),
+            (Ok(r), None) => r,
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    // Finishes queued jobs, then stops the workers
+    fn drop(&mut self) {
+        {
+            let _g = self.registry.sleep_lock.lock().unwrap();
+            self.registry.shutdown.store(true, Ordering::SeqCst);
+            self.registry.wake.notify_all();
+        }
+        for t in self.threads.drain(..) {
+            let _ = t.join();
+        }
+    }
+}
+
+struct ScopeState {
+    pending: CountLatch,
+    panic: Mutex<Option<Panic>>, // first panic wins
+}
+
+struct Scope<'env> {
+    registry: Arc<Registry>,
+    state: Arc<ScopeState>,
+    // Invariant in 'env, like std::thread::Scope
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'env> Scope<'env> {
+    fn spawn(&self, f: impl FnOnce() + Send + 'env) {
+        self.state.pending.increment();
+        let state = Arc::clone(&self.state);
+        let job = Box::new(move || {
+            if let Err(p) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                state.panic.lock().unwrap().get_or_insert(p);
+            }
+            state.pending.decrement();
+        });
+        // Safety: ThreadPool::scope waits for `pending` to reach zero
+        self.registry.push(unsafe { erase_lifetime(job) });
+    }
+}
+
+// --- Example workloads ---
+
+// Fork-join: split until small, then sum sequentially
+fn par_sum(pool: &ThreadPool, data: &[u64]) -> u64 {
+    if data.len() <= 1024 {
+        return data.iter().sum();
+    }
+    let (left, right) = data.split_at(data.len() / 2);
+    let (a, b) = pool.join(|| par_sum(pool, left), || par_sum(pool, right));
+    a + b
+}
+
+fn par_quicksort<T: Ord + Send>(pool: &ThreadPool, v: &mut [T]) {
+    if v.len() <= 512 {
+        v.sort();
+        return;
+    }
+    let mid = partition(v);
+    let (left, right) = v.split_at_mut(mid);
+    pool.join(
+        || par_quicksort(pool, left),
+        || par_quicksort(pool, &mut right[1..]),
+    );
+}
+
+// Lomuto partition around the middle element; returns the pivot's final index
+fn partition<T: Ord>(v: &mut [T]) -> usize {
+    let last = v.len() - 1;
+    v.swap(v.len() / 2, last);
+    let mut store = 0;
+    for i in 0..last {
+        if v[i] <= v[last] {
+            v.swap(i, store);
+            store += 1;
+        }
+    }
+    v.swap(store, last);
+    store
+}
+
+fn busy_work(seed: u64) -> u64 {
+    (0..2_000u64).fold(seed, |acc, x| acc.wrapping_mul(31).wrapping_add(x))
+}
+
+fn main() {
+    let pool = ThreadPool::new(4);
+    println!("{} workers", pool.size()); // 4 workers
+
+    // --- spawn: 'static jobs, results via a channel ---
+    let (tx, rx) = std::sync::mpsc::channel();
+    for i in 0..5u64 {
+        let tx = tx.clone();
+        pool.spawn(move || tx.send(i * i).unwrap());
+    }
+    drop(tx);
+    let mut squares: Vec<u64> = rx.iter().collect();
+    squares.sort();
+    println!("{:?}", squares); // [0, 1, 4, 9, 16]
+
+    // --- scope: borrow local data, no Arc needed ---
+    let mut data = vec![1u64; 1000];
+    let total = AtomicUsize::new(0);
+    pool.scope(|s| {
+        for (i, chunk) in data.chunks_mut(250).enumerate() {
+            let total = &total;
+            s.spawn(move || {
+                for x in chunk.iter_mut() {
+                    *x *= i as u64 + 1;
+                }
+                total.fetch_add(chunk.len(), Ordering::Relaxed);
+            });
+        }
+    });
+    println!("{} {} {}", data[0], data[999], total.into_inner()); // 1 4 1000
+
+    // --- join: recursive fork-join ---
+    let numbers: Vec<u64> = (1..=1_000_000).collect();
+    println!("{}", par_sum(&pool, &numbers)); // 500000500000
+
+    let mut v: Vec<u32> = (0..100_000u32)
+        .map(|i| i.wrapping_mul(2_654_435_761) % 1000)
+        .collect();
+    par_quicksort(&pool, &mut v);
+    println!("{}", v.windows(2).all(|w| w[0] <= w[1])); // true
+    println!("stolen jobs: {}", pool.steals() > 0); // stolen jobs: true
+
+    // --- Panics propagate to the caller of join/scope ---
+    // (the default hook still prints both panic messages to stderr)
+    let r = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.join(|| 1, || -> i32 { panic!("right half failed") })
+    }));
+    let msg = r.unwrap_err().downcast::<&str>().map(|s| *s);
+    println!("{:?}", msg); // Ok("right half failed")
+    pool.spawn(|| panic!("detached"));
+    while pool.detached_panics() == 0 {
+        thread::yield_now();
+    }
+    println!("detached panics: {}", pool.detached_panics()); // detached panics: 1
+    println!("{}", pool.join(|| 2, || 3).0 + 1); // 3  (pool still works)
+
+    // --- Benchmark: spawn-per-task vs pool (timings vary by machine) ---
+    let tasks = 2_000;
+    let start = Instant::now();
+    let handles: Vec<_> = (0..tasks)
+        .map(|i| thread::spawn(move || busy_work(i)))
+        .collect();
+    let naive: u64 = handles
+        .into_iter()
+        .map(|h| h.join().unwrap())
+        .fold(0, u64::wrapping_add);
+    let naive_time = start.elapsed();
+
+    let start = Instant::now();
+    let results: Vec<AtomicUsize> = (0..tasks).map(|_| AtomicUsize::new(0)).collect();
+    pool.scope(|s| {
+        for (i, slot) in results.iter().enumerate() {
+            s.spawn(move || slot.store(busy_work(i as u64) as usize, Ordering::Relaxed));
+        }
+    });
+    let pooled: u64 = results
+        .iter()
+        .map(|r| r.load(Ordering::Relaxed) as u64)
+        .fold(0, u64::wrapping_add);
+    let pool_time = start.elapsed();
+
+    println!("same results: {}", naive == pooled); // same results: true
+    println!("spawn-per-task: {:?}, pool: {:?}", naive_time, pool_time);
+    // e.g. spawn-per-task: 150ms, pool: 50ms
+
+    drop(pool); // finishes queued work, joins the threads
+    println!("thread pool done"); // thread pool done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Silence only the panics these tests cause on purpose. Tests share one
+    // global hook and run in parallel, so anything else (an assertion failing
+    // in another test) still goes to the default hook.
+    fn quiet() {
+        static ONCE: std::sync::Once = std::sync::Once::new();
+        ONCE.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let msg = info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| info.payload().downcast_ref::<&str>().copied())
+                    .unwrap_or("");
+                if !matches!(msg, "left failed" | "job 3 failed" | "oops") {
+                    previous(info);
+                }
+            }));
+        });
+    }
+
+    #[test]
+    fn spawned_jobs_all_run_before_drop_returns() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = ThreadPool::new(3);
+            for _ in 0..500 {
+                let counter = Arc::clone(&counter);
+                pool.spawn(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 500);
+    }
+
+    #[test]
+    fn scope_borrows_and_mutates_locals() {
+        let pool = ThreadPool::new(4);
+        let mut grid = vec![vec![0u32; 100]; 16];
+        let offset = 7; // borrowed by every job
+        pool.scope(|s| {
+            for (r, row) in grid.iter_mut().enumerate() {
+                s.spawn(move || {
+                    for (c, cell) in row.iter_mut().enumerate() {
+                        *cell = (r * 100 + c) as u32 + offset;
+                    }
+                });
+            }
+        });
+        assert_eq!(grid[15][99], 1599 + 7);
+        assert!(grid.iter().flatten().all(|&x| x >= 7));
+    }
+
+    #[test]
+    fn join_recursion_matches_sequential() {
+        let pool = ThreadPool::new(4);
+        let data: Vec<u64> = (0..200_000).map(|i| i * 3 % 1001).collect();
+        assert_eq!(par_sum(&pool, &data), data.iter().sum::<u64>());
+
+        let mut v: Vec<i64> = (0..50_000)
+            .map(|i: i64| (i * 7919) % 10_007 - 5000)
+            .collect();
+        let mut expected = v.clone();
+        expected.sort();
+        par_quicksort(&pool, &mut v);
+        assert_eq!(v, expected);
+    }
+
+    #[test]
+    fn idle_workers_steal() {
+        let pool = ThreadPool::new(4);
+        // All the forking happens on one worker's deque; others must steal
+        fn tree(pool: &ThreadPool, depth: u32) -> u32 {
+            if depth == 0 {
+                thread::sleep(Duration::from_millis(1));
+                return 1;
+            }
+            let (a, b) = pool.join(|| tree(pool, depth - 1), || tree(pool, depth - 1));
+            a + b
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        let pool = Arc::new(pool);
+        let p = Arc::clone(&pool);
+        pool.spawn(move || tx.send(tree(&p, 7)).unwrap());
+        assert_eq!(rx.recv().unwrap(), 128);
+        assert!(pool.steals() > 0);
+    }
+
+    #[test]
+    fn join_propagates_panic_after_both_sides_finish() {
+        let pool = ThreadPool::new(2);
+        let other_side_done = AtomicBool::new(false);
+        quiet();
+        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.join(
+                || panic!("left failed"),
+                || {
+                    thread::sleep(Duration::from_millis(20));
+                    other_side_done.store(true, Ordering::SeqCst);
+                },
+            )
+        }));
+        assert_eq!(*r.unwrap_err().downcast::<&str>().unwrap(), "left failed");
+        assert!(other_side_done.load(Ordering::SeqCst));
+        assert_eq!(pool.join(|| 1, || 2), (1, 2)); // pool still healthy
+    }
+
+    #[test]
+    fn scope_propagates_job_panic_and_waits_for_the_rest() {
+        let pool = ThreadPool::new(3);
+        let finished = AtomicUsize::new(0);
+        quiet();
+        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                s.spawn(|| panic!("job 3 failed"));
+                for _ in 0..10 {
+                    s.spawn(|| {
+                        thread::sleep(Duration::from_millis(2));
+                        finished.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+            })
+        }));
+        assert_eq!(*r.unwrap_err().downcast::<&str>().unwrap(), "job 3 failed");
+        assert_eq!(finished.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn detached_panics_are_counted_not_fatal() {
+        let pool = ThreadPool::new(2);
+        quiet();
+        for _ in 0..5 {
+            pool.spawn(|| panic!("oops"));
+        }
+        pool.scope(|_| {}); // no-op scope; then wait for the panics to land
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.detached_panics() < 5 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(pool.detached_panics(), 5);
+        assert_eq!(pool.join(|| "still", || "alive"), ("still", "alive"));
+    }
+}
@@ -35,6 +35,7 @@ fn main() {
     // println!("{:?}", data); // error: data moved into thread
 
     // --- Multiple threads ---
+    // (one OS thread per unit of work; thread_pool.rs reuses a fixed set instead)
     let mut handles = vec![];
 
     for i in 0..5 {
@@ -0,0 +1,809 @@
+// Tracked locks: catching lock-order deadlocks before they happen
+//
+// mutex_rwlock.rs warns "never acquire locks in different orders across
+// threads". With two locks that's easy to eyeball; with twenty it isn't,
+// and an ABBA deadlock only shows up when the timing is unlucky:
+//
+//   thread 1: lock(a) ... lock(b)        a → b
+//   thread 2: lock(b) ... lock(a)        b → a      ← both block forever
+//
+// TrackedMutex / TrackedRwLock are drop-in wrappers that, in debug builds,
+// record every "acquired B while holding A" as an edge A → B in one global
+// graph. Adding an edge that closes a cycle means some interleaving can
+// deadlock, even if this run didn't. We report it *before* blocking, with
+// the backtrace of this acquisition and of the earlier opposite one.
+// (This is what the Linux kernel's lockdep does.)
+//
+// They also time every critical section: per-lock hold-time histograms,
+// and a warning when a lock is held longer than a threshold.
+//
+// Release builds: TrackedMutex<T> *is* std::sync::Mutex<T> (a type alias),
+// so the checks cost nothing in production.
+
+#[cfg(debug_assertions)]
+use std::ops::{Deref, DerefMut};
+#[cfg(debug_assertions)]
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+// What to do when a lock-order cycle is found
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OnCycle {
+    Panic, // default: fail the test run that exercised the bad order
+    Log,   // eprintln and keep going (the cycle is reported once)
+}
+
+const BUCKETS: usize = 20;
+
+// Hold times for every lock created at one source location
+#[derive(Clone, Debug, Default)]
+struct HoldStats {
+    name: String,
+    count: u64,
+    total: Duration,
+    max: Duration,
+    long: u64,               // holds over the long-hold threshold
+    buckets: [u64; BUCKETS], // [0] < 1µs, [k] < 2^k µs, last = everything above
+}
+
+impl std::fmt::Display for HoldStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} holds, mean {:?}, max {:?}, {} long |",
+            self.name,
+            self.count,
+            self.total / self.count.max(1) as u32,
+            self.max,
+            self.long
+        )?;
+        for (k, n) in self.buckets.iter().enumerate().filter(|(_, n)| **n > 0) {
+            write!(f, " <{}µs:{}", 1u64 << k, n)?;
+        }
+        Ok(())
+    }
+}
+
+// --- Lock-order graph (debug builds) ---
+
+#[cfg(debug_assertions)]
+mod lockdep {
+    use super::{HoldStats, OnCycle, BUCKETS};
+    use std::backtrace::Backtrace;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+    use std::panic::Location;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static LOG_CYCLES: AtomicBool = AtomicBool::new(false);
+    pub(super) static LONG_HOLD_MICROS: AtomicU64 = AtomicU64::new(10_000);
+
+    struct Edge {
+        backtrace: Arc<Backtrace>,
+        thread: String,
+    }
+
+    #[derive(Default)]
+    struct Graph {
+        names: HashMap<usize, String>,
+        edges: HashMap<usize, HashMap<usize, Edge>>, // from → to → first sighting
+        stats: HashMap<String, HoldStats>,
+        reports: Vec<String>,
+    }
+
+    // The graph's own lock is a plain Mutex: tracking it would recurse
+    fn graph() -> MutexGuard<'static, Graph> {
+        static GRAPH: OnceLock<Mutex<Graph>> = OnceLock::new();
+        GRAPH
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    thread_local! {
+        // Ids of the tracked locks this thread holds, in acquisition order
+        static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn thread_name() -> String {
+        thread::current().name().unwrap_or("<unnamed>").to_string()
+    }
+
+    impl Graph {
+        fn name(&self, id: usize) -> &str {
+            self.names.get(&id).map_or("?", String::as_str)
+        }
+
+        // Path from → … → to along recorded edges, if there is one
+        fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+            let mut stack = vec![vec![from]];
+            let mut seen = HashSet::new();
+            while let Some(path) = stack.pop() {
+                let last = *path.last().unwrap();
+                if last == to {
+                    return Some(path);
+                }
+                if !seen.insert(last) {
+                    continue;
+                }
+                for &next in self.edges.get(&last).into_iter().flat_map(|m| m.keys()) {
+                    let mut longer = path.clone();
+                    longer.push(next);
+                    stack.push(longer);
+                }
+            }
+            None
+        }
+
+        fn describe_cycle(
+            &self,
+            held: usize,
+            id: usize,
+            path: &[usize],
+            now: &Backtrace,
+        ) -> String {
+            let chain: Vec<_> = path
+                .iter()
+                .map(|&n| format!("`{}`", self.name(n)))
+                .collect();
+            let mut msg = format!(
+                "lock order inversion: acquiring `{}` while holding `{}`, \
+                 but {} was seen before\n\
+                 --- this acquisition: `{}` -> `{}` (thread `{}`) ---\n{}",
+                self.name(id),
+                self.name(held),
+                chain.join(" -> "),
+                self.name(held),
+                self.name(id),
+                thread_name(),
+                now
+            );
+            for pair in path.windows(2) {
+                let edge = &self.edges[&pair[0]][&pair[1]];
+                msg += &format!(
+                    "\n--- earlier acquisition: `{}` -> `{}` (thread `{}`) ---\n{}",
+                    self.name(pair[0]),
+                    self.name(pair[1]),
+                    edge.thread,
+                    edge.backtrace
+                );
+            }
+            msg
+        }
+    }
+
+    // One per tracked lock: its node in the graph, removed on drop
+    pub(super) struct Registration {
+        id: usize,
+    }
+
+    impl Registration {
+        pub(super) fn new(site: &Location<'_>) -> Self {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let file = site.file().rsplit(['/', '\\']).next().unwrap_or("");
+            graph()
+                .names
+                .insert(id, format!("{}:{}", file, site.line()));
+            Registration { id }
+        }
+
+        // Called before a blocking acquire: this is where deadlocks are caught
+        pub(super) fn before_lock(&self) {
+            let id = self.id;
+            let held = HELD.with(|h| h.borrow().clone());
+            if held.contains(&id) {
+                let name = graph().name(id).to_string();
+                panic!("recursive lock: `{}` is already held by this thread", name);
+            }
+            let log = LOG_CYCLES.load(Ordering::Relaxed);
+            let mut g = graph();
+            let mut cycles = Vec::new();
+            let mut backtrace = None; // captured once, and only for new edges
+            for &from in &held {
+                if g.edges.get(&from).is_some_and(|m| m.contains_key(&id)) {
+                    continue; // known edge: the common, cheap case
+                }
+                let bt = backtrace
+                    .get_or_insert_with(|| Arc::new(Backtrace::force_capture()))
+                    .clone();
+                let cycle = g.path(id, from);
+                if let Some(path) = &cycle {
+                    cycles.push(g.describe_cycle(from, id, path, &bt));
+                }
+                // In panic mode leave the bad edge out, so every attempt fails
+                if cycle.is_none() || log {
+                    let edge = Edge {
+                        backtrace: bt,
+                        thread: thread_name(),
+                    };
+                    g.edges.entry(from).or_default().insert(id, edge);
+                }
+            }
+            if cycles.is_empty() {
+                return;
+            }
+            if log {
+                for msg in &cycles {
+                    eprintln!("{}", msg);
+                }
+                g.reports.extend(cycles);
+            } else {
+                drop(g); // don't poison the graph
+                panic!("{}", cycles.join("\n\n"));
+            }
+        }
+
+        pub(super) fn acquired(&self) -> Held {
+            HELD.with(|h| h.borrow_mut().push(self.id));
+            Held {
+                id: self.id,
+                since: Instant::now(),
+            }
+        }
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            let mut g = graph();
+            g.names.remove(&self.id);
+            g.edges.remove(&self.id);
+            for to in g.edges.values_mut() {
+                to.remove(&self.id);
+            }
+        }
+    }
+
+    // Lives inside each guard; records the hold time when the guard drops
+    pub(super) struct Held {
+        id: usize,
+        since: Instant,
+    }
+
+    impl Drop for Held {
+        fn drop(&mut self) {
+            let held_for = self.since.elapsed();
+            HELD.with(|h| {
+                let mut h = h.borrow_mut();
+                if let Some(i) = h.iter().rposition(|&x| x == self.id) {
+                    h.remove(i); // guards may drop out of order
+                }
+            });
+            let limit = Duration::from_micros(LONG_HOLD_MICROS.load(Ordering::Relaxed));
+            let mut g = graph();
+            let name = g.name(self.id).to_string();
+            let stats = g.stats.entry(name.clone()).or_insert_with(|| HoldStats {
+                name: name.clone(),
+                ..Default::default()
+            });
+            let micros = held_for.as_micros() as u64;
+            let bucket = (64 - micros.leading_zeros() as usize).min(BUCKETS - 1);
+            stats.buckets[bucket] += 1;
+            stats.count += 1;
+            stats.total += held_for;
+            stats.max = stats.max.max(held_for);
+            if held_for > limit {
+                stats.long += 1;
+                drop(g);
+                eprintln!(
+                    "long critical section: `{}` held for {:?} (threshold {:?})",
+                    name, held_for, limit
+                );
+            }
+        }
+    }
+
+    pub(super) fn set_on_cycle(mode: OnCycle) {
+        LOG_CYCLES.store(mode == OnCycle::Log, Ordering::Relaxed);
+    }
+
+    pub(super) fn hold_report() -> Vec<HoldStats> {
+        let mut all: Vec<_> = graph().stats.values().cloned().collect();
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        all
+    }
+
+    pub(super) fn take_cycle_reports() -> Vec<String> {
+        std::mem::take(&mut graph().reports)
+    }
+}
+
+// --- Configuration (no-ops in release builds) ---
+
+fn set_on_cycle(mode: OnCycle) {
+    #[cfg(debug_assertions)]
+    lockdep::set_on_cycle(mode);
+    #[cfg(not(debug_assertions))]
+    let _ = mode;
+}
+
+fn set_long_hold(threshold: Duration) {
+    #[cfg(debug_assertions)]
+    lockdep::LONG_HOLD_MICROS.store(
+        threshold.as_micros() as u64,
+        std::sync::atomic::Ordering::Relaxed,
+    );
+    #[cfg(not(debug_assertions))]
+    let _ = threshold;
+}
+
+fn hold_report() -> Vec<HoldStats> {
+    #[cfg(debug_assertions)]
+    return lockdep::hold_report();
+    #[cfg(not(debug_assertions))]
+    Vec::new()
+}
+
+fn take_cycle_reports() -> Vec<String> {
+    #[cfg(debug_assertions)]
+    return lockdep::take_cycle_reports();
+    #[cfg(not(debug_assertions))]
+    Vec::new()
+}
+
+// Re-wrap the guard inside a lock result, keeping the poison flag
+#[cfg(debug_assertions)]
+fn map_lock<G, H>(result: LockResult<G>, f: impl FnOnce(G) -> H) -> LockResult<H> {
+    match result {
+        Ok(g) => Ok(f(g)),
+        Err(poisoned) => Err(PoisonError::new(f(poisoned.into_inner()))),
+    }
+}
+
+#[cfg(debug_assertions)]
+fn map_try_lock<G, H>(result: TryLockResult<G>, f: impl FnOnce(G) -> H) -> TryLockResult<H> {
+    match result {
+        Ok(g) => Ok(f(g)),
+        Err(TryLockError::Poisoned(p)) => {
+            Err(TryLockError::Poisoned(PoisonError::new(f(p.into_inner()))))
+        }
+        Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+    }
+}
+
+// --- TrackedMutex ---
+
+#[cfg(not(debug_assertions))]
+type TrackedMutex<T> = Mutex<T>;
+#[cfg(not(debug_assertions))]
+type TrackedMutexGuard<'a, T> = MutexGuard<'a, T>;
+
+#[cfg(debug_assertions)]
+struct TrackedMutex<T> {
+    reg: lockdep::Registration,
+    inner: Mutex<T>,
+}
+
+#[cfg(debug_assertions)]
+struct TrackedMutexGuard<'a, T> {
+    inner: MutexGuard<'a, T>,
+    _held: lockdep::Held, // dropped after `inner`: the hold includes the unlock
+}
+
+#[cfg(debug_assertions)]
+impl<T> TrackedMutex<T> {
+    // The lock is named after the line that creates it
+    #[track_caller]
+    fn new(value: T) -> Self {
+        TrackedMutex {
+            reg: lockdep::Registration::new(std::panic::Location::caller()),
+            inner: Mutex::new(value),
+        }
+    }
+
+    fn lock(&self) -> LockResult<TrackedMutexGuard<'_, T>> {
+        self.reg.before_lock();
+        let result = self.inner.lock();
+        map_lock(result, |inner| TrackedMutexGuard {
+            inner,
+            _held: self.reg.acquired(),
+        })
+    }
+
+    // try_lock can't deadlock, so it adds no edges; it still counts as held
+    fn try_lock(&self) -> TryLockResult<TrackedMutexGuard<'_, T>> {
+        map_try_lock(self.inner.try_lock(), |inner| TrackedMutexGuard {
+            inner,
+            _held: self.reg.acquired(),
+        })
+    }
+
+    fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Deref for TrackedMutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> DerefMut for TrackedMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+// --- TrackedRwLock ---
+// Readers count as acquisitions too: a reader waiting behind a writer
+// deadlocks just the same
+
+#[cfg(not(debug_assertions))]
+type TrackedRwLock<T> = RwLock<T>;
+#[cfg(not(debug_assertions))]
+type TrackedReadGuard<'a, T> = RwLockReadGuard<'a, T>;
+#[cfg(not(debug_assertions))]
+type TrackedWriteGuard<'a, T> = RwLockWriteGuard<'a, T>;
+
+#[cfg(debug_assertions)]
+struct TrackedRwLock<T> {
+    reg: lockdep::Registration,
+    inner: RwLock<T>,
+}
+
+#[cfg(debug_assertions)]
+struct TrackedReadGuard<'a, T> {
+    inner: RwLockReadGuard<'a, T>,
+    _held: lockdep::Held,
+}
+
+#[cfg(debug_assertions)]
+struct TrackedWriteGuard<'a, T> {
+    inner: RwLockWriteGuard<'a, T>,
+    _held: lockdep::Held,
+}
+
+#[cfg(debug_assertions)]
+impl<T> TrackedRwLock<T> {
+    #[track_caller]
+    fn new(value: T) -> Self {
+        TrackedRwLock {
+            reg: lockdep::Registration::new(std::panic::Location::caller()),
+            inner: RwLock::new(value),
+        }
+    }
+
+    fn read(&self) -> LockResult<TrackedReadGuard<'_, T>> {
+        self.reg.before_lock();
+        let result = self.inner.read();
+        map_lock(result, |inner| TrackedReadGuard {
+            inner,
+            _held: self.reg.acquired(),
+        })
+    }
+
+    fn write(&self) -> LockResult<TrackedWriteGuard<'_, T>> {
+        self.reg.before_lock();
+        let result = self.inner.write();
+        map_lock(result, |inner| TrackedWriteGuard {
+            inner,
+            _held: self.reg.acquired(),
+        })
+    }
+
+    fn try_read(&self) -> TryLockResult<TrackedReadGuard<'_, T>> {
+        map_try_lock(self.inner.try_read(), |inner| TrackedReadGuard {
+            inner,
+            _held: self.reg.acquired(),
+        })
+    }
+
+    fn try_write(&self) -> TryLockResult<TrackedWriteGuard<'_, T>> {
+        map_try_lock(self.inner.try_write(), |inner| TrackedWriteGuard {
+            inner,
+            _held: self.reg.acquired(),
+        })
+    }
+
+    fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Deref for TrackedReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Deref for TrackedWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> DerefMut for TrackedWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+// A function written against the tracked types works in both build modes
+fn transfer(from: &TrackedMutex<i64>, to: &TrackedMutex<i64>, amount: i64) {
+    let mut a: TrackedMutexGuard<'_, i64> = from.lock().unwrap();
+    let mut b = to.lock().unwrap();
+    *a -= amount;
+    *b += amount;
+}
+
+fn main() {
+    use std::sync::Arc;
+    use std::thread;
+
+    // Outputs below are for a debug build (`cargo run`). With --release the
+    // types are the std locks and every report is empty.
+    println!("{}", cfg!(debug_assertions)); // true
+
+    // --- Drop-in usage: same API as Mutex / RwLock ---
+    let checking = Arc::new(TrackedMutex::new(100));
+    let savings = Arc::new(TrackedMutex::new(0));
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let (c, s) = (Arc::clone(&checking), Arc::clone(&savings));
+            // Every thread locks checking → savings: one consistent order
+            thread::spawn(move || transfer(&c, &s, 10))
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+    println!("{} {}", *checking.lock().unwrap(), *savings.lock().unwrap()); // 60 40
+
+    let config = TrackedRwLock::new(vec!["a"]);
+    let mut writer: TrackedWriteGuard<'_, _> = config.write().unwrap();
+    writer.push("b");
+    drop(writer);
+    println!("{}", config.try_read().is_ok()); // true
+    {
+        let _reader: TrackedReadGuard<'_, _> = config.read().unwrap();
+        println!("{}", config.try_write().is_err()); // true — a reader holds it
+    }
+    println!("{:?}", config.into_inner().unwrap()); // ["a", "b"]
+
+    // --- ABBA: caught without ever deadlocking ---
+    // This run is sequential, so it can't hang, but the opposite orders
+    // mean two threads *could*. Log mode reports it and carries on.
+    set_on_cycle(OnCycle::Log);
+    transfer(&checking, &savings, 1); // checking → savings (already known)
+    transfer(&savings, &checking, 1); // savings → checking: closes a cycle
+    let reports = take_cycle_reports();
+    println!("{}", reports.len()); // 1
+    println!(
+        "{}",
+        reports
+            .iter()
+            .all(|r| r.starts_with("lock order inversion"))
+    ); // true
+
+    // Panic mode (the default) fails the offending lock() call instead
+    set_on_cycle(OnCycle::Panic);
+    let left = Arc::new(TrackedMutex::new(()));
+    let right = Arc::new(TrackedMutex::new(()));
+    let (l, r) = (Arc::clone(&left), Arc::clone(&right));
+    thread::spawn(move || {
+        let _l = l.lock().unwrap();
+        let _r = r.lock().unwrap();
+    })
+    .join()
+    .unwrap();
+    let (l, r) = (Arc::clone(&left), Arc::clone(&right));
+    let result = thread::Builder::new()
+        .name("inverted".into())
+        .spawn(move || {
+            let _r = r.lock().unwrap();
+            let _l = l.lock().unwrap(); // panics here, before blocking
+        })
+        .unwrap()
+        .join();
+    println!("{}", result.is_err()); // true
+
+    // --- Hold times ---
+    set_long_hold(Duration::from_millis(5));
+    let cache = TrackedMutex::new(0u64);
+    for i in 0..100 {
+        *cache.lock().unwrap() += i;
+    }
+    {
+        let _slow = cache.lock().unwrap();
+        thread::sleep(Duration::from_millis(20)); // stderr: long critical section
+    }
+    let stats = hold_report();
+    // (the report is empty in release builds)
+    if let Some(cache_stats) = stats.iter().max_by_key(|s| s.count) {
+        println!("{} {}", cache_stats.count, cache_stats.long); // 101 1
+        println!("{}", cache_stats);
+        // e.g. tracked_locks.rs:619: 101 holds, mean 198µs, max 20.07ms, 1 long | <1µs:100 <32768µs:1
+    }
+
+    // The wrappers give the value back like the std locks do
+    let mut cache = cache;
+    *cache.get_mut().unwrap() += 50;
+    println!("{}", cache.try_lock().map(|g| *g).unwrap_or(0)); // 5000
+    println!("{}", cache.into_inner().unwrap()); // 5000
+    println!("tracked locks done"); // tracked locks done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+// The checks only exist in debug builds, which is what `cargo test` uses
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    // The cycle policy is global: tests that change it take turns
+    static POLICY: Mutex<()> = Mutex::new(());
+
+    fn panic_message(err: Box<dyn std::any::Any + Send>) -> String {
+        match err.downcast::<String>() {
+            Ok(s) => *s,
+            Err(err) => err.downcast_ref::<&str>().unwrap_or(&"").to_string(),
+        }
+    }
+
+    #[test]
+    fn consistent_order_is_silent() {
+        let a = Arc::new(TrackedMutex::new(0));
+        let b = Arc::new(TrackedMutex::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (a, b) = (Arc::clone(&a), Arc::clone(&b));
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        transfer(&a, &b, 1);
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(*a.lock().unwrap(), -400);
+        assert_eq!(*b.lock().unwrap(), 400);
+    }
+
+    #[test]
+    fn abba_panics_with_both_backtraces() {
+        let _policy = POLICY.lock().unwrap();
+        let a = Arc::new(TrackedMutex::new(()));
+        let b = Arc::new(TrackedMutex::new(()));
+        let (a2, b2) = (Arc::clone(&a), Arc::clone(&b));
+        thread::Builder::new()
+            .name("first".into())
+            .spawn(move || {
+                let _a = a2.lock().unwrap();
+                let _b = b2.lock().unwrap();
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let err = thread::Builder::new()
+            .name("second".into())
+            .spawn(move || {
+                let _b = b.lock().unwrap();
+                let _a = a.lock().unwrap();
+            })
+            .unwrap()
+            .join()
+            .unwrap_err();
+        let msg = panic_message(err);
+        assert!(msg.starts_with("lock order inversion"), "{}", msg);
+        assert!(msg.contains("this acquisition"));
+        assert!(msg.contains("(thread `second`)"));
+        assert!(msg.contains("earlier acquisition"));
+        assert!(msg.contains("(thread `first`)"));
+    }
+
+    #[test]
+    fn longer_cycles_through_rwlocks_are_found() {
+        let _policy = POLICY.lock().unwrap();
+        set_on_cycle(OnCycle::Log);
+        let a = TrackedMutex::new(());
+        let b = TrackedRwLock::new(());
+        let c = TrackedMutex::new(());
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.read().unwrap(); // a → b
+        }
+        {
+            let _b = b.write().unwrap();
+            let _c = c.lock().unwrap(); // b → c
+        }
+        take_cycle_reports();
+        {
+            let _c = c.lock().unwrap();
+            let _a = a.lock().unwrap(); // c → a closes a → b → c → a
+        }
+        // Logged once: the edge is now known
+        {
+            let _c = c.lock().unwrap();
+            let _a = a.lock().unwrap();
+        }
+        set_on_cycle(OnCycle::Panic);
+        let reports = take_cycle_reports();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].matches("earlier acquisition").count(), 2);
+    }
+
+    #[test]
+    fn relocking_on_the_same_thread_panics_instead_of_hanging() {
+        let m = Arc::new(TrackedMutex::new(1));
+        let err = thread::spawn(move || {
+            let _g = m.lock().unwrap();
+            let _again = m.lock().unwrap();
+        })
+        .join()
+        .unwrap_err();
+        assert!(panic_message(err).starts_with("recursive lock"));
+    }
+
+    #[test]
+    fn try_lock_adds_no_edges() {
+        let a = TrackedMutex::new(());
+        let b = TrackedMutex::new(());
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.try_lock().unwrap();
+        }
+        // b → a is fine: a → b was only a try_lock
+        let _b = b.lock().unwrap();
+        let _a = a.lock().unwrap();
+        assert!(matches!(a.try_lock(), Err(TryLockError::WouldBlock)));
+    }
+
+    #[test]
+    fn hold_times_land_in_histogram_buckets() {
+        let m = TrackedMutex::new(());
+        for _ in 0..10 {
+            drop(m.lock().unwrap());
+        }
+        {
+            let _g = m.lock().unwrap();
+            thread::sleep(Duration::from_millis(40)); // over the 10ms default
+        }
+        let here = format!("tracked_locks.rs:{}", line!() - 8);
+        let stats = hold_report().into_iter().find(|s| s.name == here).unwrap();
+        assert_eq!(stats.count, 11);
+        assert_eq!(stats.long, 1);
+        assert!(stats.max >= Duration::from_millis(40));
+        assert_eq!(stats.buckets.iter().sum::<u64>(), 11);
+        // 40ms = 40_000µs sits in the [2^15, 2^16) µs bucket
+        assert_eq!(stats.buckets[16], 1);
+    }
+
+    #[test]
+    fn poisoning_passes_through() {
+        let m = Arc::new(TrackedMutex::new(5));
+        let m2 = Arc::clone(&m);
+        let _ = thread::spawn(move || {
+            let _g = m2.lock().unwrap();
+            panic!("poison it");
+        })
+        .join();
+        match m.lock() {
+            Err(poisoned) => assert_eq!(*poisoned.into_inner(), 5),
+            Ok(_) => panic!("expected a poisoned lock"),
+        };
+    }
+}
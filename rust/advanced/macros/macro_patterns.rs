@@ -65,19 +65,20 @@ macro_rules! debug_print {
 }
 
 // --- Pattern: retry logic ---
-#[allow(unused_macros)]
+// Same call shape as retry!(3, { .. }) in expert/async_programming/retry_policy.rs,
+// where it is sugar over a RetryPolicy with backoff. This stub reruns the block
+// (which evaluates to a Result) up to $times times, never waits, and hands back
+// the last error.
 macro_rules! retry {
     ($times:expr, $block:block) => {{
-        let mut success = false;
-        for attempt in 1..=$times {
-            let result: Result<_, String> = (|| Ok($block))();
-            if result.is_ok() {
-                success = true;
-                println!("succeeded on attempt {}", attempt);
-                break;
+        let mut attempt = 1;
+        loop {
+            match $block {
+                Ok(v) => break Ok(v),
+                Err(e) if attempt >= $times => break Err(e),
+                Err(_) => attempt += 1,
             }
         }
-        success
     }};
 }
 
@@ -151,6 +152,20 @@ fn main() {
     // debug_print (only prints in debug builds)
     debug_print!("x = {}, y = {}", 10, 20); // [DEBUG] x = 10, y = 20
 
+    // retry: the block runs again until it succeeds or the attempts run out
+    let mut calls = 0;
+    let r: Result<i32, String> = retry!(3, {
+        calls += 1;
+        if calls < 2 {
+            Err(format!("attempt {} failed", calls))
+        } else {
+            Ok(calls)
+        }
+    });
+    println!("{:?}", r); // Ok(2)
+    let r: Result<(), String> = retry!(2, { Err("always".to_string()) });
+    println!("{:?}", r); // Err("always")
+
     // try_or_log
     find_first_even(&[1, 3, 4, 6]); // first even: 4
     find_first_even(&[1, 3, 5, 7]); // [WARN] no even number found
@@ -0,0 +1,23 @@
+// build.rs — runs before the crate compiles
+// Generates full_project.h from the extern "C" functions in src/ffi.rs.
+// Build scripts may only write to OUT_DIR, so it goes there; the copy in
+// include/ is for C users, and tests/c_api.rs fails if the two differ.
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let header = out_dir.join("full_project.h");
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("cbindgen.toml is invalid");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate the C header")
+        .write_to_file(&header);
+    // Lets tests/c_api.rs find it
+    println!("cargo:rustc-env=FULL_PROJECT_HEADER={}", header.display());
+}
@@ -0,0 +1,145 @@
+#ifndef FULL_PROJECT_H
+#define FULL_PROJECT_H
+
+/* Generated by cbindgen from src/ffi.rs by build.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of every fallible call. FP_STATUS_OK is 0; anything else means
+ * fp_last_error_message() has the details.
+ */
+typedef enum FpStatus {
+  FP_STATUS_OK = 0,
+  FP_STATUS_NULL_POINTER = 1,
+  FP_STATUS_INVALID_UTF8 = 2,
+  FP_STATUS_INVALID_ARGUMENT = 3,
+  FP_STATUS_OUT_OF_STOCK = 4,
+  FP_STATUS_PANIC = 5,
+} FpStatus;
+
+typedef struct FpProduct FpProduct;
+
+typedef struct FpUser FpUser;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Message for the most recent failed call on this thread, or NULL if none
+ * has failed yet. Owned by the library: valid until the next failing call
+ * on the same thread. Do not free it.
+ */
+const char *fp_last_error_message(void);
+
+/**
+ * Frees a string returned by this library. NULL is ignored.
+ *
+ * # Safety
+ * `s` must be NULL or a string from this library that hasn't been freed.
+ */
+void fp_string_free(char *s);
+
+/**
+ * Creates a user. `name` and `email` are copied; `email` must pass
+ * validation (FP_STATUS_INVALID_ARGUMENT otherwise). On success `*out`
+ * receives a handle to release with fp_user_free.
+ *
+ * # Safety
+ * `name` and `email` must be NULL or NUL-terminated strings; `out` must be
+ * NULL or writable.
+ */
+enum FpStatus fp_user_new(uint32_t id, const char *name, const char *email, struct FpUser **out);
+
+/**
+ * Writes whether the user is active to `*out`.
+ *
+ * # Safety
+ * `user` must be NULL or a live handle; `out` must be NULL or writable.
+ */
+enum FpStatus fp_user_is_active(const struct FpUser *user, bool *out);
+
+/**
+ * Marks the user inactive.
+ *
+ * # Safety
+ * `user` must be NULL or a live handle not used by another thread.
+ */
+enum FpStatus fp_user_deactivate(struct FpUser *user);
+
+/**
+ * Writes the "Name#id" display name to `*out` as a new string; release it
+ * with fp_string_free.
+ *
+ * # Safety
+ * `user` must be NULL or a live handle; `out` must be NULL or writable.
+ */
+enum FpStatus fp_user_display_name(const struct FpUser *user, char **out);
+
+/**
+ * Releases a user. NULL is ignored.
+ *
+ * # Safety
+ * `user` must be NULL or a handle from fp_user_new that hasn't been freed.
+ */
+void fp_user_free(struct FpUser *user);
+
+/**
+ * Creates a product. `price` must be finite and non-negative. On success
+ * `*out` receives a handle to release with fp_product_free.
+ *
+ * # Safety
+ * `name` must be NULL or a NUL-terminated string; `out` must be NULL or
+ * writable.
+ */
+enum FpStatus fp_product_new(uint32_t id,
+                             const char *name,
+                             double price,
+                             uint32_t stock,
+                             struct FpProduct **out);
+
+/**
+ * Buys `qty` units. On success the total price goes to `*total` (pass NULL
+ * to ignore it). FP_STATUS_OUT_OF_STOCK leaves the stock unchanged.
+ *
+ * # Safety
+ * `product` must be NULL or a live handle not used by another thread;
+ * `total` must be NULL or writable.
+ */
+enum FpStatus fp_product_purchase(struct FpProduct *product, uint32_t qty, double *total);
+
+/**
+ * Adds `qty` units to the stock.
+ *
+ * # Safety
+ * `product` must be NULL or a live handle not used by another thread.
+ */
+enum FpStatus fp_product_restock(struct FpProduct *product, uint32_t qty);
+
+/**
+ * Writes the number of units in stock to `*out`.
+ *
+ * # Safety
+ * `product` must be NULL or a live handle; `out` must be NULL or writable.
+ */
+enum FpStatus fp_product_stock(const struct FpProduct *product, uint32_t *out);
+
+/**
+ * Releases a product. NULL is ignored.
+ *
+ * # Safety
+ * `product` must be NULL or a handle from fp_product_new that hasn't been
+ * freed.
+ */
+void fp_product_free(struct FpProduct *product);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FULL_PROJECT_H */
@@ -0,0 +1,476 @@
+// ffi.rs — the C ABI for the models
+// Cargo.toml's [lib] crate-type also builds a cdylib (.so/.dylib/.dll) and a
+// staticlib (.a/.lib) that C programs can link against. build.rs runs
+// cbindgen over this file and writes the matching include/full_project.h.
+//
+// Every function follows the same conventions:
+//   - User and Product are opaque handles (FpUser*, FpProduct*): C can only
+//     hold the pointer, never see the fields. *_new creates, *_free releases.
+//   - Fallible calls return an FpStatus; FP_STATUS_OK is 0.
+//   - On failure, fp_last_error_message() says what went wrong. It is
+//     per thread, like errno.
+//   - Results go through out-pointers and are written only on success.
+//   - Panics are caught at the boundary and become FP_STATUS_PANIC:
+//     unwinding into C is undefined behavior.
+// expert/unsafe_rust/ffi.rs covers the building blocks (extern "C", CStr, repr(C)).
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::models::{Product, User};
+use crate::utils::validate_email;
+
+/// Result of every fallible call. FP_STATUS_OK is 0; anything else means
+/// fp_last_error_message() has the details.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    InvalidArgument = 3,
+    OutOfStock = 4,
+    Panic = 5,
+}
+
+// --- Error plumbing ---
+
+struct FfiError {
+    status: FpStatus,
+    message: String,
+}
+
+impl FfiError {
+    fn new(status: FpStatus, message: impl Into<String>) -> FfiError {
+        FfiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // An interior NUL would truncate the message; replace it instead
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+// Runs the body of an exported function: maps errors to a status, records
+// the message, and stops panics from unwinding into C
+fn run(body: impl FnOnce() -> Result<(), FfiError>) -> FpStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => FpStatus::Ok,
+        Ok(Err(e)) => {
+            set_last_error(e.message);
+            e.status
+        }
+        Err(payload) => {
+            let what = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            set_last_error(format!("panic: {}", what));
+            FpStatus::Panic
+        }
+    }
+}
+
+// --- Argument checking ---
+// Each turns a raw pointer into a reference, or an FP_STATUS_NULL_POINTER
+// error naming the argument
+
+unsafe fn str_arg<'a>(p: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    if p.is_null() {
+        return Err(FfiError::new(
+            FpStatus::NullPointer,
+            format!("`{}` is NULL", name),
+        ));
+    }
+    // SAFETY: caller guarantees a NUL-terminated string that outlives 'a
+    unsafe { CStr::from_ptr(p) }.to_str().map_err(|_| {
+        FfiError::new(
+            FpStatus::InvalidUtf8,
+            format!("`{}` is not valid UTF-8", name),
+        )
+    })
+}
+
+unsafe fn ref_arg<'a, T>(p: *const T, name: &str) -> Result<&'a T, FfiError> {
+    // SAFETY: caller guarantees p is NULL or a live handle
+    unsafe { p.as_ref() }
+        .ok_or_else(|| FfiError::new(FpStatus::NullPointer, format!("`{}` is NULL", name)))
+}
+
+unsafe fn mut_arg<'a, T>(p: *mut T, name: &str) -> Result<&'a mut T, FfiError> {
+    // SAFETY: caller guarantees p is NULL or a live handle nobody else is using
+    unsafe { p.as_mut() }
+        .ok_or_else(|| FfiError::new(FpStatus::NullPointer, format!("`{}` is NULL", name)))
+}
+
+// Checked up front, so a NULL out-pointer fails before any side effect
+fn out_arg<T>(p: *mut T, name: &str) -> Result<*mut T, FfiError> {
+    if p.is_null() {
+        return Err(FfiError::new(
+            FpStatus::NullPointer,
+            format!("`{}` is NULL", name),
+        ));
+    }
+    Ok(p)
+}
+
+// --- Errors and strings ---
+
+/// Message for the most recent failed call on this thread, or NULL if none
+/// has failed yet. Owned by the library: valid until the next failing call
+/// on the same thread. Do not free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn fp_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Frees a string returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `s` must be NULL or a string from this library that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: s came from CString::into_raw in this library
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+// --- User ---
+
+/// Creates a user. `name` and `email` are copied; `email` must pass
+/// validation (FP_STATUS_INVALID_ARGUMENT otherwise). On success `*out`
+/// receives a handle to release with fp_user_free.
+///
+/// # Safety
+/// `name` and `email` must be NULL or NUL-terminated strings; `out` must be
+/// NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_user_new(
+    id: u32,
+    name: *const c_char,
+    email: *const c_char,
+    out: *mut *mut User,
+) -> FpStatus {
+    run(|| {
+        let out = out_arg(out, "out")?;
+        let name = unsafe { str_arg(name, "name") }?;
+        let email = unsafe { str_arg(email, "email") }?;
+        validate_email(email)
+            .map_err(|e| FfiError::new(FpStatus::InvalidArgument, format!("email: {}", e)))?;
+        let user = Box::new(User::new(id, name, email));
+        unsafe { out.write(Box::into_raw(user)) };
+        Ok(())
+    })
+}
+
+/// Writes whether the user is active to `*out`.
+///
+/// # Safety
+/// `user` must be NULL or a live handle; `out` must be NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_user_is_active(user: *const User, out: *mut bool) -> FpStatus {
+    run(|| {
+        let out = out_arg(out, "out")?;
+        let user = unsafe { ref_arg(user, "user") }?;
+        unsafe { out.write(user.is_active()) };
+        Ok(())
+    })
+}
+
+/// Marks the user inactive.
+///
+/// # Safety
+/// `user` must be NULL or a live handle not used by another thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_user_deactivate(user: *mut User) -> FpStatus {
+    run(|| {
+        unsafe { mut_arg(user, "user") }?.deactivate();
+        Ok(())
+    })
+}
+
+/// Writes the "Name#id" display name to `*out` as a new string; release it
+/// with fp_string_free.
+///
+/// # Safety
+/// `user` must be NULL or a live handle; `out` must be NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_user_display_name(
+    user: *const User,
+    out: *mut *mut c_char,
+) -> FpStatus {
+    run(|| {
+        let out = out_arg(out, "out")?;
+        let user = unsafe { ref_arg(user, "user") }?;
+        // The name came in through a C string, so it has no interior NUL
+        let name = CString::new(user.display_name())
+            .map_err(|_| FfiError::new(FpStatus::InvalidArgument, "name contains NUL"))?;
+        unsafe { out.write(name.into_raw()) };
+        Ok(())
+    })
+}
+
+/// Releases a user. NULL is ignored.
+///
+/// # Safety
+/// `user` must be NULL or a handle from fp_user_new that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_user_free(user: *mut User) {
+    if !user.is_null() {
+        // SAFETY: the handle came from Box::into_raw in fp_user_new
+        drop(unsafe { Box::from_raw(user) });
+    }
+}
+
+// --- Product ---
+
+/// Creates a product. `price` must be finite and non-negative. On success
+/// `*out` receives a handle to release with fp_product_free.
+///
+/// # Safety
+/// `name` must be NULL or a NUL-terminated string; `out` must be NULL or
+/// writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_product_new(
+    id: u32,
+    name: *const c_char,
+    price: f64,
+    stock: u32,
+    out: *mut *mut Product,
+) -> FpStatus {
+    run(|| {
+        let out = out_arg(out, "out")?;
+        let name = unsafe { str_arg(name, "name") }?;
+        if !price.is_finite() || price < 0.0 {
+            return Err(FfiError::new(
+                FpStatus::InvalidArgument,
+                format!("price must be a non-negative number, got {}", price),
+            ));
+        }
+        let product = Box::new(Product::new(id, name, price, stock));
+        unsafe { out.write(Box::into_raw(product)) };
+        Ok(())
+    })
+}
+
+/// Buys `qty` units. On success the total price goes to `*total` (pass NULL
+/// to ignore it). FP_STATUS_OUT_OF_STOCK leaves the stock unchanged.
+///
+/// # Safety
+/// `product` must be NULL or a live handle not used by another thread;
+/// `total` must be NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_product_purchase(
+    product: *mut Product,
+    qty: u32,
+    total: *mut f64,
+) -> FpStatus {
+    run(|| {
+        let product = unsafe { mut_arg(product, "product") }?;
+        let paid = product
+            .purchase(qty)
+            .map_err(|e| FfiError::new(FpStatus::OutOfStock, e))?;
+        if !total.is_null() {
+            unsafe { total.write(paid) };
+        }
+        Ok(())
+    })
+}
+
+/// Adds `qty` units to the stock.
+///
+/// # Safety
+/// `product` must be NULL or a live handle not used by another thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_product_restock(product: *mut Product, qty: u32) -> FpStatus {
+    run(|| {
+        let product = unsafe { mut_arg(product, "product") }?;
+        // Product::restock would overflow (and panic in debug builds)
+        if product.stock_count().checked_add(qty).is_none() {
+            return Err(FfiError::new(
+                FpStatus::InvalidArgument,
+                format!("restocking {} would overflow the stock count", qty),
+            ));
+        }
+        product.restock(qty);
+        Ok(())
+    })
+}
+
+/// Writes the number of units in stock to `*out`.
+///
+/// # Safety
+/// `product` must be NULL or a live handle; `out` must be NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_product_stock(product: *const Product, out: *mut u32) -> FpStatus {
+    run(|| {
+        let out = out_arg(out, "out")?;
+        let product = unsafe { ref_arg(product, "product") }?;
+        unsafe { out.write(product.stock_count()) };
+        Ok(())
+    })
+}
+
+/// Releases a product. NULL is ignored.
+///
+/// # Safety
+/// `product` must be NULL or a handle from fp_product_new that hasn't been
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fp_product_free(product: *mut Product) {
+    if !product.is_null() {
+        // SAFETY: the handle came from Box::into_raw in fp_product_new
+        drop(unsafe { Box::from_raw(product) });
+    }
+}
+
+// ============================================================
+// UNIT TESTS — calling the C ABI from Rust
+// ============================================================
+// tests/c_api.rs compiles and runs a real C program against the cdylib
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let p = fp_last_error_message();
+        assert!(!p.is_null());
+        unsafe { CStr::from_ptr(p) }.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_user_round_trip() {
+        let mut user = ptr::null_mut();
+        let status = unsafe {
+            fp_user_new(
+                7,
+                c"Alice".as_ptr(),
+                c"alice@example.com".as_ptr(),
+                &mut user,
+            )
+        };
+        assert_eq!(status, FpStatus::Ok);
+
+        let mut name = ptr::null_mut();
+        assert_eq!(
+            unsafe { fp_user_display_name(user, &mut name) },
+            FpStatus::Ok
+        );
+        assert_eq!(unsafe { CStr::from_ptr(name) }.to_str().unwrap(), "Alice#7");
+        unsafe { fp_string_free(name) };
+
+        let mut active = false;
+        assert_eq!(unsafe { fp_user_deactivate(user) }, FpStatus::Ok);
+        assert_eq!(
+            unsafe { fp_user_is_active(user, &mut active) },
+            FpStatus::Ok
+        );
+        assert!(!active);
+        unsafe { fp_user_free(user) };
+    }
+
+    #[test]
+    fn test_invalid_email_sets_last_error() {
+        let mut user = ptr::null_mut();
+        let status =
+            unsafe { fp_user_new(1, c"Bob".as_ptr(), c"bob.example.com".as_ptr(), &mut user) };
+        assert_eq!(status, FpStatus::InvalidArgument);
+        assert!(user.is_null(), "out is untouched on failure");
+        assert_eq!(last_error(), "email: invalid format: missing @ symbol");
+    }
+
+    #[test]
+    fn test_null_arguments_are_reported_by_name() {
+        assert_eq!(
+            unsafe { fp_user_new(1, ptr::null(), c"a@b.co".as_ptr(), &mut ptr::null_mut()) },
+            FpStatus::NullPointer
+        );
+        assert_eq!(last_error(), "`name` is NULL");
+        assert_eq!(
+            unsafe { fp_product_restock(ptr::null_mut(), 1) },
+            FpStatus::NullPointer
+        );
+        assert_eq!(last_error(), "`product` is NULL");
+        unsafe { fp_user_free(ptr::null_mut()) }; // a no-op, like free(NULL)
+    }
+
+    #[test]
+    fn test_purchase_and_restock() {
+        let mut product = ptr::null_mut();
+        assert_eq!(
+            unsafe { fp_product_new(1, c"Widget".as_ptr(), 2.5, 4, &mut product) },
+            FpStatus::Ok
+        );
+        let mut total = 0.0;
+        assert_eq!(
+            unsafe { fp_product_purchase(product, 3, &mut total) },
+            FpStatus::Ok
+        );
+        assert_eq!(total, 7.5);
+        assert_eq!(
+            unsafe { fp_product_purchase(product, 2, ptr::null_mut()) },
+            FpStatus::OutOfStock
+        );
+        assert_eq!(last_error(), "only 1 in stock");
+        assert_eq!(
+            unsafe { fp_product_restock(product, u32::MAX) },
+            FpStatus::InvalidArgument
+        );
+
+        let mut stock = 0;
+        assert_eq!(unsafe { fp_product_restock(product, 9) }, FpStatus::Ok);
+        assert_eq!(
+            unsafe { fp_product_stock(product, &mut stock) },
+            FpStatus::Ok
+        );
+        assert_eq!(stock, 10);
+        unsafe { fp_product_free(product) };
+    }
+
+    #[test]
+    fn test_bad_price_and_utf8_are_rejected() {
+        let mut product = ptr::null_mut();
+        assert_eq!(
+            unsafe { fp_product_new(1, c"Widget".as_ptr(), f64::NAN, 1, &mut product) },
+            FpStatus::InvalidArgument
+        );
+        let bad = [0xffu8, 0];
+        assert_eq!(
+            unsafe { fp_product_new(1, bad.as_ptr().cast(), 1.0, 1, &mut product) },
+            FpStatus::InvalidUtf8
+        );
+        assert!(product.is_null());
+    }
+
+    #[test]
+    fn test_panics_stop_at_the_boundary() {
+        let status = run(|| panic!("boom"));
+        assert_eq!(status, FpStatus::Panic);
+        assert_eq!(last_error(), "panic: boom");
+    }
+
+    #[test]
+    fn test_last_error_is_per_thread() {
+        assert_eq!(
+            unsafe { fp_user_deactivate(ptr::null_mut()) },
+            FpStatus::NullPointer
+        );
+        let other = std::thread::spawn(|| fp_last_error_message().is_null())
+            .join()
+            .unwrap();
+        assert!(other, "a fresh thread has no error");
+    }
+}
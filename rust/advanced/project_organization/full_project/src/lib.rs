@@ -2,6 +2,7 @@
 // Exposes the public API of this crate.
 // main.rs calls into this; integration tests (tests/) also use this.
 
+pub mod ffi; // C ABI: built into the cdylib/staticlib, header in include/
 pub mod models;
 pub mod utils;
 
@@ -0,0 +1,92 @@
+/* ffi_test.c — exercises include/full_project.h from plain C.
+ * tests/c_api.rs compiles this against the cdylib and runs it;
+ * any failed CHECK exits non-zero with the line and expression. */
+
+#include <math.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "full_project.h"
+
+static int checks = 0;
+
+#define CHECK(expr)                                                      \
+    do {                                                                 \
+        checks++;                                                        \
+        if (!(expr)) {                                                   \
+            fprintf(stderr, "%s:%d: CHECK failed: %s\n", __FILE__,       \
+                    __LINE__, #expr);                                    \
+            exit(1);                                                     \
+        }                                                                \
+    } while (0)
+
+#define CHECK_ERROR(expected)                                            \
+    CHECK(fp_last_error_message() != NULL &&                             \
+          strcmp(fp_last_error_message(), expected) == 0)
+
+static void test_user(void) {
+    FpUser *user = NULL;
+    CHECK(fp_user_new(1, "Alice", "alice@example.com", &user) == FP_STATUS_OK);
+    CHECK(user != NULL);
+
+    char *name = NULL;
+    CHECK(fp_user_display_name(user, &name) == FP_STATUS_OK);
+    CHECK(strcmp(name, "Alice#1") == 0);
+    fp_string_free(name);
+
+    bool active = false;
+    CHECK(fp_user_is_active(user, &active) == FP_STATUS_OK && active);
+    CHECK(fp_user_deactivate(user) == FP_STATUS_OK);
+    CHECK(fp_user_is_active(user, &active) == FP_STATUS_OK && !active);
+    fp_user_free(user);
+    fp_user_free(NULL); /* allowed, like free(NULL) */
+}
+
+static void test_user_errors(void) {
+    FpUser *user = NULL;
+    CHECK(fp_user_new(2, "Bob", "not-an-email", &user) == FP_STATUS_INVALID_ARGUMENT);
+    CHECK(user == NULL);
+    CHECK_ERROR("email: invalid format: missing @ symbol");
+
+    CHECK(fp_user_new(2, NULL, "bob@example.com", &user) == FP_STATUS_NULL_POINTER);
+    CHECK_ERROR("`name` is NULL");
+
+    CHECK(fp_user_new(2, "\xff", "bob@example.com", &user) == FP_STATUS_INVALID_UTF8);
+    CHECK(fp_user_deactivate(NULL) == FP_STATUS_NULL_POINTER);
+}
+
+static void test_product(void) {
+    FpProduct *widget = NULL;
+    CHECK(fp_product_new(1, "Widget", 9.99, 10, &widget) == FP_STATUS_OK);
+
+    double total = 0.0;
+    CHECK(fp_product_purchase(widget, 3, &total) == FP_STATUS_OK);
+    CHECK(fabs(total - 29.97) < 1e-9);
+
+    uint32_t stock = 0;
+    CHECK(fp_product_stock(widget, &stock) == FP_STATUS_OK && stock == 7);
+
+    CHECK(fp_product_purchase(widget, 8, NULL) == FP_STATUS_OUT_OF_STOCK);
+    CHECK_ERROR("only 7 in stock");
+    CHECK(fp_product_stock(widget, &stock) == FP_STATUS_OK && stock == 7);
+
+    CHECK(fp_product_restock(widget, 5) == FP_STATUS_OK);
+    CHECK(fp_product_stock(widget, &stock) == FP_STATUS_OK && stock == 12);
+    CHECK(fp_product_restock(widget, UINT32_MAX) == FP_STATUS_INVALID_ARGUMENT);
+    fp_product_free(widget);
+
+    FpProduct *bad = NULL;
+    CHECK(fp_product_new(2, "Gadget", -1.0, 0, &bad) == FP_STATUS_INVALID_ARGUMENT);
+    CHECK(bad == NULL);
+    CHECK(fp_product_stock(NULL, &stock) == FP_STATUS_NULL_POINTER);
+    CHECK_ERROR("`product` is NULL");
+}
+
+int main(void) {
+    test_user();
+    test_user_errors();
+    test_product();
+    printf("all %d C checks passed\n", checks);
+    return 0;
+}
@@ -0,0 +1,96 @@
+// c_api.rs — integration test: compile a real C program against the cdylib
+// `cargo test` doesn't build the cdylib, so the test runs `cargo build --lib`
+// itself (same target dir and profile) and links what that produced.
+// The header comes from build.rs in OUT_DIR. Set CC to use a compiler other than `cc`.
+
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The header build.rs generated from the current src/ffi.rs
+fn generated_header() -> &'static Path {
+    Path::new(env!("FULL_PROJECT_HEADER"))
+}
+
+// Build the cdylib/staticlib for the profile this test runs under and return
+// target/<profile>/, where they end up
+fn build_library() -> PathBuf {
+    // target/<profile>/deps/c_api-<hash> → target/<profile>
+    let exe = env::current_exe().unwrap();
+    let profile_dir = exe.parent().unwrap().parent().unwrap().to_path_buf();
+    let profile = match profile_dir.file_name().unwrap().to_str().unwrap() {
+        "debug" => "dev",
+        other => other,
+    };
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let build = Command::new(&cargo)
+        .args(["build", "--lib", "--profile", profile])
+        .arg("--manifest-path")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(profile_dir.parent().unwrap())
+        .output()
+        .unwrap_or_else(|e| panic!("could not run `{}`: {}", cargo, e));
+    assert!(
+        build.status.success(),
+        "cargo build --lib failed:\n{}",
+        String::from_utf8_lossy(&build.stderr)
+    );
+    profile_dir
+}
+
+#[test]
+fn test_c_program_against_cdylib() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = build_library();
+    let exe = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi_test");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let compile = Command::new(&cc)
+        .arg(manifest.join("tests/c/ffi_test.c"))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(generated_header().parent().unwrap())
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lfull_project")
+        .arg("-lm")
+        .arg("-o")
+        .arg(&exe)
+        .output()
+        .unwrap_or_else(|e| panic!("could not run C compiler `{}`: {}", cc, e));
+    assert!(
+        compile.status.success(),
+        "C compile failed:\n{}",
+        String::from_utf8_lossy(&compile.stderr)
+    );
+
+    let run = Command::new(&exe).output().unwrap();
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    assert!(
+        run.status.success(),
+        "C test failed:\n{}{}",
+        stdout,
+        String::from_utf8_lossy(&run.stderr)
+    );
+    assert!(stdout.contains("C checks passed"), "{}", stdout);
+}
+
+// include/full_project.h is what C users see; it must match src/ffi.rs
+#[test]
+fn test_committed_header_is_up_to_date() {
+    let committed = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/full_project.h");
+    let committed = fs::read_to_string(committed).unwrap();
+    let generated = fs::read_to_string(generated_header()).unwrap();
+    assert!(
+        committed == generated,
+        "include/full_project.h is stale: copy {} over it",
+        generated_header().display()
+    );
+}
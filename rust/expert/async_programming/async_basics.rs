@@ -75,6 +75,7 @@ async fn compute(a: &str, b: &str) -> Result<i32, String> {
 }
 
 // --- Spawning concurrent tasks ---
+// Fire-and-forget: cancellation.rs shows how to stop spawned tasks cleanly
 async fn spawn_tasks() {
     let mut handles = vec![];
 
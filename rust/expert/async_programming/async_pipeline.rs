@@ -0,0 +1,644 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full"] }
+//! futures = "0.3"
+//! ```
+
+// Async pipelines: AsyncProcessor stages joined by bounded channels
+//
+// futures.rs defines AsyncProcessor with one Doubler impl. Here processors
+// become pipeline stages:
+//
+//   source ──[queue]──> stage "parse" (×2 workers) ──[queue]──> stage "double" (×4) ──[queue]──> output
+//                            │                                        │
+//                            └──────────── dead letters <─────────────┘
+//
+// - Every queue is a bounded tokio mpsc channel. A stage only pulls an item
+//   when one of its workers is free, so a slow stage fills its input queue, the
+//   stage before it blocks on send, and so on back to the source:
+//   backpressure with no extra machinery.
+// - Each stage runs up to `concurrency` items at once (a JoinSet of workers).
+//   With concurrency > 1 output order is not preserved.
+// - A failed or panicking item becomes a DeadLetter (stage, item, error) and
+//   the pipeline carries on.
+// - Per-stage metrics: processed, failed, in flight, queue depth, throughput.
+//
+// Compared to futures.rs the trait gains associated Input/Output types and a
+// Result, so stages can change type and report failures.
+
+use futures::FutureExt;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// --- Processors ---
+
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessError(String);
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+trait AsyncProcessor: Send + Sync + 'static {
+    // Debug: a failed item is recorded in its dead letter
+    type Input: fmt::Debug + Send + 'static;
+    type Output: Send + 'static;
+
+    fn process<'a>(
+        &'a self,
+        input: Self::Input,
+    ) -> BoxFuture<'a, Result<Self::Output, ProcessError>>;
+}
+
+struct Doubler;
+
+impl AsyncProcessor for Doubler {
+    type Input = i32;
+    type Output = i32;
+
+    fn process<'a>(&'a self, input: i32) -> BoxFuture<'a, Result<i32, ProcessError>> {
+        Box::pin(async move {
+            input
+                .checked_mul(2)
+                .ok_or_else(|| ProcessError(format!("{} * 2 overflows", input)))
+        })
+    }
+}
+
+struct ParseNumber;
+
+impl AsyncProcessor for ParseNumber {
+    type Input = String;
+    type Output = i32;
+
+    fn process<'a>(&'a self, input: String) -> BoxFuture<'a, Result<i32, ProcessError>> {
+        Box::pin(async move {
+            input
+                .trim()
+                .parse()
+                .map_err(|e| ProcessError(format!("{:?}: {}", input, e)))
+        })
+    }
+}
+
+// Stands in for a network call
+struct SlowLookup {
+    delay: Duration,
+}
+
+impl AsyncProcessor for SlowLookup {
+    type Input = i32;
+    type Output = String;
+
+    fn process<'a>(&'a self, input: i32) -> BoxFuture<'a, Result<String, ProcessError>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.delay).await;
+            Ok(format!("user-{}", input))
+        })
+    }
+}
+
+// --- Dead letters and metrics ---
+
+#[derive(Debug, Clone)]
+struct DeadLetter {
+    stage: &'static str,
+    item: String, // Debug rendering of the input
+    error: String,
+}
+
+struct StageMetrics {
+    name: &'static str,
+    started: Instant,
+    processed: AtomicU64,
+    failed: AtomicU64,
+    in_flight: AtomicU64,
+    // (queued, capacity) of the stage's input queue; a closure because the
+    // item type differs per stage
+    queue: Box<dyn Fn() -> (usize, usize) + Send + Sync>,
+}
+
+#[derive(Debug, Clone)]
+struct MetricsSnapshot {
+    name: &'static str,
+    processed: u64,
+    failed: u64,
+    in_flight: u64,
+    queued: usize,
+    capacity: usize,
+    per_sec: f64,
+}
+
+impl StageMetrics {
+    fn snapshot(&self) -> MetricsSnapshot {
+        let processed = self.processed.load(Ordering::Relaxed);
+        let (queued, capacity) = (self.queue)();
+        MetricsSnapshot {
+            name: self.name,
+            processed,
+            failed: self.failed.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            queued,
+            capacity,
+            per_sec: processed as f64 / self.started.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<8} ok {:>4}  failed {:>3}  in flight {:>2}  queue {:>2}/{:<2}  {:>8.0}/s",
+            self.name,
+            self.processed,
+            self.failed,
+            self.in_flight,
+            self.queued,
+            self.capacity,
+            self.per_sec
+        )
+    }
+}
+
+// --- Stage configuration ---
+
+struct StageConfig {
+    name: &'static str,
+    concurrency: usize,
+    capacity: usize, // size of the queue this stage writes into
+}
+
+impl StageConfig {
+    fn new(name: &'static str) -> Self {
+        StageConfig {
+            name,
+            concurrency: 1,
+            capacity: 16,
+        }
+    }
+
+    fn concurrency(mut self, n: usize) -> Self {
+        assert!(n > 0, "concurrency must be at least 1");
+        self.concurrency = n;
+        self
+    }
+
+    fn capacity(mut self, n: usize) -> Self {
+        assert!(n > 0, "queue capacity must be at least 1");
+        self.capacity = n;
+        self
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+async fn run_stage<P: AsyncProcessor>(
+    processor: Arc<P>,
+    mut input: mpsc::Receiver<P::Input>,
+    output: mpsc::Sender<P::Output>,
+    dead: mpsc::UnboundedSender<DeadLetter>,
+    metrics: Arc<StageMetrics>,
+    concurrency: usize,
+) {
+    let mut workers = JoinSet::new();
+    loop {
+        // Free a worker slot *before* pulling: this is what makes backpressure work
+        while workers.len() >= concurrency {
+            workers.join_next().await;
+        }
+        let Some(item) = input.recv().await else {
+            break; // upstream finished
+        };
+        let (processor, output, dead, metrics) = (
+            Arc::clone(&processor),
+            output.clone(),
+            dead.clone(),
+            Arc::clone(&metrics),
+        );
+        metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+        workers.spawn(async move {
+            let described = format!("{:?}", item);
+            let result = AssertUnwindSafe(processor.process(item))
+                .catch_unwind()
+                .await;
+            let error = match result {
+                Ok(Ok(out)) => {
+                    // Counted as in flight until downstream accepts it
+                    let delivered = output.send(out).await.is_ok();
+                    metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+                    if delivered {
+                        metrics.processed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    return;
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(panic) => format!("panicked: {}", panic_message(&*panic)),
+            };
+            metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+            metrics.failed.fetch_add(1, Ordering::Relaxed);
+            let _ = dead.send(DeadLetter {
+                stage: metrics.name,
+                item: described,
+                error,
+            });
+        });
+    }
+    while workers.join_next().await.is_some() {}
+    // `output` drops here: the next stage sees the end of its input
+}
+
+// --- Builder ---
+
+struct PipelineBuilder<In, T> {
+    input: mpsc::Sender<In>,
+    rx: mpsc::Receiver<T>,
+    rx_probe: mpsc::WeakSender<T>, // reads the queue depth without keeping it open
+    dead_tx: mpsc::UnboundedSender<DeadLetter>,
+    dead_rx: mpsc::UnboundedReceiver<DeadLetter>,
+    metrics: Vec<Arc<StageMetrics>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+struct Pipeline<In, Out> {
+    input: mpsc::Sender<In>,
+    output: mpsc::Receiver<Out>,
+    dead_letters: mpsc::UnboundedReceiver<DeadLetter>,
+    metrics: Vec<Arc<StageMetrics>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl<In: Send + 'static> Pipeline<In, In> {
+    fn builder(source_capacity: usize) -> PipelineBuilder<In, In> {
+        let (input, rx) = mpsc::channel(source_capacity);
+        let (dead_tx, dead_rx) = mpsc::unbounded_channel();
+        PipelineBuilder {
+            rx_probe: input.downgrade(),
+            input,
+            rx,
+            dead_tx,
+            dead_rx,
+            metrics: Vec::new(),
+            tasks: Vec::new(),
+        }
+    }
+}
+
+impl<In, T: Send + 'static> PipelineBuilder<In, T> {
+    // The compiler checks that each processor accepts the previous one's output
+    fn stage<P>(mut self, processor: P, config: StageConfig) -> PipelineBuilder<In, P::Output>
+    where
+        P: AsyncProcessor<Input = T>,
+    {
+        let (tx, rx) = mpsc::channel(config.capacity);
+        let probe = self.rx_probe;
+        let metrics = Arc::new(StageMetrics {
+            name: config.name,
+            started: Instant::now(),
+            processed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            queue: Box::new(move || match probe.upgrade() {
+                Some(s) => (s.max_capacity() - s.capacity(), s.max_capacity()),
+                None => (0, 0), // upstream closed
+            }),
+        });
+        self.metrics.push(Arc::clone(&metrics));
+        self.tasks.push(tokio::spawn(run_stage(
+            Arc::new(processor),
+            self.rx,
+            tx.clone(),
+            self.dead_tx.clone(),
+            metrics,
+            config.concurrency,
+        )));
+        PipelineBuilder {
+            input: self.input,
+            rx,
+            rx_probe: tx.downgrade(), // `tx` itself drops now; the stage holds the real one
+            dead_tx: self.dead_tx,
+            dead_rx: self.dead_rx,
+            metrics: self.metrics,
+            tasks: self.tasks,
+        }
+    }
+
+    fn build(self) -> Pipeline<In, T> {
+        Pipeline {
+            input: self.input,
+            output: self.rx,
+            dead_letters: self.dead_rx,
+            metrics: self.metrics,
+            tasks: self.tasks,
+        }
+    }
+}
+
+impl<In, Out> Pipeline<In, Out> {
+    fn metrics(&self) -> Vec<MetricsSnapshot> {
+        self.metrics.iter().map(|m| m.snapshot()).collect()
+    }
+
+    // Stop accepting input; returns the output side to drain
+    fn close(
+        self,
+    ) -> (
+        mpsc::Receiver<Out>,
+        mpsc::UnboundedReceiver<DeadLetter>,
+        Vec<JoinHandle<()>>,
+    ) {
+        (self.output, self.dead_letters, self.tasks)
+    }
+}
+
+// Close, drain everything, and wait for every stage to finish
+async fn finish<In, Out>(pipeline: Pipeline<In, Out>) -> (Vec<Out>, Vec<DeadLetter>) {
+    let (mut output, mut dead, tasks) = pipeline.close();
+    let mut results = Vec::new();
+    while let Some(v) = output.recv().await {
+        results.push(v);
+    }
+    for t in tasks {
+        t.await.expect("stage task panicked");
+    }
+    let mut letters = Vec::new();
+    while let Ok(d) = dead.try_recv() {
+        letters.push(d);
+    }
+    (results, letters)
+}
+
+#[tokio::main]
+async fn main() {
+    // String -> i32 -> i32 -> String
+    let pipeline = Pipeline::builder(8)
+        .stage(ParseNumber, StageConfig::new("parse").capacity(8))
+        .stage(
+            Doubler,
+            StageConfig::new("double").concurrency(2).capacity(4),
+        )
+        .stage(
+            SlowLookup {
+                delay: Duration::from_millis(5),
+            },
+            StageConfig::new("lookup").concurrency(4).capacity(4),
+        )
+        .build();
+
+    let inputs = ["1", "2", "oops", "4", "2147483647", " 6 "];
+    // input.send() waits whenever the first queue is full: backpressure
+    // reaches the feeder. It must run alongside the drain below — feeding
+    // everything first would fill every queue and stall.
+    let input = pipeline.input.clone();
+    tokio::spawn(async move {
+        for s in inputs.iter().cycle().take(60) {
+            input.send(s.to_string()).await.unwrap();
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    println!("--- Metrics while running ---");
+    for m in pipeline.metrics() {
+        println!("{}", m); // lookup (slowest) is busy and every queue upstream of it is full
+    }
+
+    let metrics = pipeline.metrics.clone();
+    let (mut results, dead) = finish(pipeline).await;
+    results.sort();
+    results.dedup();
+    println!("\nunique results: {:?}", results); // ["user-12", "user-2", "user-4", "user-8"]
+
+    println!("dead letters: {}", dead.len()); // dead letters: 20
+    for d in dead.iter().take(2) {
+        println!("  [{}] {} -> {}", d.stage, d.item, d.error);
+    }
+    // [parse] "oops" -> "oops": invalid digit found in string
+    // [double] 2147483647 -> 2147483647 * 2 overflows
+
+    println!("\n--- Final metrics ---");
+    for m in &metrics {
+        println!("{}", m.snapshot());
+    }
+    // parse    ok   50  failed  10 ...
+    // double   ok   40  failed  10 ...
+    // lookup   ok   40  failed   0 ...
+
+    println!("pipeline done"); // pipeline done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::sync::Semaphore;
+
+    struct Panicky;
+
+    impl AsyncProcessor for Panicky {
+        type Input = i32;
+        type Output = i32;
+        fn process<'a>(&'a self, input: i32) -> BoxFuture<'a, Result<i32, ProcessError>> {
+            Box::pin(async move {
+                if input == 13 {
+                    panic!("unlucky");
+                }
+                Ok(input)
+            })
+        }
+    }
+
+    // Blocks until the test opens the gate; records peak concurrency
+    struct Gated {
+        gate: Arc<Semaphore>,
+        active: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl Gated {
+        fn new(open: bool) -> Self {
+            Gated {
+                gate: Arc::new(Semaphore::new(if open {
+                    Semaphore::MAX_PERMITS
+                } else {
+                    0
+                })),
+                active: Arc::new(AtomicUsize::new(0)),
+                peak: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl AsyncProcessor for Gated {
+        type Input = i32;
+        type Output = i32;
+        fn process<'a>(&'a self, input: i32) -> BoxFuture<'a, Result<i32, ProcessError>> {
+            Box::pin(async move {
+                let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(now, Ordering::SeqCst);
+                let _permit = self.gate.acquire().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                self.active.fetch_sub(1, Ordering::SeqCst);
+                Ok(input)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn items_flow_through_typed_stages() {
+        let p = Pipeline::builder(4)
+            .stage(ParseNumber, StageConfig::new("parse"))
+            .stage(Doubler, StageConfig::new("double").concurrency(3))
+            .build();
+        for s in ["1", "2", "3", "4"] {
+            p.input.send(s.to_string()).await.unwrap();
+        }
+        let (mut out, dead) = finish(p).await;
+        out.sort();
+        assert_eq!(out, vec![2, 4, 6, 8]);
+        assert!(dead.is_empty());
+    }
+
+    #[tokio::test]
+    async fn single_worker_preserves_order() {
+        let p = Pipeline::builder(4)
+            .stage(Doubler, StageConfig::new("a"))
+            .stage(Doubler, StageConfig::new("b"))
+            .build();
+        let feeder = {
+            let input = p.input.clone();
+            tokio::spawn(async move {
+                for i in 0..100 {
+                    input.send(i).await.unwrap();
+                }
+            })
+        };
+        let (out, _) = finish(p).await; // drains while the feeder is still sending
+        feeder.await.unwrap();
+        assert_eq!(out, (0..100).map(|i| i * 4).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn failures_go_to_dead_letters() {
+        let p = Pipeline::builder(4)
+            .stage(ParseNumber, StageConfig::new("parse"))
+            .stage(Doubler, StageConfig::new("double"))
+            .build();
+        for s in ["5", "x", "2147483647", "7"] {
+            p.input.send(s.to_string()).await.unwrap();
+        }
+        let metrics = p.metrics.clone();
+        let (out, dead) = finish(p).await;
+        assert_eq!(out, vec![10, 14]);
+        assert_eq!(dead.len(), 2);
+        assert_eq!(dead[0].stage, "parse");
+        assert_eq!(dead[0].item, "\"x\"");
+        assert_eq!(dead[1].stage, "double");
+        assert!(dead[1].error.contains("overflows"));
+
+        let snap: Vec<(u64, u64)> = metrics
+            .iter()
+            .map(|m| {
+                let s = m.snapshot();
+                (s.processed, s.failed)
+            })
+            .collect();
+        assert_eq!(snap, vec![(3, 1), (2, 1)]);
+    }
+
+    #[tokio::test]
+    async fn panics_are_caught_per_item() {
+        let p = Pipeline::builder(4)
+            .stage(Panicky, StageConfig::new("risky").concurrency(2))
+            .build();
+        for i in [12, 13, 14] {
+            p.input.send(i).await.unwrap();
+        }
+        let (mut out, dead) = finish(p).await;
+        out.sort();
+        assert_eq!(out, vec![12, 14]);
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].item, "13");
+        assert_eq!(dead[0].error, "panicked: unlucky");
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_is_respected() {
+        let gated = Gated::new(true);
+        let peak = Arc::clone(&gated.peak);
+        let p = Pipeline::builder(64)
+            .stage(gated, StageConfig::new("work").concurrency(4).capacity(64))
+            .build();
+        for i in 0..40 {
+            p.input.send(i).await.unwrap();
+        }
+        let (out, _) = finish(p).await;
+        assert_eq!(out.len(), 40);
+        assert_eq!(peak.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn slow_stage_pushes_back_to_the_source() {
+        let gated = Gated::new(false);
+        let gate = Arc::clone(&gated.gate);
+        let p = Pipeline::builder(2)
+            .stage(Doubler, StageConfig::new("fast").capacity(3))
+            .stage(
+                gated,
+                StageConfig::new("stuck").concurrency(1).capacity(100),
+            )
+            .build();
+
+        // Keep offering items until the source queue refuses one
+        let mut accepted = 0;
+        loop {
+            match p.input.try_send(accepted) {
+                Ok(()) => accepted += 1,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    // Stages may still be pulling; give them a chance first
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    if p.input.try_send(accepted).is_err() {
+                        break;
+                    }
+                    accepted += 1;
+                }
+                Err(e) => panic!("{:?}", e),
+            }
+            assert!(accepted < 100, "no backpressure");
+        }
+        // source queue 2 + fast in flight 1 + fast->stuck queue 3 + stuck in flight 1
+        assert_eq!(accepted, 7);
+        let m = p.metrics();
+        assert_eq!((m[1].queued, m[1].capacity), (3, 3)); // stuck's input is full
+        assert_eq!(m[1].in_flight, 1);
+
+        gate.add_permits(Semaphore::MAX_PERMITS);
+        let (out, _) = finish(p).await;
+        assert_eq!(out, (0..7).map(|i| i * 2).collect::<Vec<_>>());
+    }
+}
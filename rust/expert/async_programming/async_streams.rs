@@ -38,6 +38,7 @@ fn numbers_stream() -> impl Stream<Item = i32> {
 }
 
 // stream! macro: generator-style, yield values one at a time
+// test_clock.rs has clock-injected versions of countdown and event_stream
 fn countdown(from: i32) -> impl Stream<Item = i32> {
     stream! {
         for i in (1..=from).rev() {
@@ -81,6 +82,7 @@ async fn main() {
     println!(); // 1 2 3 4 5
 
     // --- Stream adaptors (like Iterator adaptors) ---
+    // (time-aware ones — throttle, debounce, chunks_timeout... — are in stream_operators.rs)
     // tokio-stream's StreamExt uses plain closures (not async) for map/filter/fold
 
     // map
@@ -0,0 +1,612 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full"] }
+//! [dev-dependencies]
+//! libc = "0.2"
+//! ```
+
+// Cancellation tokens and graceful shutdown
+//
+// tokio::spawn and thread::spawn are fire-and-forget: once started, nothing
+// tells a task to stop. An AtomicBool `running` flag (atomic_types.rs) works,
+// but only for loops that poll it, and it can't wake a sleeping task.
+//
+// CancellationToken:
+//   token.cancel()          — flips the flag, wakes every waiter (async and thread)
+//   token.cancelled().await — async: resolves once cancelled (use in select!)
+//   token.wait_timeout(d)   — threads: sleep up to d, return early on cancel
+//   token.child_token()     — cancelled with its parent, but cancelling the
+//                             child leaves the parent alone
+//   token.drop_guard()      — cancels when the guard is dropped (scope exit, panic)
+//
+//   root ── server ──┬── connection 1
+//                    └── connection 2
+//        └── metrics
+//   cancel(server) stops the server and its connections, not metrics
+//
+// Shutdown coordinator:
+//   1. waits for SIGINT (Ctrl-C) / SIGTERM, or a manual trigger()
+//   2. cancels the root token — every tracked task sees it
+//   3. waits up to a deadline for tracked tasks (async or threads) to finish
+//   4. reports who finished and who didn't (the stragglers)
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Instant};
+
+// --- CancellationToken ---
+
+struct Node {
+    cancelled: AtomicBool,
+    children: Mutex<Vec<Weak<Node>>>,
+    notify: Notify,  // async waiters
+    lock: Mutex<()>, // thread waiters: Condvar needs a mutex to pair with
+    condvar: Condvar,
+}
+
+impl Node {
+    fn new() -> Arc<Node> {
+        Arc::new(Node {
+            cancelled: AtomicBool::new(false),
+            children: Mutex::new(Vec::new()),
+            notify: Notify::new(),
+            lock: Mutex::new(()),
+            condvar: Condvar::new(),
+        })
+    }
+
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::AcqRel) {
+            return; // already cancelled: children were handled then
+        }
+        self.notify.notify_waiters();
+        {
+            // Taking the lock orders this notify after any thread that has
+            // checked the flag but not yet started waiting
+            let _g = self.lock.lock().unwrap();
+            self.condvar.notify_all();
+        }
+        let children = std::mem::take(&mut *self.children.lock().unwrap());
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CancellationToken {
+    node: Arc<Node>,
+}
+
+impl CancellationToken {
+    fn new() -> Self {
+        CancellationToken { node: Node::new() }
+    }
+
+    fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut children = self.node.children.lock().unwrap();
+        // Checked under the lock: cancel() takes the list before walking it,
+        // so a child is either in the list it walks or sees the flag here
+        if self.is_cancelled() {
+            child.node.cancelled.store(true, Ordering::Release);
+        } else {
+            children.retain(|w| w.strong_count() > 0); // forget dropped children
+            children.push(Arc::downgrade(&child.node));
+        }
+        child
+    }
+
+    fn cancel(&self) {
+        self.node.cancel();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.node.cancelled.load(Ordering::Acquire)
+    }
+
+    async fn cancelled(&self) {
+        loop {
+            let notified = self.node.notify.notified();
+            tokio::pin!(notified);
+            // Register before checking, or a cancel in between is missed
+            notified.as_mut().enable();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // Blocking wait for threads; true if cancelled
+    fn wait_timeout(&self, timeout: Duration) -> bool {
+        let guard = self.node.lock.lock().unwrap();
+        let (_guard, _) = self
+            .node
+            .condvar
+            .wait_timeout_while(guard, timeout, |_| !self.is_cancelled())
+            .unwrap();
+        self.is_cancelled()
+    }
+
+    // Run `fut` unless cancelled first; None if cancelled
+    async fn run_until_cancelled<F: Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => None,
+            out = fut => Some(out),
+        }
+    }
+
+    fn drop_guard(self) -> DropGuard {
+        DropGuard { token: Some(self) }
+    }
+
+    #[cfg(test)]
+    fn live_children(&self) -> usize {
+        let children = self.node.children.lock().unwrap();
+        children.iter().filter(|w| w.strong_count() > 0).count()
+    }
+}
+
+// Cancels the token on drop unless disarmed
+struct DropGuard {
+    token: Option<CancellationToken>,
+}
+
+impl DropGuard {
+    fn disarm(mut self) -> CancellationToken {
+        self.token.take().unwrap()
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if let Some(token) = &self.token {
+            token.cancel();
+        }
+    }
+}
+
+// --- Shutdown coordinator ---
+
+#[derive(Debug, Default)]
+struct ShutdownReport {
+    finished: Vec<String>,
+    stragglers: Vec<String>,
+    elapsed: Duration,
+}
+
+impl ShutdownReport {
+    fn is_clean(&self) -> bool {
+        self.stragglers.is_empty()
+    }
+}
+
+struct Tracker {
+    next_id: AtomicU64,
+    running: Mutex<BTreeMap<u64, String>>,
+    finished: Mutex<Vec<String>>,
+    changed: Notify,
+}
+
+// Removes a task from `running` however it ends — return, panic or abort
+struct Done {
+    tracker: Arc<Tracker>,
+    id: u64,
+}
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        let name = self.tracker.running.lock().unwrap().remove(&self.id);
+        if let Some(name) = name {
+            self.tracker.finished.lock().unwrap().push(name);
+        }
+        self.tracker.changed.notify_waiters();
+    }
+}
+
+struct Shutdown {
+    token: CancellationToken,
+    tracker: Arc<Tracker>,
+}
+
+impl Shutdown {
+    fn new() -> Self {
+        Shutdown {
+            token: CancellationToken::new(),
+            tracker: Arc::new(Tracker {
+                next_id: AtomicU64::new(0),
+                running: Mutex::new(BTreeMap::new()),
+                finished: Mutex::new(Vec::new()),
+                changed: Notify::new(),
+            }),
+        }
+    }
+
+    fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    fn track(&self, name: &str) -> Done {
+        let id = self.tracker.next_id.fetch_add(1, Ordering::Relaxed);
+        self.tracker
+            .running
+            .lock()
+            .unwrap()
+            .insert(id, name.to_string());
+        Done {
+            tracker: Arc::clone(&self.tracker),
+            id,
+        }
+    }
+
+    // Each task gets its own child token, so it can also be cancelled alone
+    fn spawn<F, Fut>(&self, name: &str, task: F) -> JoinHandle<Fut::Output>
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let done = self.track(name);
+        let fut = task(self.token.child_token());
+        tokio::spawn(async move {
+            let _done = done;
+            fut.await
+        })
+    }
+
+    fn spawn_thread<F, T>(&self, name: &str, task: F) -> thread::JoinHandle<T>
+    where
+        F: FnOnce(CancellationToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let done = self.track(name);
+        let token = self.token.child_token();
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                let _done = done;
+                task(token)
+            })
+            .expect("failed to spawn thread")
+    }
+
+    fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    // Cancel on SIGINT/SIGTERM; call once, early
+    fn listen_for_signals(&self) -> JoinHandle<()> {
+        let token = self.token();
+        tokio::spawn(async move {
+            let signal = wait_for_signal();
+            tokio::select! {
+                name = signal => {
+                    println!("received {}, shutting down", name);
+                    token.cancel();
+                }
+                _ = token.cancelled() => {}
+            }
+        })
+    }
+
+    // Wait until cancelled (by a signal or trigger), then drain
+    async fn run_until_shutdown(&self, deadline: Duration) -> ShutdownReport {
+        self.token.cancelled().await;
+        self.shutdown(deadline).await
+    }
+
+    // Cancel everything and wait up to `deadline` for tracked tasks
+    async fn shutdown(&self, deadline: Duration) -> ShutdownReport {
+        let start = Instant::now();
+        self.token.cancel();
+        let limit = start + deadline;
+        loop {
+            let changed = self.tracker.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            if self.tracker.running.lock().unwrap().is_empty() {
+                break;
+            }
+            if tokio::time::timeout_at(limit, changed).await.is_err() {
+                break; // deadline hit
+            }
+        }
+        ShutdownReport {
+            finished: self.tracker.finished.lock().unwrap().clone(),
+            stragglers: self
+                .tracker
+                .running
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect(),
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut term = signal(SignalKind::terminate()).expect("SIGTERM handler");
+    let mut int = signal(SignalKind::interrupt()).expect("SIGINT handler");
+    tokio::select! {
+        _ = term.recv() => "SIGTERM",
+        _ = int.recv() => "SIGINT",
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    tokio::signal::ctrl_c().await.expect("Ctrl-C handler");
+    "Ctrl-C"
+}
+
+// --- Example workers ---
+
+// Cooperative async worker: does a unit of work, checks for cancellation between units
+async fn ticker(name: &'static str, token: CancellationToken) -> u32 {
+    let mut ticks = 0;
+    while token
+        .run_until_cancelled(sleep(Duration::from_millis(10)))
+        .await
+        .is_some()
+    {
+        ticks += 1;
+    }
+    println!("  {} stopped after {} ticks", name, ticks);
+    ticks
+}
+
+// Cooperative thread worker: the token replaces the AtomicBool flag and
+// wakes the thread mid-sleep instead of after it
+fn poller(token: CancellationToken) -> u32 {
+    let mut polls = 0;
+    while !token.wait_timeout(Duration::from_millis(10)) {
+        polls += 1;
+    }
+    polls
+}
+
+#[tokio::main]
+async fn main() {
+    // --- Hierarchy ---
+    let root = CancellationToken::new();
+    let server = root.child_token();
+    let conn = server.child_token();
+    let metrics = root.child_token();
+    server.cancel();
+    println!(
+        "{} {} {} {}",
+        root.is_cancelled(),
+        server.is_cancelled(),
+        conn.is_cancelled(),
+        metrics.is_cancelled()
+    ); // false true true false
+    root.cancel();
+    println!("{}", metrics.is_cancelled()); // true
+
+    // --- Drop guard: cancel when a scope ends, even by panic or early return ---
+    let token = CancellationToken::new();
+    {
+        let _guard = token.clone().drop_guard();
+    }
+    println!("{}", token.is_cancelled()); // true
+    let kept = CancellationToken::new();
+    let token = kept.clone().drop_guard().disarm();
+    println!("{}", token.is_cancelled()); // false
+
+    // --- select! on cancelled() ---
+    let token = CancellationToken::new();
+    let waiter = tokio::spawn({
+        let token = token.clone();
+        async move {
+            tokio::select! {
+                _ = token.cancelled() => "cancelled",
+                _ = sleep(Duration::from_secs(10)) => "timed out",
+            }
+        }
+    });
+    sleep(Duration::from_millis(5)).await;
+    token.cancel();
+    println!("{}", waiter.await.unwrap()); // cancelled
+
+    // --- Shutdown coordinator ---
+    let shutdown = Shutdown::new();
+    let _signals = shutdown.listen_for_signals(); // Ctrl-C here works too
+    let a = shutdown.spawn("ticker-a", |t| ticker("ticker-a", t));
+    let b = shutdown.spawn("ticker-b", |t| ticker("ticker-b", t));
+    let p = shutdown.spawn_thread("poller", poller);
+    // Ignores its token: will be reported as a straggler
+    shutdown.spawn("stubborn", |_t| sleep(Duration::from_secs(5)));
+
+    // trigger() stands in for a signal arriving
+    let (_, report) = tokio::join!(
+        async {
+            sleep(Duration::from_millis(35)).await;
+            shutdown.trigger();
+        },
+        shutdown.run_until_shutdown(Duration::from_millis(100)),
+    );
+    // ticker-a stopped after 3 ticks
+    // ticker-b stopped after 3 ticks
+    let mut finished = report.finished.clone();
+    finished.sort();
+    println!("finished:   {:?}", finished); // finished:   ["poller", "ticker-a", "ticker-b"]
+    println!("stragglers: {:?}", report.stragglers); // stragglers: ["stubborn"]
+    println!(
+        "clean: {}, waited ~{}ms",
+        report.is_clean(),
+        report.elapsed.as_millis()
+    ); // clean: false, waited ~100ms
+    let ticks = a.await.unwrap() + b.await.unwrap();
+    let polls = p.join().unwrap();
+    println!("{} {}", ticks > 0, polls > 0); // true true
+
+    println!("cancellation done"); // cancellation done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn cancellation_flows_down_not_up() {
+        let root = CancellationToken::new();
+        let a = root.child_token();
+        let a1 = a.child_token();
+        let b = root.child_token();
+
+        a1.cancel();
+        assert!(!a.is_cancelled() && !root.is_cancelled());
+        a.cancel();
+        assert!(!b.is_cancelled() && !root.is_cancelled());
+        root.cancel();
+        assert!(b.is_cancelled());
+
+        // Children of an already-cancelled token start cancelled
+        assert!(root.child_token().is_cancelled());
+        assert!(a1.child_token().child_token().is_cancelled());
+    }
+
+    #[test]
+    fn dropped_children_are_not_kept_alive() {
+        let root = CancellationToken::new();
+        let kept = root.child_token();
+        for _ in 0..100 {
+            let _short_lived = root.child_token();
+        }
+        assert_eq!(root.live_children(), 1);
+        assert!(root.node.children.lock().unwrap().len() <= 2);
+        root.cancel();
+        assert!(kept.is_cancelled());
+    }
+
+    #[test]
+    fn drop_guard_cancels_unless_disarmed() {
+        let token = CancellationToken::new();
+        let t2 = token.clone();
+        let r = std::panic::catch_unwind(move || {
+            let _guard = t2.drop_guard();
+            panic!("boom");
+        });
+        assert!(r.is_err());
+        assert!(token.is_cancelled());
+
+        let token = CancellationToken::new();
+        let back = token.clone().drop_guard().disarm();
+        assert!(!token.is_cancelled() && !back.is_cancelled());
+    }
+
+    #[test]
+    fn thread_wait_wakes_on_cancel() {
+        let root = CancellationToken::new();
+        let child = root.child_token();
+        let start = std::time::Instant::now();
+        let h = thread::spawn(move || child.wait_timeout(Duration::from_secs(10)));
+        thread::sleep(ms(20));
+        root.cancel();
+        assert!(h.join().unwrap());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // Timeout without cancel reports false
+        assert!(!CancellationToken::new().wait_timeout(ms(5)));
+    }
+
+    #[tokio::test]
+    async fn async_waiters_wake_and_late_waiters_return_at_once() {
+        let root = CancellationToken::new();
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let t = root.child_token();
+                tokio::spawn(async move { t.cancelled().await })
+            })
+            .collect();
+        sleep(ms(5)).await;
+        root.cancel();
+        for w in waiters {
+            tokio::time::timeout(ms(500), w).await.unwrap().unwrap();
+        }
+        tokio::time::timeout(ms(10), root.cancelled())
+            .await
+            .unwrap();
+        let out = root.run_until_cancelled(async { 1 }).await;
+        assert_eq!(out, None);
+    }
+
+    #[tokio::test]
+    async fn shutdown_reports_stragglers_after_deadline() {
+        let shutdown = Shutdown::new();
+        shutdown.spawn("async-ok", |t| async move { t.cancelled().await });
+        shutdown.spawn_thread("thread-ok", |t| {
+            t.wait_timeout(Duration::from_secs(10));
+        });
+        shutdown.spawn("quick", |_| async {}); // finishes before shutdown
+        shutdown.spawn("stuck", |_| sleep(Duration::from_secs(10)));
+        shutdown.spawn_thread("stuck-thread", |_| thread::sleep(ms(300)));
+        sleep(ms(5)).await;
+
+        let report = shutdown.shutdown(ms(80)).await;
+        let mut finished = report.finished.clone();
+        finished.sort();
+        assert_eq!(finished, ["async-ok", "quick", "thread-ok"]);
+        assert_eq!(report.stragglers, ["stuck", "stuck-thread"]);
+        assert!(!report.is_clean());
+        assert!(report.elapsed >= ms(80) && report.elapsed < ms(250));
+    }
+
+    #[tokio::test]
+    async fn shutdown_returns_early_when_everyone_cooperates() {
+        let shutdown = Shutdown::new();
+        for name in ["a", "b", "c"] {
+            shutdown.spawn(name, |t| async move { t.cancelled().await });
+        }
+        let panicky = shutdown.spawn("panics", |t| async move {
+            t.cancelled().await;
+            panic!("cleanup failed");
+        });
+        let report = shutdown.shutdown(Duration::from_secs(5)).await;
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!(report.finished.len(), 4);
+        assert!(report.elapsed < Duration::from_secs(1));
+        assert!(panicky.await.unwrap_err().is_panic());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sigterm_triggers_shutdown() {
+        let shutdown = Shutdown::new();
+        let listener = shutdown.listen_for_signals();
+        shutdown.spawn("worker", |t| async move { t.cancelled().await });
+        sleep(ms(20)).await; // let the listener install its handlers
+        unsafe {
+            libc::kill(libc::getpid(), libc::SIGTERM);
+        }
+        let report =
+            tokio::time::timeout(Duration::from_secs(2), shutdown.run_until_shutdown(ms(500)))
+                .await
+                .expect("signal never arrived");
+        assert!(report.is_clean());
+        assert_eq!(report.finished, ["worker"]);
+        listener.await.unwrap();
+    }
+}
@@ -51,6 +51,7 @@ impl Future for ReadyAfter {
             Poll::Ready(self.value)
         } else {
             // Schedule a wakeup so the runtime tries again
+            // (a whole task per wakeup; mini_executor.rs registers with a timer wheel instead)
             let waker = cx.waker().clone();
             let deadline = self.deadline;
             tokio::spawn(async move {
@@ -144,6 +145,7 @@ async fn pinning_demo() {
 // --- Async trait methods (requires boxing) ---
 // async fn in traits isn't directly stable in all contexts
 // Common pattern: return Box<dyn Future<...> + Send>
+// async_pipeline.rs chains processors like this into stages with bounded queues
 
 trait AsyncProcessor {
     fn process<'a>(&'a self, input: i32) -> Pin<Box<dyn Future<Output = i32> + Send + 'a>>;
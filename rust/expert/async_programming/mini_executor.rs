@@ -0,0 +1,959 @@
+// A minimal single-threaded async executor with timers — no tokio
+//
+// futures.rs needs tokio even for its hand-written ReadyAfter future, which
+// spawns a whole tokio task just to call waker.wake() later. This file builds
+// the missing piece from std alone:
+//
+//   Runtime::block_on(fut)   — drive a future to completion on this thread
+//   spawn(fut) -> JoinHandle — run a task concurrently; await the handle for its output
+//   sleep(d) / timeout(d, f) — backed by a hashed timer wheel (1ms ticks)
+//
+// Two clocks:
+//   Runtime::new()          — real time; idle means "park until the next timer
+//                             or until another thread calls wake()"
+//   Runtime::new_virtual()  — virtual time; idle means "jump straight to the next
+//                             timer". A 10-minute sleep finishes instantly and
+//                             every run is identical. run_until_idle() and
+//                             advance(d) let tests step through time by hand.
+//
+// How waking works:
+//   every task has an Arc<TaskWaker> holding its id; wake() pushes the id onto a
+//   shared ready queue (Mutex + Condvar, so wakers are Send + Sync as Waker
+//   requires). The loop pops ids and polls those tasks — nothing is polled
+//   unless something woke it.
+//
+// Not included: multi-threading, I/O readiness (epoll/kqueue), task abort,
+// panic isolation — a panicking task takes block_on down with it.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+// --- Wakers ---
+
+type TaskId = usize;
+const MAIN: TaskId = usize::MAX; // the future passed to block_on
+
+struct ReadyQueue {
+    ids: Mutex<VecDeque<TaskId>>,
+    cv: Condvar,
+}
+
+struct TaskWaker {
+    id: TaskId,
+    queued: AtomicBool, // dedupe: waking a task twice before it runs queues it once
+    queue: Arc<ReadyQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            self.queue.ids.lock().unwrap().push_back(self.id);
+            self.queue.cv.notify_one();
+        }
+    }
+}
+
+// --- Timer wheel ---
+// SLOTS buckets, one per tick modulo SLOTS. A timer due in 1000 ticks sits in
+// bucket 1000 % 64 and is skipped (deadline not reached) on earlier laps.
+// Insert/remove are O(bucket); advancing one tick only looks at one bucket.
+
+const SLOTS: usize = 64;
+
+struct TimerEntry {
+    id: u64,
+    deadline: u64,
+    waker: Waker,
+}
+
+struct TimerWheel {
+    slots: Vec<Vec<TimerEntry>>,
+    processed: u64, // every deadline <= processed has been fired
+    len: usize,
+}
+
+impl TimerWheel {
+    fn new() -> Self {
+        TimerWheel {
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+            processed: 0,
+            len: 0,
+        }
+    }
+
+    fn slot(deadline: u64) -> usize {
+        (deadline % SLOTS as u64) as usize
+    }
+
+    // Returns the deadline actually used: never in an already-processed tick
+    fn insert(&mut self, id: u64, deadline: u64, waker: Waker) -> u64 {
+        let deadline = deadline.max(self.processed + 1);
+        self.slots[Self::slot(deadline)].push(TimerEntry {
+            id,
+            deadline,
+            waker,
+        });
+        self.len += 1;
+        deadline
+    }
+
+    fn update_waker(&mut self, id: u64, deadline: u64, waker: &Waker) {
+        if let Some(e) = self.slots[Self::slot(deadline)]
+            .iter_mut()
+            .find(|e| e.id == id)
+        {
+            if !e.waker.will_wake(waker) {
+                e.waker = waker.clone();
+            }
+        }
+    }
+
+    fn remove(&mut self, id: u64, deadline: u64) {
+        let slot = &mut self.slots[Self::slot(deadline)];
+        if let Some(pos) = slot.iter().position(|e| e.id == id) {
+            slot.swap_remove(pos);
+            self.len -= 1;
+        }
+    }
+
+    fn next_deadline(&self) -> Option<u64> {
+        self.slots.iter().flatten().map(|e| e.deadline).min()
+    }
+
+    // Fire everything due at or before `now`; returns the wakers to call
+    fn advance(&mut self, now: u64) -> Vec<Waker> {
+        let mut fired = Vec::new();
+        if now <= self.processed {
+            return fired;
+        }
+        let ticks = now - self.processed;
+        let visit: Vec<usize> = if ticks >= SLOTS as u64 {
+            (0..SLOTS).collect()
+        } else {
+            (self.processed + 1..=now).map(Self::slot).collect()
+        };
+        for s in visit {
+            let slot = &mut self.slots[s];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].deadline <= now {
+                    fired.push(slot.swap_remove(i).waker);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.len -= fired.len();
+        self.processed = now;
+        fired
+    }
+}
+
+// --- Clock ---
+
+enum Clock {
+    Real(Instant),
+    Virtual(u64), // milliseconds since the runtime started
+}
+
+impl Clock {
+    fn now_ms(&self) -> u64 {
+        match self {
+            Clock::Real(start) => start.elapsed().as_millis() as u64,
+            Clock::Virtual(ms) => *ms,
+        }
+    }
+}
+
+fn ceil_ms(d: Duration) -> u64 {
+    d.as_nanos().div_ceil(1_000_000) as u64
+}
+
+// --- Runtime ---
+
+struct Task {
+    future: Option<Pin<Box<dyn Future<Output = ()>>>>, // None while being polled
+    waker: Arc<TaskWaker>,
+}
+
+struct Inner {
+    tasks: HashMap<TaskId, Task>,
+    next_task: TaskId,
+    timers: TimerWheel,
+    next_timer: u64,
+    clock: Clock,
+}
+
+#[derive(Clone)]
+struct Handle {
+    inner: Rc<RefCell<Inner>>,
+    queue: Arc<ReadyQueue>,
+}
+
+thread_local! {
+    // Set while the runtime is running, so spawn()/sleep() can find it
+    static CURRENT: RefCell<Option<Handle>> = const { RefCell::new(None) };
+}
+
+fn with_current<R>(f: impl FnOnce(&Handle) -> R) -> R {
+    let handle = CURRENT.with(|c| c.borrow().clone());
+    f(&handle.expect("must be called from inside a mini_executor Runtime"))
+}
+
+struct EnterGuard;
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|c| *c.borrow_mut() = None);
+    }
+}
+
+struct Runtime {
+    handle: Handle,
+}
+
+impl Runtime {
+    fn new() -> Self {
+        Self::with_clock(Clock::Real(Instant::now()))
+    }
+
+    fn new_virtual() -> Self {
+        Self::with_clock(Clock::Virtual(0))
+    }
+
+    fn with_clock(clock: Clock) -> Self {
+        Runtime {
+            handle: Handle {
+                inner: Rc::new(RefCell::new(Inner {
+                    tasks: HashMap::new(),
+                    next_task: 0,
+                    timers: TimerWheel::new(),
+                    next_timer: 0,
+                    clock,
+                })),
+                queue: Arc::new(ReadyQueue {
+                    ids: Mutex::new(VecDeque::new()),
+                    cv: Condvar::new(),
+                }),
+            },
+        }
+    }
+
+    fn enter(&self) -> EnterGuard {
+        CURRENT.with(|c| {
+            let mut c = c.borrow_mut();
+            assert!(c.is_none(), "cannot start a Runtime from inside a Runtime");
+            *c = Some(self.handle.clone());
+        });
+        EnterGuard
+    }
+
+    fn now(&self) -> Duration {
+        Duration::from_millis(self.handle.now_ms())
+    }
+
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        self.handle.spawn(future)
+    }
+
+    fn pending_tasks(&self) -> usize {
+        self.handle.inner.borrow().tasks.len()
+    }
+
+    fn pending_timers(&self) -> usize {
+        self.handle.inner.borrow().timers.len
+    }
+
+    // Run `future` to completion, running spawned tasks and timers meanwhile
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _enter = self.enter();
+        let mut future = std::pin::pin!(future);
+        let main_waker = Arc::new(TaskWaker {
+            id: MAIN,
+            queued: AtomicBool::new(false),
+            queue: Arc::clone(&self.handle.queue),
+        });
+        main_waker.wake_by_ref();
+        let waker = Waker::from(Arc::clone(&main_waker));
+
+        loop {
+            self.handle.fire_timers();
+            match self.handle.pop_ready() {
+                Some(MAIN) => {
+                    main_waker.queued.store(false, Ordering::Release);
+                    if let Poll::Ready(v) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                        return v;
+                    }
+                }
+                Some(id) => self.handle.poll_task(id),
+                None => self.handle.park(),
+            }
+        }
+    }
+
+    // Poll every ready task (and fire due timers) until nothing is runnable.
+    // Never moves the clock. Returns how many polls happened.
+    fn run_until_idle(&self) -> usize {
+        let _enter = self.enter();
+        self.handle.run_until_idle()
+    }
+
+    // Virtual clock only: move time forward by `d`, firing timers in deadline
+    // order and letting woken tasks run at each step
+    fn advance(&self, d: Duration) {
+        let _enter = self.enter();
+        let target = self.handle.now_ms() + ceil_ms(d);
+        self.handle.run_until_idle();
+        loop {
+            let next = self.handle.inner.borrow().timers.next_deadline();
+            match next {
+                Some(t) if t <= target => self.handle.set_virtual_now(t),
+                _ => break,
+            }
+            self.handle.run_until_idle();
+        }
+        self.handle.set_virtual_now(target);
+        self.handle.run_until_idle();
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handle {
+    fn now_ms(&self) -> u64 {
+        self.inner.borrow().clock.now_ms()
+    }
+
+    fn set_virtual_now(&self, ms: u64) {
+        match &mut self.inner.borrow_mut().clock {
+            Clock::Virtual(now) => *now = (*now).max(ms),
+            Clock::Real(_) => panic!("advance() needs Runtime::new_virtual()"),
+        }
+    }
+
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let slot = Rc::new(RefCell::new(JoinSlot {
+            result: None,
+            waiter: None,
+        }));
+        let task_slot = Rc::clone(&slot);
+        let wrapped = async move {
+            let output = future.await;
+            let mut s = task_slot.borrow_mut();
+            s.result = Some(output);
+            if let Some(w) = s.waiter.take() {
+                w.wake();
+            }
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        let id = inner.next_task;
+        inner.next_task += 1;
+        let waker = Arc::new(TaskWaker {
+            id,
+            queued: AtomicBool::new(false),
+            queue: Arc::clone(&self.queue),
+        });
+        waker.wake_by_ref(); // new tasks start runnable
+        inner.tasks.insert(
+            id,
+            Task {
+                future: Some(Box::pin(wrapped)),
+                waker,
+            },
+        );
+        JoinHandle { slot }
+    }
+
+    fn pop_ready(&self) -> Option<TaskId> {
+        self.queue.ids.lock().unwrap().pop_front()
+    }
+
+    fn poll_task(&self, id: TaskId) {
+        // Take the future out so the task can call spawn()/sleep() — which
+        // borrow `inner` — while it's being polled
+        let taken = match self.inner.borrow_mut().tasks.get_mut(&id) {
+            Some(task) => task.future.take().map(|f| (f, Arc::clone(&task.waker))),
+            None => None, // finished; stale wake-up
+        };
+        let Some((mut future, task_waker)) = taken else {
+            return;
+        };
+        task_waker.queued.store(false, Ordering::Release);
+        let waker = Waker::from(task_waker);
+
+        let done = future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready();
+        if done {
+            self.inner.borrow_mut().tasks.remove(&id);
+            drop(future); // outside the borrow: its destructor may touch timers
+        } else if let Some(task) = self.inner.borrow_mut().tasks.get_mut(&id) {
+            task.future = Some(future);
+        }
+    }
+
+    fn fire_timers(&self) {
+        let wakers = {
+            let mut inner = self.inner.borrow_mut();
+            let now = inner.clock.now_ms();
+            inner.timers.advance(now)
+        };
+        for w in wakers {
+            w.wake();
+        }
+    }
+
+    fn run_until_idle(&self) -> usize {
+        let mut polls = 0;
+        loop {
+            self.fire_timers();
+            match self.pop_ready() {
+                Some(MAIN) => {} // only block_on polls the main future
+                Some(id) => {
+                    self.poll_task(id);
+                    polls += 1;
+                }
+                None => return polls,
+            }
+        }
+    }
+
+    // Nothing is ready: wait for a timer or a wake-up from another thread
+    fn park(&self) {
+        let (now, next) = {
+            let inner = self.inner.borrow();
+            (inner.clock.now_ms(), inner.timers.next_deadline())
+        };
+        if matches!(self.inner.borrow().clock, Clock::Virtual(_)) {
+            // Nobody else can make progress in virtual time: jump, or give up
+            match next {
+                Some(t) => self.set_virtual_now(t),
+                None => panic!("block_on: future can never complete (no ready tasks, no timers)"),
+            }
+            return;
+        }
+        let ids = self.queue.ids.lock().unwrap();
+        if !ids.is_empty() {
+            return;
+        }
+        match next {
+            Some(t) => {
+                let wait = Duration::from_millis(t.saturating_sub(now));
+                drop(self.queue.cv.wait_timeout(ids, wait).unwrap());
+            }
+            None => drop(self.queue.cv.wait(ids).unwrap()),
+        }
+    }
+}
+
+// --- JoinHandle ---
+
+struct JoinSlot<T> {
+    result: Option<T>,
+    waiter: Option<Waker>,
+}
+
+// Await it to get the task's output. Dropping it detaches the task.
+struct JoinHandle<T> {
+    slot: Rc<RefCell<JoinSlot<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    fn is_finished(&self) -> bool {
+        self.slot.borrow().result.is_some()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.result.take() {
+            Some(v) => Poll::Ready(v),
+            None => {
+                slot.waiter = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    with_current(|h| h.spawn(future))
+}
+
+// --- Sleep ---
+// Holds only plain data (no Rc), so it is Send and fits in `dyn Future + Send`.
+// The deadline is fixed when sleep() is called inside a runtime; a Sleep built
+// outside one (e.g. `rt.block_on(sleep(d))`) starts counting at its first poll.
+
+struct Sleep {
+    duration: u64,
+    deadline: Option<u64>,
+    timer: Option<(u64, u64)>, // (timer id, deadline used in the wheel)
+}
+
+fn sleep(d: Duration) -> Sleep {
+    let now = CURRENT.with(|c| c.borrow().as_ref().map(|h| h.now_ms()));
+    Sleep {
+        duration: ceil_ms(d),
+        deadline: now.map(|now| now + ceil_ms(d)),
+        timer: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        with_current(|h| {
+            let mut inner = h.inner.borrow_mut();
+            let now = inner.clock.now_ms();
+            let duration = self.duration;
+            let deadline = *self.deadline.get_or_insert(now + duration);
+            if now >= deadline {
+                if let Some((id, at)) = self.timer.take() {
+                    inner.timers.remove(id, at);
+                }
+                return Poll::Ready(());
+            }
+            match self.timer {
+                Some((id, at)) => inner.timers.update_waker(id, at, cx.waker()),
+                None => {
+                    let id = inner.next_timer;
+                    inner.next_timer += 1;
+                    let at = inner.timers.insert(id, deadline, cx.waker().clone());
+                    self.timer = Some((id, at));
+                }
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl Drop for Sleep {
+    // A cancelled sleep (e.g. the losing side of a timeout) must not leave its
+    // timer behind
+    fn drop(&mut self) {
+        if let Some((id, at)) = self.timer {
+            let handle = CURRENT.with(|c| c.borrow().clone());
+            if let Some(h) = handle {
+                h.inner.borrow_mut().timers.remove(id, at);
+            }
+        }
+    }
+}
+
+// --- Timeout ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+// The inner future is boxed so Timeout is Unpin without pin-projection tricks
+struct Timeout<F: Future> {
+    future: Pin<Box<F>>,
+    sleep: Sleep,
+}
+
+fn timeout<F: Future>(d: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future: Box::pin(future),
+        sleep: sleep(d),
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The future gets the first chance: finishing exactly at the deadline wins
+        if let Poll::Ready(v) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(v));
+        }
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+// --- futures.rs examples, now without tokio ---
+
+// Same shape as futures.rs, but the runtime's timer does the waking
+struct ReadyAfter {
+    sleep: Sleep,
+    value: i32,
+}
+
+impl ReadyAfter {
+    fn new(delay_ms: u64, value: i32) -> Self {
+        ReadyAfter {
+            sleep: sleep(Duration::from_millis(delay_ms)),
+            value,
+        }
+    }
+}
+
+impl Future for ReadyAfter {
+    type Output = i32;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<i32> {
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(self.value),
+            Poll::Pending => Poll::Pending, // timer wheel holds our waker
+        }
+    }
+}
+
+trait AsyncProcessor {
+    fn process<'a>(&'a self, input: i32) -> Pin<Box<dyn Future<Output = i32> + Send + 'a>>;
+}
+
+struct Doubler;
+
+impl AsyncProcessor for Doubler {
+    fn process<'a>(&'a self, input: i32) -> Pin<Box<dyn Future<Output = i32> + Send + 'a>> {
+        Box::pin(async move { input * 2 })
+    }
+}
+
+// Simulates a slow backend
+struct SlowSquarer {
+    delay: Duration,
+}
+
+impl AsyncProcessor for SlowSquarer {
+    fn process<'a>(&'a self, input: i32) -> Pin<Box<dyn Future<Output = i32> + Send + 'a>> {
+        Box::pin(async move {
+            sleep(self.delay).await;
+            input * input
+        })
+    }
+}
+
+fn main() {
+    // --- Real clock ---
+    let rt = Runtime::new();
+    let start = Instant::now();
+    let v = rt.block_on(ReadyAfter::new(20, 42));
+    println!("{} after ~{}ms", v, start.elapsed().as_millis()); // 42 after ~20ms
+
+    // Spawned tasks interleave at their .await points
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let total = rt.block_on({
+        let log = Rc::clone(&log);
+        async move {
+            let handles: Vec<JoinHandle<u64>> = [30u64, 10, 20]
+                .into_iter()
+                .map(|ms| {
+                    let log = Rc::clone(&log);
+                    spawn(async move {
+                        sleep(Duration::from_millis(ms)).await;
+                        log.borrow_mut().push(ms);
+                        ms
+                    })
+                })
+                .collect();
+            let mut total = 0;
+            for h in handles {
+                total += h.await;
+            }
+            total
+        }
+    });
+    println!("{:?} total {}", log.borrow(), total); // [10, 20, 30] total 60
+
+    // Timeouts
+    rt.block_on(async {
+        let fast = timeout(Duration::from_millis(50), ReadyAfter::new(5, 1)).await;
+        let slow = timeout(Duration::from_millis(5), ReadyAfter::new(50, 2)).await;
+        println!("{:?} {:?}", fast, slow); // Ok(1) Err(Elapsed)
+    });
+
+    // AsyncProcessor implementations, driven by our executor
+    let processors: Vec<Box<dyn AsyncProcessor>> = vec![
+        Box::new(Doubler),
+        Box::new(SlowSquarer {
+            delay: Duration::from_millis(5),
+        }),
+    ];
+    let results: Vec<i32> = processors
+        .iter()
+        .map(|p| rt.block_on(p.process(7)))
+        .collect();
+    println!("{:?}", results); // [14, 49]
+
+    // --- Virtual clock: an hour of sleeping takes no time at all ---
+    let vrt = Runtime::new_virtual();
+    let start = Instant::now();
+    vrt.block_on(async {
+        for _ in 0..60 {
+            sleep(Duration::from_secs(60)).await;
+        }
+    });
+    println!(
+        "virtual {:?}, real {}ms",
+        vrt.now(),
+        start.elapsed().as_millis()
+    ); // virtual 3600s, real 0ms
+
+    // Manual stepping
+    let vrt = Runtime::new_virtual();
+    let ticks = Rc::new(RefCell::new(0));
+    let t = Rc::clone(&ticks);
+    vrt.spawn(async move {
+        loop {
+            sleep(Duration::from_millis(100)).await;
+            *t.borrow_mut() += 1;
+        }
+    });
+    let once = vrt.spawn(async { sleep(Duration::from_millis(150)).await });
+    vrt.run_until_idle(); // both tasks reach their first sleep
+    println!(
+        "{} tasks, {} timers",
+        vrt.pending_tasks(),
+        vrt.pending_timers()
+    ); // 2 tasks, 2 timers
+
+    vrt.advance(Duration::from_millis(250));
+    println!("ticks after 250ms: {}", ticks.borrow()); // ticks after 250ms: 2
+    println!("one-shot finished: {}", once.is_finished()); // one-shot finished: true
+
+    println!("mini executor done"); // mini executor done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn block_on_ready_future() {
+        let rt = Runtime::new_virtual();
+        assert_eq!(rt.block_on(async { 1 + 2 }), 3);
+        assert_eq!(rt.now(), Duration::ZERO);
+    }
+
+    #[test]
+    fn join_handles_return_task_output() {
+        let rt = Runtime::new_virtual();
+        let out = rt.block_on(async {
+            let a = spawn(async { "a".to_string() });
+            let b = spawn(async {
+                sleep(Duration::from_millis(5)).await;
+                7
+            });
+            (a.await, b.await)
+        });
+        assert_eq!(out, ("a".to_string(), 7));
+        assert_eq!(rt.pending_tasks(), 0);
+    }
+
+    #[test]
+    fn virtual_sleeps_fire_in_deadline_order() {
+        let rt = Runtime::new_virtual();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for ms in [300u64, 100, 200, 100] {
+            let log = Rc::clone(&log);
+            rt.spawn(async move {
+                sleep(Duration::from_millis(ms)).await;
+                log.borrow_mut().push(ms);
+            });
+        }
+        rt.block_on(sleep(Duration::from_millis(1000)));
+        assert_eq!(*log.borrow(), vec![100, 100, 200, 300]);
+        assert_eq!(rt.now(), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn timers_beyond_one_wheel_lap() {
+        let rt = Runtime::new_virtual();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        // 1 and 65 share a bucket (65 % 64 == 1); 1000 is many laps out
+        for ms in [1000u64, 65, 1] {
+            let log = Rc::clone(&log);
+            rt.spawn(async move {
+                sleep(Duration::from_millis(ms)).await;
+                log.borrow_mut().push(rt_now_ms());
+            });
+        }
+        fn rt_now_ms() -> u64 {
+            with_current(|h| h.now_ms())
+        }
+        rt.advance(Duration::from_millis(64));
+        assert_eq!(*log.borrow(), vec![1]);
+        rt.advance(Duration::from_millis(10_000));
+        assert_eq!(*log.borrow(), vec![1, 65, 1000]);
+    }
+
+    #[test]
+    fn run_until_idle_does_not_move_time() {
+        let rt = Runtime::new_virtual();
+        let count = Rc::new(RefCell::new(0));
+        let c = Rc::clone(&count);
+        rt.spawn(async move {
+            loop {
+                *c.borrow_mut() += 1;
+                sleep(Duration::from_millis(100)).await;
+            }
+        });
+        rt.run_until_idle();
+        assert_eq!(*count.borrow(), 1);
+        rt.run_until_idle();
+        assert_eq!(*count.borrow(), 1);
+        rt.advance(Duration::from_millis(250));
+        assert_eq!(*count.borrow(), 3); // t=0, 100, 200
+        assert_eq!(rt.now(), Duration::from_millis(250));
+        assert_eq!(rt.pending_timers(), 1);
+    }
+
+    #[test]
+    fn timeout_cancels_the_losing_timer() {
+        let rt = Runtime::new_virtual();
+        let (fast, slow) = rt.block_on(async {
+            let fast = timeout(Duration::from_secs(10), ReadyAfter::new(5, 1)).await;
+            let slow = timeout(Duration::from_millis(5), ReadyAfter::new(10_000, 2)).await;
+            (fast, slow)
+        });
+        assert_eq!(fast, Ok(1));
+        assert_eq!(slow, Err(Elapsed));
+        assert_eq!(rt.now(), Duration::from_millis(10)); // 5 + 5, never 10s
+        assert_eq!(rt.pending_timers(), 0);
+    }
+
+    #[test]
+    fn async_processors_run_without_tokio() {
+        let rt = Runtime::new_virtual();
+        let slow = SlowSquarer {
+            delay: Duration::from_millis(40),
+        };
+        let results = rt.block_on(async {
+            let a = Doubler.process(4).await;
+            let b = slow.process(4).await;
+            (a, b)
+        });
+        assert_eq!(results, (8, 16));
+        assert_eq!(rt.now(), Duration::from_millis(40));
+    }
+
+    #[test]
+    fn real_clock_sleep_waits() {
+        let rt = Runtime::new();
+        let start = Instant::now();
+        rt.block_on(sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    // A one-shot value filled in by another OS thread
+    struct Shared {
+        value: Option<i32>,
+        waker: Option<Waker>,
+    }
+
+    struct FromThread(Arc<Mutex<Shared>>);
+
+    impl Future for FromThread {
+        type Output = i32;
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<i32> {
+            let mut s = self.0.lock().unwrap();
+            match s.value {
+                Some(v) => Poll::Ready(v),
+                None => {
+                    s.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wakers_work_across_threads() {
+        let shared = Arc::new(Mutex::new(Shared {
+            value: None,
+            waker: None,
+        }));
+        let remote = Arc::clone(&shared);
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            let mut s = remote.lock().unwrap();
+            s.value = Some(99);
+            if let Some(w) = s.waker.take() {
+                w.wake();
+            }
+        });
+        let rt = Runtime::new();
+        assert_eq!(rt.block_on(FromThread(shared)), 99);
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn duplicate_wakes_poll_once() {
+        struct CountPolls(Rc<RefCell<u32>>);
+        impl Future for CountPolls {
+            type Output = ();
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+                *self.0.borrow_mut() += 1;
+                if *self.0.borrow() == 1 {
+                    cx.waker().wake_by_ref();
+                    cx.waker().wake_by_ref();
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                } else {
+                    Poll::Ready(())
+                }
+            }
+        }
+        let rt = Runtime::new_virtual();
+        let polls = Rc::new(RefCell::new(0));
+        rt.spawn(CountPolls(Rc::clone(&polls)));
+        assert_eq!(rt.run_until_idle(), 2);
+        assert_eq!(*polls.borrow(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "can never complete")]
+    fn virtual_deadlock_is_reported() {
+        let rt = Runtime::new_virtual();
+        rt.block_on(std::future::pending::<()>());
+    }
+}
@@ -0,0 +1,689 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full", "test-util"] }
+//! ```
+
+// Retry policies: backoff, jitter, error predicates, time budgets
+//
+// The retry! macro in macro_patterns.rs retries immediately, throws the error
+// away, and only prints which attempt worked. Real retry logic must decide:
+//
+//   how long to wait   — Backoff::Constant / Exponential / DecorrelatedJitter
+//   whether to retry   — a predicate on the error (don't retry "404 Not Found")
+//   when to give up    — max attempts and/or a max total elapsed time
+//   what to report     — the *last* error, how many attempts ran, and why we stopped
+//
+//   let policy = RetryPolicy::exponential(ms(100)).max_attempts(5).retry_if(is_transient);
+//   policy.retry(|attempt| call_service())                 // blocking: thread::sleep
+//   policy.retry_async(|attempt| call_service_async()).await  // async: tokio::time::sleep
+//
+// retry! at the bottom is now sugar over RetryPolicy.
+//
+// Decorrelated jitter (from the AWS "Exponential Backoff and Jitter" article):
+//   delay = min(cap, random_between(base, previous_delay * 3))
+// Many clients failing at the same moment spread out instead of retrying in
+// lock-step and knocking the recovering service over again.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// --- Backoff strategies ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backoff {
+    Constant(Duration),
+    Exponential {
+        initial: Duration,
+        factor: f64,
+        max: Duration,
+    },
+    DecorrelatedJitter {
+        base: Duration,
+        max: Duration,
+    },
+}
+
+// xorshift64*: plenty for jitter, and seedable so tests are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniform in [lo, hi]
+    fn between(&mut self, lo: Duration, hi: Duration) -> Duration {
+        if hi <= lo {
+            return lo;
+        }
+        let span = (hi - lo).as_nanos() as u64;
+        lo + Duration::from_nanos(self.next_u64() % (span + 1))
+    }
+}
+
+impl Backoff {
+    // Delay before retry number `retry` (1-based), given the previous delay
+    fn delay(&self, retry: u32, previous: Duration, rng: &mut Rng) -> Duration {
+        match *self {
+            Backoff::Constant(d) => d,
+            Backoff::Exponential {
+                initial,
+                factor,
+                max,
+            } => {
+                let scaled = initial.as_secs_f64() * factor.powi(retry as i32 - 1);
+                // min() first: factor^n overflows Duration long before it overflows f64
+                Duration::from_secs_f64(scaled.min(max.as_secs_f64()))
+            }
+            Backoff::DecorrelatedJitter { base, max } => {
+                let upper = previous.max(base) * 3;
+                rng.between(base, upper).min(max)
+            }
+        }
+    }
+}
+
+// --- Errors ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GiveUpReason {
+    NotRetryable,
+    AttemptsExhausted,
+    ElapsedExceeded, // waiting again would cross max_elapsed
+}
+
+#[derive(Debug)]
+struct RetryError<E> {
+    last: E,
+    attempts: u32,
+    reason: GiveUpReason,
+}
+
+impl<E> RetryError<E> {
+    fn into_inner(self) -> E {
+        self.last
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let why = match self.reason {
+            GiveUpReason::NotRetryable => "not retryable",
+            GiveUpReason::AttemptsExhausted => "attempts exhausted",
+            GiveUpReason::ElapsedExceeded => "time budget exceeded",
+        };
+        write!(
+            f,
+            "failed after {} attempt(s) ({}): {}",
+            self.attempts, why, self.last
+        )
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RetryError<E> {}
+
+// --- The policy ---
+
+type Predicate<E> = Arc<dyn Fn(&E) -> bool + Send + Sync>;
+
+struct RetryPolicy<E> {
+    backoff: Backoff,
+    max_attempts: u32,
+    max_elapsed: Option<Duration>,
+    retryable: Predicate<E>,
+    seed: Option<u64>,
+}
+
+// Manual impl: a derive would demand E: Clone
+impl<E> Clone for RetryPolicy<E> {
+    fn clone(&self) -> Self {
+        RetryPolicy {
+            backoff: self.backoff,
+            max_attempts: self.max_attempts,
+            max_elapsed: self.max_elapsed,
+            retryable: Arc::clone(&self.retryable),
+            seed: self.seed,
+        }
+    }
+}
+
+impl<E> RetryPolicy<E> {
+    fn with_backoff(backoff: Backoff) -> Self {
+        RetryPolicy {
+            backoff,
+            max_attempts: 3,
+            max_elapsed: None,
+            retryable: Arc::new(|_| true),
+            seed: None,
+        }
+    }
+
+    fn constant(delay: Duration) -> Self {
+        Self::with_backoff(Backoff::Constant(delay))
+    }
+
+    // Doubles each time, capped at 30s (change with .factor() / .max_delay())
+    fn exponential(initial: Duration) -> Self {
+        Self::with_backoff(Backoff::Exponential {
+            initial,
+            factor: 2.0,
+            max: Duration::from_secs(30),
+        })
+    }
+
+    fn decorrelated_jitter(base: Duration, max: Duration) -> Self {
+        Self::with_backoff(Backoff::DecorrelatedJitter { base, max })
+    }
+
+    // Total attempts including the first one
+    fn max_attempts(mut self, n: u32) -> Self {
+        assert!(n >= 1, "max_attempts must be at least 1");
+        self.max_attempts = n;
+        self
+    }
+
+    fn max_elapsed(mut self, budget: Duration) -> Self {
+        self.max_elapsed = Some(budget);
+        self
+    }
+
+    fn factor(mut self, f: f64) -> Self {
+        if let Backoff::Exponential { ref mut factor, .. } = self.backoff {
+            *factor = f;
+        }
+        self
+    }
+
+    fn max_delay(mut self, cap: Duration) -> Self {
+        match self.backoff {
+            Backoff::Exponential { ref mut max, .. }
+            | Backoff::DecorrelatedJitter { ref mut max, .. } => *max = cap,
+            Backoff::Constant(_) => {}
+        }
+        self
+    }
+
+    fn retry_if(mut self, pred: impl Fn(&E) -> bool + Send + Sync + 'static) -> Self {
+        self.retryable = Arc::new(pred);
+        self
+    }
+
+    fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    fn schedule(&self) -> Schedule<'_, E> {
+        let seed = self.seed.unwrap_or_else(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64);
+            let addr = &nanos as *const u64 as u64;
+            nanos ^ addr.rotate_left(32)
+        });
+        Schedule {
+            policy: self,
+            attempt: 1,
+            previous: Duration::ZERO,
+            // Scramble so nearby seeds diverge; xorshift state must be non-zero
+            rng: Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1),
+        }
+    }
+
+    // The delays this policy would use if every attempt failed (ignores
+    // max_elapsed and the predicate) — handy for logging a config
+    fn delays(&self) -> Vec<Duration> {
+        let mut s = self.schedule();
+        (1..self.max_attempts)
+            .map(|retry| {
+                let d = self.backoff.delay(retry, s.previous, &mut s.rng);
+                s.previous = d;
+                d
+            })
+            .collect()
+    }
+
+    // Blocking flavour; `op` receives the attempt number (1-based)
+    fn retry<T>(&self, op: impl FnMut(u32) -> Result<T, E>) -> Result<T, RetryError<E>> {
+        self.retry_with(&mut SystemSleeper(Instant::now()), op)
+    }
+
+    fn retry_with<T>(
+        &self,
+        sleeper: &mut impl Sleeper,
+        mut op: impl FnMut(u32) -> Result<T, E>,
+    ) -> Result<T, RetryError<E>> {
+        let mut schedule = self.schedule();
+        loop {
+            let attempt = schedule.attempt;
+            match op(attempt) {
+                Ok(v) => return Ok(v),
+                Err(e) => match schedule.next_delay(&e, sleeper.elapsed()) {
+                    Ok(delay) => sleeper.sleep(delay),
+                    Err(reason) => {
+                        return Err(RetryError {
+                            last: e,
+                            attempts: attempt,
+                            reason,
+                        })
+                    }
+                },
+            }
+        }
+    }
+
+    // Async flavour: waits with tokio::time::sleep, so other tasks keep running
+    async fn retry_async<T, F, Fut>(&self, mut op: F) -> Result<T, RetryError<E>>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let start = tokio::time::Instant::now();
+        let mut schedule = self.schedule();
+        loop {
+            let attempt = schedule.attempt;
+            match op(attempt).await {
+                Ok(v) => return Ok(v),
+                Err(e) => match schedule.next_delay(&e, start.elapsed()) {
+                    Ok(delay) => tokio::time::sleep(delay).await,
+                    Err(reason) => {
+                        return Err(RetryError {
+                            last: e,
+                            attempts: attempt,
+                            reason,
+                        })
+                    }
+                },
+            }
+        }
+    }
+}
+
+// One run's state: shared by the sync and async loops
+struct Schedule<'p, E> {
+    policy: &'p RetryPolicy<E>,
+    attempt: u32,
+    previous: Duration,
+    rng: Rng,
+}
+
+impl<E> Schedule<'_, E> {
+    fn next_delay(&mut self, err: &E, elapsed: Duration) -> Result<Duration, GiveUpReason> {
+        let p = self.policy;
+        if !(p.retryable)(err) {
+            return Err(GiveUpReason::NotRetryable);
+        }
+        if self.attempt >= p.max_attempts {
+            return Err(GiveUpReason::AttemptsExhausted);
+        }
+        let delay = p.backoff.delay(self.attempt, self.previous, &mut self.rng);
+        if let Some(budget) = p.max_elapsed {
+            // Don't sleep if we'd wake up past the budget anyway
+            if elapsed + delay > budget {
+                return Err(GiveUpReason::ElapsedExceeded);
+            }
+        }
+        self.previous = delay;
+        self.attempt += 1;
+        Ok(delay)
+    }
+}
+
+// Where the blocking flavour gets time from; tests swap in a fake
+trait Sleeper {
+    fn elapsed(&self) -> Duration;
+    fn sleep(&mut self, d: Duration);
+}
+
+struct SystemSleeper(Instant);
+
+impl Sleeper for SystemSleeper {
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        thread::sleep(d);
+    }
+}
+
+// --- retry! as sugar ---
+//   retry!(policy, |attempt| expr)   — blocking, closure gets the attempt number
+//   retry!(policy, { block })        — blocking, block evaluates to a Result
+//   retry!(3, { block })             — old call shape: 3 attempts, no waiting
+//   retry!(async policy, { block })  — must be used inside async code; awaits
+macro_rules! retry {
+    (async $policy:expr, $body:block) => {
+        $policy.retry_async(|_| async $body).await
+    };
+    ($policy:expr, |$attempt:pat_param| $body:expr) => {
+        $policy.retry(|$attempt| $body)
+    };
+    ($times:literal, $body:block) => {
+        RetryPolicy::constant(Duration::ZERO)
+            .max_attempts($times)
+            .retry(|_| $body)
+    };
+    ($policy:expr, $body:block) => {
+        $policy.retry(|_| $body)
+    };
+}
+
+// --- Example: a flaky service ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum ServiceError {
+    Unavailable, // transient: worth retrying
+    NotFound,    // permanent: retrying won't help
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::Unavailable => write!(f, "503 service unavailable"),
+            ServiceError::NotFound => write!(f, "404 not found"),
+        }
+    }
+}
+
+fn is_transient(e: &ServiceError) -> bool {
+    *e == ServiceError::Unavailable
+}
+
+// Fails `failures` times, then succeeds
+struct Flaky {
+    failures: u32,
+    calls: u32,
+}
+
+impl Flaky {
+    fn call(&mut self) -> Result<&'static str, ServiceError> {
+        self.calls += 1;
+        if self.calls <= self.failures {
+            Err(ServiceError::Unavailable)
+        } else {
+            Ok("payload")
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let ms = Duration::from_millis;
+
+    // The waits each strategy would use
+    let constant = RetryPolicy::<ServiceError>::constant(ms(50)).max_attempts(4);
+    println!("{:?}", constant.delays()); // [50ms, 50ms, 50ms]
+    let expo = RetryPolicy::<ServiceError>::exponential(ms(10))
+        .max_delay(ms(60))
+        .max_attempts(6);
+    println!("{:?}", expo.delays()); // [10ms, 20ms, 40ms, 60ms, 60ms]
+    println!("{:?}", expo.clone().factor(3.0).delays()); // [10ms, 30ms, 60ms, 60ms, 60ms]
+    let jitter = RetryPolicy::<ServiceError>::decorrelated_jitter(ms(10), ms(1000))
+        .max_attempts(5)
+        .seed(7);
+    println!("{:?}", jitter.delays()); // four random delays, each in [10ms, 3 × previous]
+
+    // Blocking retry that eventually works
+    let policy = RetryPolicy::exponential(ms(5))
+        .max_attempts(5)
+        .retry_if(is_transient);
+    let mut service = Flaky {
+        failures: 2,
+        calls: 0,
+    };
+    let start = Instant::now();
+    let result = policy.retry(|attempt| {
+        println!("  attempt {}", attempt);
+        service.call()
+    });
+    println!("{:?} after ~{}ms", result, start.elapsed().as_millis()); // Ok("payload") after ~15ms
+
+    // A permanent error stops at once, and the error is kept
+    let err = policy
+        .retry(|_| Err::<(), _>(ServiceError::NotFound))
+        .unwrap_err();
+    println!("{}", err); // failed after 1 attempt(s) (not retryable): 404 not found
+
+    // Exhausted: the *last* error comes back
+    let err = RetryPolicy::constant(ms(1))
+        .max_attempts(3)
+        .retry(|_| Err::<(), _>(ServiceError::Unavailable))
+        .unwrap_err();
+    println!("{}", err); // failed after 3 attempt(s) (attempts exhausted): 503 service unavailable
+    println!("{:?}", err.into_inner()); // Unavailable
+
+    // Async flavour
+    let mut remote = Flaky {
+        failures: 1,
+        calls: 0,
+    };
+    let r = policy
+        .retry_async(|_| {
+            let outcome = remote.call();
+            async move { outcome }
+        })
+        .await;
+    println!("{:?}", r); // Ok("payload")
+
+    // Macro sugar
+    let mut tries = 0;
+    let r: Result<i32, RetryError<String>> = retry!(3, {
+        tries += 1;
+        if tries < 3 {
+            Err(format!("try {} failed", tries))
+        } else {
+            Ok(tries)
+        }
+    });
+    println!("{:?}", r.map_err(|e| e.to_string())); // Ok(3)
+
+    let budget = RetryPolicy::constant(ms(20))
+        .max_elapsed(ms(50))
+        .max_attempts(100);
+    let r = retry!(budget, |n| if n < 10 { Err(n) } else { Ok(n) });
+    println!("{}", r.unwrap_err()); // failed after 3 attempt(s) (time budget exceeded): 3
+
+    let async_policy = RetryPolicy::constant(ms(1)).max_attempts(2);
+    let r: Result<&str, RetryError<&str>> = retry!(async async_policy, { Err("still down") });
+    println!("{}", r.unwrap_err()); // failed after 2 attempt(s) (attempts exhausted): still down
+
+    println!("retry policy done"); // retry policy done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    // Records the waits instead of sleeping; time passes only when we "sleep"
+    #[derive(Default)]
+    struct FakeSleeper {
+        now: Duration,
+        slept: Vec<Duration>,
+    }
+
+    impl Sleeper for FakeSleeper {
+        fn elapsed(&self) -> Duration {
+            self.now
+        }
+        fn sleep(&mut self, d: Duration) {
+            self.now += d;
+            self.slept.push(d);
+        }
+    }
+
+    #[test]
+    fn constant_backoff_until_success() {
+        let mut clock = FakeSleeper::default();
+        let r = RetryPolicy::constant(ms(10))
+            .max_attempts(5)
+            .retry_with(&mut clock, |n| if n < 3 { Err("no") } else { Ok(n) });
+        assert_eq!(r.unwrap(), 3);
+        assert_eq!(clock.slept, vec![ms(10), ms(10)]);
+    }
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let p = RetryPolicy::<()>::exponential(ms(100))
+            .max_delay(ms(500))
+            .max_attempts(6);
+        assert_eq!(
+            p.delays(),
+            vec![ms(100), ms(200), ms(400), ms(500), ms(500)]
+        );
+        let tripled = RetryPolicy::<()>::exponential(ms(1))
+            .factor(3.0)
+            .max_attempts(4);
+        assert_eq!(tripled.delays(), vec![ms(1), ms(3), ms(9)]);
+        // Huge attempt counts must not overflow
+        let long = RetryPolicy::<()>::exponential(ms(1)).max_attempts(200);
+        assert_eq!(*long.delays().last().unwrap(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn decorrelated_jitter_stays_in_bounds_and_is_seeded() {
+        let p = RetryPolicy::<()>::decorrelated_jitter(ms(10), ms(300))
+            .max_attempts(50)
+            .seed(42);
+        let delays = p.delays();
+        let mut prev = ms(10);
+        for &d in &delays {
+            assert!(d >= ms(10) && d <= ms(300), "{:?}", d);
+            assert!(d <= prev * 3, "{:?} > 3 × {:?}", d, prev);
+            prev = d;
+        }
+        assert_eq!(delays, p.delays()); // same seed, same schedule
+        let other = p.clone().seed(43).delays();
+        assert_ne!(delays, other);
+    }
+
+    #[test]
+    fn non_retryable_error_stops_immediately() {
+        let mut clock = FakeSleeper::default();
+        let err = RetryPolicy::constant(ms(10))
+            .max_attempts(5)
+            .retry_if(is_transient)
+            .retry_with(&mut clock, |_| Err::<(), _>(ServiceError::NotFound))
+            .unwrap_err();
+        assert_eq!(err.attempts, 1);
+        assert_eq!(err.reason, GiveUpReason::NotRetryable);
+        assert_eq!(err.last, ServiceError::NotFound);
+        assert!(clock.slept.is_empty());
+    }
+
+    #[test]
+    fn exhaustion_returns_the_last_error() {
+        let mut clock = FakeSleeper::default();
+        let err = RetryPolicy::constant(ms(1))
+            .max_attempts(4)
+            .retry_with(&mut clock, |n| Err::<(), _>(format!("failure #{}", n)))
+            .unwrap_err();
+        assert_eq!(err.attempts, 4);
+        assert_eq!(err.reason, GiveUpReason::AttemptsExhausted);
+        assert_eq!(err.into_inner(), "failure #4");
+    }
+
+    #[test]
+    fn max_elapsed_is_never_overshot() {
+        let mut clock = FakeSleeper::default();
+        let err = RetryPolicy::exponential(ms(100))
+            .max_attempts(100)
+            .max_elapsed(ms(1000))
+            .retry_with(&mut clock, |_| Err::<(), _>("down"))
+            .unwrap_err();
+        // 100 + 200 + 400 = 700; another 800 would end at 1500
+        assert_eq!(clock.slept, vec![ms(100), ms(200), ms(400)]);
+        assert_eq!(err.attempts, 4);
+        assert_eq!(err.reason, GiveUpReason::ElapsedExceeded);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn async_retry_waits_on_the_tokio_clock() {
+        // Paused clock: tokio jumps ahead whenever every task is asleep
+        let start = tokio::time::Instant::now();
+        let mut service = Flaky {
+            failures: 3,
+            calls: 0,
+        };
+        let r = RetryPolicy::exponential(ms(100))
+            .max_attempts(5)
+            .retry_async(|_| {
+                let outcome = service.call();
+                async move { outcome }
+            })
+            .await;
+        assert_eq!(r.unwrap(), "payload");
+        assert_eq!(service.calls, 4);
+        assert_eq!(start.elapsed(), ms(100 + 200 + 400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn async_retry_respects_budget_and_predicate() {
+        let budget = RetryPolicy::constant(ms(300))
+            .max_attempts(10)
+            .max_elapsed(ms(1000));
+        let err = budget
+            .retry_async(|n| async move { Err::<(), _>(n) })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            (err.attempts, err.reason),
+            (4, GiveUpReason::ElapsedExceeded)
+        );
+
+        let picky = RetryPolicy::constant(ms(1))
+            .max_attempts(10)
+            .retry_if(is_transient);
+        let err = picky
+            .retry_async(|n| async move {
+                if n < 3 {
+                    Err::<(), _>(ServiceError::Unavailable)
+                } else {
+                    Err(ServiceError::NotFound)
+                }
+            })
+            .await
+            .unwrap_err();
+        assert_eq!((err.attempts, err.last), (3, ServiceError::NotFound));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn macro_forms_expand_to_the_policy() {
+        let mut calls = 0;
+        let r: Result<u32, RetryError<&str>> = retry!(2, {
+            calls += 1;
+            Err("nope")
+        });
+        assert_eq!(r.unwrap_err().attempts, 2);
+        assert_eq!(calls, 2);
+
+        let p = RetryPolicy::constant(ms(1)).max_attempts(5);
+        let r = retry!(p, |n| if n == 4 { Ok(n) } else { Err(n) });
+        assert_eq!(r.unwrap(), 4);
+
+        let r: Result<(), RetryError<u32>> = retry!(p, { Err(7) });
+        assert_eq!(r.unwrap_err().attempts, 5);
+
+        let start = tokio::time::Instant::now();
+        let r: Result<(), RetryError<u32>> = retry!(async p, { Err(9) });
+        assert_eq!(r.unwrap_err().attempts, 5);
+        assert_eq!(start.elapsed(), ms(4));
+    }
+}
@@ -0,0 +1,776 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full"] }
+//! tokio-stream = "0.1"
+//! futures = "0.3"
+//! ```
+
+// Time-aware stream operators
+//
+// async_streams.rs only uses map / filter / take. Event-processing code usually
+// also needs to control *when* items flow:
+//
+//   throttle(p)            — pass an item, then drop everything for p
+//   debounce(q)            — emit an item only once q passes with nothing newer
+//   sample(p)              — every p, emit the latest item seen in that window
+//   chunks_timeout(n, t)   — batch up to n items, or whatever arrived within t
+//                            of the batch's first item
+//   merge_sorted(streams)  — k-way merge of already-sorted streams
+//   dedup_by_key(f)        — drop consecutive items with the same key
+//
+// Each is a plain struct implementing Stream::poll_next, exposed through an
+// extension trait (same trick as StreamExt): `events.throttle(&clock, ms(100))`.
+//
+// Operators take a Clock (see test_clock.rs) instead of calling tokio::time
+// directly, so the tests below control time exactly.
+//
+// Input streams must be Unpin — wrap others with Box::pin(s) or pin!(s) first.
+
+use futures::stream::{Stream, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// --- Clock: the trait from test_clock.rs, minus the helpers not used here ---
+
+trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Duration;
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture;
+}
+
+type SharedClock = Arc<dyn Clock>;
+
+struct SystemClock {
+    start: tokio::time::Instant,
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture {
+        Box::pin(tokio::time::sleep_until(self.start + deadline))
+    }
+}
+
+// --- throttle: leading edge, drop the rest of the window ---
+
+struct Throttle<S> {
+    inner: S,
+    clock: SharedClock,
+    period: Duration,
+    next_allowed: Duration,
+}
+
+impl<S: Stream + Unpin> Stream for Throttle<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        loop {
+            match self.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => {
+                    let now = self.clock.now();
+                    if now >= self.next_allowed {
+                        self.next_allowed = now + self.period;
+                        return Poll::Ready(Some(item));
+                    }
+                    // inside the window: drop it and keep draining
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+// --- debounce: trailing edge, wait for a quiet period ---
+
+struct Debounce<S: Stream> {
+    inner: S,
+    clock: SharedClock,
+    quiet: Duration,
+    pending: Option<S::Item>,
+    timer: Option<BoxFuture>,
+    done: bool,
+}
+
+impl<S> Stream for Debounce<S>
+where
+    S: Stream + Unpin,
+    S::Item: Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = &mut *self;
+        while !this.done {
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => {
+                    // Every new item restarts the quiet period
+                    this.pending = Some(item);
+                    this.timer = Some(this.clock.sleep_until(this.clock.now() + this.quiet));
+                }
+                // Input ended: whatever is pending has nothing newer coming
+                Poll::Ready(None) => {
+                    this.done = true;
+                    this.timer = None;
+                    return Poll::Ready(this.pending.take());
+                }
+                Poll::Pending => break,
+            }
+        }
+        if this.done {
+            return Poll::Ready(None);
+        }
+        if let Some(timer) = this.timer.as_mut() {
+            if timer.as_mut().poll(cx).is_ready() {
+                this.timer = None;
+                return Poll::Ready(this.pending.take());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// --- sample: latest value per period ---
+// Windows are aligned to when the operator was created. Nothing is emitted for
+// an empty window, and a value still unsampled when the input ends is dropped
+// (as in Rx's sample).
+
+struct Sample<S: Stream> {
+    inner: S,
+    clock: SharedClock,
+    period: Duration,
+    next_tick: Duration,
+    timer: BoxFuture,
+    latest: Option<S::Item>,
+}
+
+impl<S> Stream for Sample<S>
+where
+    S: Stream + Unpin,
+    S::Item: Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = &mut *self;
+        loop {
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => this.latest = Some(item),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => break,
+            }
+        }
+        while this.timer.as_mut().poll(cx).is_ready() {
+            this.next_tick += this.period;
+            this.timer = this.clock.sleep_until(this.next_tick);
+            if let Some(item) = this.latest.take() {
+                return Poll::Ready(Some(item));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// --- chunks_timeout: batch by count or by time ---
+
+struct ChunksTimeout<S: Stream> {
+    inner: S,
+    clock: SharedClock,
+    max: usize,
+    timeout: Duration,
+    batch: Vec<S::Item>,
+    timer: Option<BoxFuture>, // armed by the first item of a batch
+    done: bool,
+}
+
+impl<S> Stream for ChunksTimeout<S>
+where
+    S: Stream + Unpin,
+    S::Item: Unpin,
+{
+    type Item = Vec<S::Item>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Vec<S::Item>>> {
+        let this = &mut *self;
+        while !this.done {
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => {
+                    if this.batch.is_empty() {
+                        this.timer = Some(this.clock.sleep_until(this.clock.now() + this.timeout));
+                    }
+                    this.batch.push(item);
+                    if this.batch.len() >= this.max {
+                        this.timer = None;
+                        return Poll::Ready(Some(std::mem::take(&mut this.batch)));
+                    }
+                }
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        if this.done {
+            this.timer = None;
+            if this.batch.is_empty() {
+                return Poll::Ready(None);
+            }
+            return Poll::Ready(Some(std::mem::take(&mut this.batch)));
+        }
+        if let Some(timer) = this.timer.as_mut() {
+            if timer.as_mut().poll(cx).is_ready() {
+                this.timer = None;
+                return Poll::Ready(Some(std::mem::take(&mut this.batch)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// --- merge_sorted: k-way merge ---
+// Can only emit once every live input has offered its next item — a slow input
+// holds the whole merge back, which is the price of correct ordering.
+
+struct MergeSorted<S: Stream> {
+    inputs: Vec<S>,
+    heads: Vec<Option<S::Item>>,
+    finished: Vec<bool>,
+}
+
+fn merge_sorted<S>(inputs: Vec<S>) -> MergeSorted<S>
+where
+    S: Stream + Unpin,
+    S::Item: Ord + Unpin,
+{
+    let n = inputs.len();
+    MergeSorted {
+        inputs,
+        heads: (0..n).map(|_| None).collect(),
+        finished: vec![false; n],
+    }
+}
+
+impl<S> Stream for MergeSorted<S>
+where
+    S: Stream + Unpin,
+    S::Item: Ord + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = &mut *self;
+        let mut waiting = false;
+        for i in 0..this.inputs.len() {
+            if this.heads[i].is_some() || this.finished[i] {
+                continue;
+            }
+            match this.inputs[i].poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => this.heads[i] = Some(item),
+                Poll::Ready(None) => this.finished[i] = true,
+                Poll::Pending => waiting = true,
+            }
+        }
+        if waiting {
+            return Poll::Pending;
+        }
+        // Smallest head wins; ties go to the earlier input (stable)
+        let best = this
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| h.as_ref().map(|v| (v, i)))
+            .min()
+            .map(|(_, i)| i);
+        Poll::Ready(best.and_then(|i| this.heads[i].take()))
+    }
+}
+
+// --- dedup_by_key: drop consecutive repeats ---
+
+struct DedupByKey<S, F, K> {
+    inner: S,
+    key_fn: F,
+    last: Option<K>,
+}
+
+impl<S, F, K> Stream for DedupByKey<S, F, K>
+where
+    S: Stream + Unpin,
+    F: FnMut(&S::Item) -> K + Unpin,
+    K: PartialEq + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = &mut *self;
+        loop {
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => {
+                    let key = (this.key_fn)(&item);
+                    if this.last.as_ref() != Some(&key) {
+                        this.last = Some(key);
+                        return Poll::Ready(Some(item));
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+// --- The extension trait ---
+
+trait TimedStreamExt: Stream + Unpin + Sized {
+    fn throttle(self, clock: &SharedClock, period: Duration) -> Throttle<Self> {
+        Throttle {
+            inner: self,
+            clock: Arc::clone(clock),
+            period,
+            next_allowed: Duration::ZERO,
+        }
+    }
+
+    fn debounce(self, clock: &SharedClock, quiet: Duration) -> Debounce<Self> {
+        Debounce {
+            inner: self,
+            clock: Arc::clone(clock),
+            quiet,
+            pending: None,
+            timer: None,
+            done: false,
+        }
+    }
+
+    fn sample(self, clock: &SharedClock, period: Duration) -> Sample<Self> {
+        assert!(!period.is_zero(), "sample period must be non-zero");
+        let next_tick = clock.now() + period;
+        Sample {
+            inner: self,
+            clock: Arc::clone(clock),
+            period,
+            next_tick,
+            timer: clock.sleep_until(next_tick),
+            latest: None,
+        }
+    }
+
+    fn chunks_timeout(
+        self,
+        clock: &SharedClock,
+        max: usize,
+        timeout: Duration,
+    ) -> ChunksTimeout<Self> {
+        assert!(max > 0, "chunk size must be non-zero");
+        ChunksTimeout {
+            inner: self,
+            clock: Arc::clone(clock),
+            max,
+            timeout,
+            batch: Vec::with_capacity(max),
+            timer: None,
+            done: false,
+        }
+    }
+
+    fn merge_sorted_with(self, other: Self) -> MergeSorted<Self>
+    where
+        Self::Item: Ord + Unpin,
+    {
+        merge_sorted(vec![self, other])
+    }
+
+    fn dedup_by_key<K, F>(self, key_fn: F) -> DedupByKey<Self, F, K>
+    where
+        F: FnMut(&Self::Item) -> K,
+        K: PartialEq,
+    {
+        DedupByKey {
+            inner: self,
+            key_fn,
+            last: None,
+        }
+    }
+}
+
+impl<S: Stream + Unpin> TimedStreamExt for S {}
+
+#[tokio::main]
+async fn main() {
+    use futures::stream;
+    use tokio_stream::wrappers::IntervalStream;
+
+    let clock: SharedClock = Arc::new(SystemClock {
+        start: tokio::time::Instant::now(),
+    });
+    let ms = Duration::from_millis;
+
+    // A sensor ticking every 5ms
+    let sensor = || {
+        IntervalStream::new(tokio::time::interval(ms(5)))
+            .enumerate()
+            .map(|(i, _)| i)
+    };
+
+    // throttle: at most one reading per 20ms
+    let throttled: Vec<usize> = sensor().throttle(&clock, ms(20)).take(3).collect().await;
+    println!("throttled: {:?}", throttled); // throttled: [0, 4, 8] (roughly)
+
+    // chunks_timeout: batches of 4
+    let batches: Vec<Vec<usize>> = sensor()
+        .take(10)
+        .chunks_timeout(&clock, 4, ms(100))
+        .collect()
+        .await;
+    println!("batches: {:?}", batches); // batches: [[0, 1, 2, 3], [4, 5, 6, 7], [8, 9]]
+
+    // debounce: a burst collapses to its last item
+    let burst = stream::iter(vec!["h", "he", "hel", "hell", "hello"]);
+    let typed: Vec<&str> = burst.debounce(&clock, ms(10)).collect().await;
+    println!("debounced: {:?}", typed); // debounced: ["hello"]
+
+    // merge_sorted: three sorted logs into one timeline
+    let logs = vec![
+        stream::iter(vec![1, 4, 9]),
+        stream::iter(vec![2, 3, 10]),
+        stream::iter(vec![5, 6, 7, 8]),
+    ];
+    let merged: Vec<i32> = merge_sorted(logs).collect().await;
+    println!("merged: {:?}", merged); // merged: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+    let pair: Vec<i32> = stream::iter(vec![1, 3])
+        .merge_sorted_with(stream::iter(vec![2]))
+        .collect()
+        .await;
+    println!("pair: {:?}", pair); // pair: [1, 2, 3]
+
+    // dedup_by_key: only report state changes
+    let states = stream::iter(vec![
+        ("door", 0),
+        ("door", 0),
+        ("door", 1),
+        ("door", 1),
+        ("door", 0),
+    ]);
+    let changes: Vec<_> = states.dedup_by_key(|&(_, s)| s).collect().await;
+    println!("changes: {:?}", changes); // changes: [("door", 0), ("door", 1), ("door", 0)]
+
+    // sample: latest sensor value every 12ms
+    let sampled: Vec<usize> = sensor().sample(&clock, ms(12)).take(3).collect().await;
+    println!("sampled {} values", sampled.len()); // sampled 3 values
+
+    println!("stream operators done"); // stream operators done
+}
+
+// ============================================================
+// TESTS — items are fed by hand and time is moved by hand
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use std::sync::Mutex;
+    use std::task::Waker;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    // --- Manual clock: a trimmed copy of ManualClock from test_clock.rs ---
+    // No sleep ids or cancellation here: a dropped sleep just leaves a stale
+    // waker behind, and waking a task for nothing is harmless.
+
+    #[derive(Default)]
+    struct ManualState {
+        now: Duration,
+        sleepers: Vec<(Duration, Waker)>,
+        polls: u64, // settle() waits until this stops changing
+    }
+
+    #[derive(Clone, Default)]
+    struct ManualClock {
+        state: Arc<Mutex<ManualState>>,
+    }
+
+    struct ManualSleep {
+        state: Arc<Mutex<ManualState>>,
+        deadline: Duration,
+    }
+
+    impl Future for ManualSleep {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut s = self.state.lock().unwrap();
+            s.polls += 1;
+            if s.now >= self.deadline {
+                return Poll::Ready(());
+            }
+            s.sleepers.push((self.deadline, cx.waker().clone()));
+            Poll::Pending
+        }
+    }
+
+    impl ManualClock {
+        fn shared(&self) -> SharedClock {
+            Arc::new(self.clone())
+        }
+
+        // Step to each due deadline in order, letting woken tasks run each time
+        async fn advance(&self, d: Duration) {
+            let target = self.now() + d;
+            loop {
+                self.settle().await;
+                let due: Vec<(Duration, Waker)> = {
+                    let mut s = self.state.lock().unwrap();
+                    let next = s.sleepers.iter().map(|&(t, _)| t).min();
+                    let Some(next) = next.filter(|&t| t <= target) else {
+                        break;
+                    };
+                    s.now = s.now.max(next);
+                    let (due, later) = std::mem::take(&mut s.sleepers)
+                        .into_iter()
+                        .partition(|&(t, _)| t <= next);
+                    s.sleepers = later;
+                    due
+                };
+                for (_, w) in due {
+                    w.wake();
+                }
+            }
+            {
+                let mut s = self.state.lock().unwrap();
+                s.now = s.now.max(target);
+            }
+            self.settle().await;
+        }
+
+        // Current-thread runtime: yield until two rounds pass with no clock activity
+        async fn settle(&self) {
+            let mut quiet = 0;
+            while quiet < 2 {
+                let before = self.state.lock().unwrap().polls;
+                tokio::task::yield_now().await;
+                if self.state.lock().unwrap().polls == before {
+                    quiet += 1;
+                } else {
+                    quiet = 0;
+                }
+            }
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Duration {
+            self.state.lock().unwrap().now
+        }
+
+        fn sleep_until(&self, deadline: Duration) -> BoxFuture {
+            Box::pin(ManualSleep {
+                state: Arc::clone(&self.state),
+                deadline,
+            })
+        }
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    // Source we push into + sink recording (item, time emitted)
+    struct Harness<T> {
+        clock: ManualClock,
+        input: Option<mpsc::UnboundedSender<T>>,
+        output: mpsc::UnboundedReceiver<(T, Duration)>,
+    }
+
+    impl<T: Send + 'static> Harness<T> {
+        fn new<O, S>(op: O) -> Self
+        where
+            O: FnOnce(UnboundedReceiverStream<T>, SharedClock) -> S,
+            S: Stream<Item = T> + Unpin + Send + 'static,
+        {
+            let clock = ManualClock::default();
+            let (in_tx, in_rx) = mpsc::unbounded_channel();
+            let (out_tx, out_rx) = mpsc::unbounded_channel();
+            let mut s = op(UnboundedReceiverStream::new(in_rx), clock.shared());
+            let c = clock.shared();
+            tokio::spawn(async move {
+                while let Some(item) = s.next().await {
+                    out_tx.send((item, c.now())).unwrap();
+                }
+            });
+            Harness {
+                clock,
+                input: Some(in_tx),
+                output: out_rx,
+            }
+        }
+
+        async fn send(&self, item: T) {
+            self.input.as_ref().unwrap().send(item).unwrap();
+            self.clock.settle().await;
+        }
+
+        async fn advance(&self, d: Duration) {
+            self.clock.advance(d).await;
+        }
+
+        async fn close(&mut self) {
+            self.input = None;
+            self.clock.settle().await;
+        }
+
+        fn drain(&mut self) -> Vec<(T, Duration)> {
+            let mut out = Vec::new();
+            while let Ok(v) = self.output.try_recv() {
+                out.push(v);
+            }
+            out
+        }
+    }
+
+    #[tokio::test]
+    async fn throttle_keeps_first_of_each_window() {
+        let mut h = Harness::new(|s, c| s.throttle(&c, ms(100)));
+        for (t, item) in [(0, 1), (10, 2), (20, 3), (120, 4), (130, 5), (220, 6)] {
+            let now = h.clock.now();
+            h.advance(ms(t) - now).await;
+            h.send(item).await;
+        }
+        assert_eq!(h.drain(), vec![(1, ms(0)), (4, ms(120)), (6, ms(220))]);
+    }
+
+    #[tokio::test]
+    async fn debounce_waits_for_quiet() {
+        let mut h = Harness::new(|s, c| s.debounce(&c, ms(50)));
+        h.send("a").await;
+        h.advance(ms(10)).await;
+        h.send("b").await;
+        h.advance(ms(49)).await;
+        assert!(h.drain().is_empty()); // t=59: b's quiet period ends at 60
+        h.advance(ms(1)).await;
+        assert_eq!(h.drain(), vec![("b", ms(60))]);
+
+        h.advance(ms(100)).await;
+        h.send("c").await;
+        h.close().await; // input ended: c flushes right away
+        assert_eq!(h.drain(), vec![("c", ms(160))]);
+    }
+
+    #[tokio::test]
+    async fn sample_emits_latest_per_window() {
+        let mut h = Harness::new(|s, c| s.sample(&c, ms(50)));
+        h.send(1).await;
+        h.advance(ms(10)).await;
+        h.send(2).await;
+        h.advance(ms(20)).await;
+        h.send(3).await;
+        h.advance(ms(20)).await; // t=50
+        assert_eq!(h.drain(), vec![(3, ms(50))]);
+        h.advance(ms(50)).await; // empty window: nothing
+        assert!(h.drain().is_empty());
+        h.advance(ms(20)).await;
+        h.send(4).await;
+        h.advance(ms(30)).await; // t=150
+        assert_eq!(h.drain(), vec![(4, ms(150))]);
+    }
+
+    #[tokio::test]
+    async fn chunks_by_count_or_time() {
+        let clock = ManualClock::default();
+        let (in_tx, in_rx) = mpsc::unbounded_channel();
+        let (out_tx, mut out_rx) = mpsc::unbounded_channel();
+        let shared = clock.shared();
+        let mut s = UnboundedReceiverStream::new(in_rx).chunks_timeout(&shared, 3, ms(100));
+        tokio::spawn(async move {
+            while let Some(batch) = s.next().await {
+                out_tx.send((batch, shared.now())).unwrap();
+            }
+        });
+
+        for i in 1..=4 {
+            in_tx.send(i).unwrap();
+        }
+        clock.settle().await;
+        assert_eq!(out_rx.try_recv().unwrap(), (vec![1, 2, 3], ms(0))); // full
+        assert!(out_rx.try_recv().is_err());
+
+        clock.advance(ms(40)).await;
+        in_tx.send(5).unwrap();
+        clock.advance(ms(60)).await; // 100ms after 4 arrived
+        assert_eq!(out_rx.try_recv().unwrap(), (vec![4, 5], ms(100)));
+
+        in_tx.send(6).unwrap();
+        drop(in_tx);
+        clock.settle().await;
+        assert_eq!(out_rx.try_recv().unwrap(), (vec![6], ms(100))); // flushed at end
+    }
+
+    #[tokio::test]
+    async fn merge_sorted_is_ordered_and_stable() {
+        let merged: Vec<(i32, char)> = merge_sorted(vec![
+            stream::iter(vec![(1, 'a'), (3, 'a'), (3, 'a')]),
+            stream::iter(vec![]),
+            stream::iter(vec![(0, 'c'), (3, 'c'), (7, 'c')]),
+        ])
+        .collect()
+        .await;
+        assert_eq!(
+            merged,
+            vec![(0, 'c'), (1, 'a'), (3, 'a'), (3, 'a'), (3, 'c'), (7, 'c')]
+        );
+        let two: Vec<i32> = stream::iter(vec![1, 5])
+            .merge_sorted_with(stream::iter(vec![2, 3, 6]))
+            .collect()
+            .await;
+        assert_eq!(two, vec![1, 2, 3, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn merge_sorted_waits_for_slow_inputs() {
+        let (fast_tx, fast_rx) = mpsc::unbounded_channel();
+        let (slow_tx, slow_rx) = mpsc::unbounded_channel();
+        let (out_tx, mut out_rx) = mpsc::unbounded_channel();
+        let mut m = merge_sorted(vec![
+            UnboundedReceiverStream::new(fast_rx),
+            UnboundedReceiverStream::new(slow_rx),
+        ]);
+        tokio::spawn(async move {
+            while let Some(v) = m.next().await {
+                out_tx.send(v).unwrap();
+            }
+        });
+        fast_tx.send(5).unwrap();
+        fast_tx.send(6).unwrap();
+        tokio::task::yield_now().await;
+        assert!(out_rx.try_recv().is_err()); // slow might still send a 1
+        slow_tx.send(1).unwrap();
+        drop(slow_tx);
+        drop(fast_tx);
+        let mut got = Vec::new();
+        while let Some(v) = out_rx.recv().await {
+            got.push(v);
+        }
+        assert_eq!(got, vec![1, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn dedup_drops_only_consecutive_repeats() {
+        let out: Vec<i32> = stream::iter(vec![1, 1, 2, 2, 2, 1, 3, 3])
+            .dedup_by_key(|&x| x)
+            .collect()
+            .await;
+        assert_eq!(out, vec![1, 2, 1, 3]);
+        let words: Vec<&str> =
+            stream::iter(vec!["apple", "avocado", "banana", "blueberry", "apricot"])
+                .dedup_by_key(|w| w.chars().next())
+                .collect()
+                .await;
+        assert_eq!(words, vec!["apple", "banana", "apricot"]);
+    }
+}
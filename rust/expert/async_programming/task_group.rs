@@ -0,0 +1,395 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full", "test-util"] }
+//! ```
+
+// Structured concurrency: task groups
+//
+// The Vec<JoinHandle> + join_all pattern (tokio_basics.rs) has three holes:
+//   - one task fails, the others keep running (and keep using resources)
+//   - a panic arrives as JoinError, and unwrap() turns it into *our* panic
+//   - nothing limits how many tasks run at once
+//
+// TaskGroup closes them. Tasks live no longer than the group:
+//
+//   let mut group = TaskGroup::new().max_concurrency(4);   // FailFast by default
+//   for url in urls { group.spawn(fetch(url)); }
+//   let pages: Vec<Page> = group.join().await?;            // spawn order
+//
+//   ErrorPolicy::FailFast    — first error/panic aborts every sibling; join returns it
+//   ErrorPolicy::CollectAll  — every task runs to the end; join returns all errors
+//
+// Dropping a group without joining aborts whatever is still running.
+// Panics come back as TaskError::Panicked(message), never as a re-panic.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::{Id, JoinError, JoinSet};
+use tokio::time::sleep;
+
+// --- Errors ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorPolicy {
+    FailFast,
+    CollectAll,
+}
+
+#[derive(Debug, PartialEq)]
+enum TaskError<E> {
+    Failed(E),
+    Panicked(String),
+}
+
+impl<E: fmt::Display> fmt::Display for TaskError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(e) => write!(f, "failed: {}", e),
+            TaskError::Panicked(msg) => write!(f, "panicked: {}", msg),
+        }
+    }
+}
+
+// `errors` holds (task index, error) in the order they happened
+#[derive(Debug)]
+struct GroupError<E> {
+    errors: Vec<(usize, TaskError<E>)>,
+    cancelled: usize, // siblings aborted by FailFast
+}
+
+impl<E> GroupError<E> {
+    fn first(&self) -> &TaskError<E> {
+        &self.errors[0].1
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for GroupError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (task, err) = &self.errors[0];
+        write!(f, "task {} {}", task, err)?;
+        if self.errors.len() > 1 {
+            write!(f, " (+{} more errors)", self.errors.len() - 1)?;
+        }
+        if self.cancelled > 0 {
+            write!(f, ", {} sibling(s) cancelled", self.cancelled)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for GroupError<E> {}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic>".to_string()
+    }
+}
+
+// --- TaskGroup ---
+
+struct TaskGroup<T, E> {
+    set: JoinSet<(usize, Result<T, E>)>,
+    ids: HashMap<Id, usize>, // to tell which task a JoinError belongs to
+    spawned: usize,
+    policy: ErrorPolicy,
+    limit: Option<Arc<Semaphore>>,
+}
+
+impl<T: Send + 'static, E: Send + 'static> TaskGroup<T, E> {
+    fn new() -> Self {
+        TaskGroup {
+            set: JoinSet::new(),
+            ids: HashMap::new(),
+            spawned: 0,
+            policy: ErrorPolicy::FailFast,
+            limit: None,
+        }
+    }
+
+    fn policy(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn max_concurrency(mut self, n: usize) -> Self {
+        assert!(n > 0, "max_concurrency must be at least 1");
+        self.limit = Some(Arc::new(Semaphore::new(n)));
+        self
+    }
+
+    // Starts right away; with a cap, the task queues for a permit first
+    fn spawn<F>(&mut self, fut: F)
+    where
+        F: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        let index = self.spawned;
+        self.spawned += 1;
+        let limit = self.limit.clone();
+        let handle = self.set.spawn(async move {
+            // Held until the task finishes; an aborted task releases it too
+            let _permit = match &limit {
+                Some(sem) => Some(sem.acquire().await.expect("semaphore closed")),
+                None => None,
+            };
+            (index, fut.await)
+        });
+        self.ids.insert(handle.id(), index);
+    }
+
+    fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    // Wait for every task; results come back in spawn order
+    async fn join(mut self) -> Result<Vec<T>, GroupError<E>> {
+        let mut results: Vec<Option<T>> = (0..self.spawned).map(|_| None).collect();
+        let mut errors = Vec::new();
+        let mut cancelled = 0;
+
+        while let Some(joined) = self.set.join_next_with_id().await {
+            let failure = match joined {
+                Ok((_, (index, Ok(value)))) => {
+                    results[index] = Some(value);
+                    None
+                }
+                Ok((_, (index, Err(e)))) => Some((index, TaskError::Failed(e))),
+                Err(err) => self.join_error(err, &mut cancelled),
+            };
+            if let Some(failure) = failure {
+                errors.push(failure);
+                if self.policy == ErrorPolicy::FailFast {
+                    // Aborted tasks still come out of join_next as cancelled
+                    self.set.abort_all();
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(results.into_iter().map(|r| r.unwrap()).collect())
+        } else {
+            Err(GroupError { errors, cancelled })
+        }
+    }
+
+    fn join_error(&self, err: JoinError, cancelled: &mut usize) -> Option<(usize, TaskError<E>)> {
+        let index = self.ids[&err.id()];
+        if err.is_cancelled() {
+            *cancelled += 1;
+            None
+        } else {
+            let msg = panic_message(err.into_panic());
+            Some((index, TaskError::Panicked(msg)))
+        }
+    }
+}
+
+// --- Example ---
+
+#[derive(Debug, PartialEq)]
+struct FetchError(String);
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Pretends to fetch a page; "bad" hosts fail, "crash" panics
+async fn fetch(host: &'static str, delay_ms: u64) -> Result<usize, FetchError> {
+    sleep(Duration::from_millis(delay_ms)).await;
+    match host {
+        "bad.example" => Err(FetchError(format!("{}: connection refused", host))),
+        "crash.example" => panic!("parser bug on {}", host),
+        _ => Ok(host.len()),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // --- All succeed: results in spawn order, not completion order ---
+    let mut group = TaskGroup::new();
+    group.spawn(fetch("slow.example", 30));
+    group.spawn(fetch("a.io", 5));
+    group.spawn(fetch("medium.example", 15));
+    println!("{:?}", group.join().await); // Ok([12, 4, 14])
+
+    // --- FailFast: the first error aborts the rest ---
+    let mut group = TaskGroup::new();
+    group.spawn(fetch("bad.example", 5));
+    group.spawn(fetch("slow.example", 1000)); // would take a second
+    group.spawn(fetch("slower.example", 2000));
+    let start = tokio::time::Instant::now();
+    let err = group.join().await.unwrap_err();
+    println!("{}", err); // task 0 failed: bad.example: connection refused, 2 sibling(s) cancelled
+    println!("{}", err.first()); // failed: bad.example: connection refused
+    println!("{}", start.elapsed() < Duration::from_millis(500)); // true
+
+    // --- CollectAll: let everything finish, report every failure ---
+    let mut group = TaskGroup::new().policy(ErrorPolicy::CollectAll);
+    group.spawn(fetch("ok.example", 5));
+    group.spawn(fetch("bad.example", 10));
+    group.spawn(fetch("crash.example", 15)); // panic message still prints on stderr
+    let err = group.join().await.unwrap_err();
+    for (task, e) in &err.errors {
+        println!("task {}: {}", task, e);
+    }
+    // task 1: failed: bad.example: connection refused
+    // task 2: panicked: parser bug on crash.example
+
+    // --- Concurrency cap: 10 tasks of 10ms, 2 at a time ≈ 50ms ---
+    let mut group: TaskGroup<usize, FetchError> = TaskGroup::new().max_concurrency(2);
+    for _ in 0..10 {
+        group.spawn(fetch("x.io", 10));
+    }
+    println!("{} tasks queued", group.len()); // 10 tasks queued
+    let start = tokio::time::Instant::now();
+    let total: usize = group.join().await.unwrap().iter().sum();
+    println!("{} {}", total, start.elapsed() >= Duration::from_millis(50)); // 40 true
+
+    println!("task group done"); // task group done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    async fn after<T>(delay: u64, value: T) -> T {
+        sleep(ms(delay)).await;
+        value
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn results_keep_spawn_order() {
+        let mut group: TaskGroup<u64, ()> = TaskGroup::new();
+        for d in [30, 10, 20, 0] {
+            group.spawn(after(d, Ok(d)));
+        }
+        assert_eq!(group.join().await.unwrap(), vec![30, 10, 20, 0]);
+        let empty: TaskGroup<(), ()> = TaskGroup::new();
+        assert_eq!(empty.join().await.unwrap(), vec![]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fail_fast_cancels_siblings() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let mut group = TaskGroup::new();
+        for i in 0..4u64 {
+            let finished = Arc::clone(&finished);
+            group.spawn(async move {
+                if i == 2 {
+                    return after(10, Err(format!("task {} broke", i))).await;
+                }
+                sleep(ms(100)).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+                Ok(i)
+            });
+        }
+        let start = tokio::time::Instant::now();
+        let err = group.join().await.unwrap_err();
+        assert_eq!(
+            err.errors,
+            vec![(2, TaskError::Failed("task 2 broke".to_string()))]
+        );
+        assert_eq!(err.cancelled, 3);
+        assert_eq!(start.elapsed(), ms(10));
+        sleep(ms(500)).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 0); // really stopped
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn collect_all_reports_every_error_and_panic() {
+        let mut group = TaskGroup::new().policy(ErrorPolicy::CollectAll);
+        group.spawn(after(30, Err("late")));
+        group.spawn(after(20, Ok(1)));
+        group.spawn(async {
+            sleep(ms(10)).await;
+            panic!("boom {}", 7);
+        });
+        group.spawn(after(5, Err("early")));
+        let err = group.join().await.unwrap_err();
+        assert_eq!(
+            err.errors,
+            vec![
+                (3, TaskError::Failed("early")),
+                (2, TaskError::Panicked("boom 7".to_string())),
+                (0, TaskError::Failed("late")),
+            ]
+        );
+        assert_eq!(err.cancelled, 0);
+        assert_eq!(*err.first(), TaskError::Failed("early"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panic_is_a_typed_error_under_fail_fast() {
+        let mut group: TaskGroup<(), String> = TaskGroup::new();
+        group.spawn(async { panic!("static message") });
+        group.spawn(after(50, Ok(())));
+        let err = group.join().await.unwrap_err();
+        assert_eq!(*err.first(), TaskError::Panicked("static message".into()));
+        assert_eq!(err.cancelled, 1);
+        assert_eq!(
+            err.to_string(),
+            "task 0 panicked: static message, 1 sibling(s) cancelled"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency_is_capped() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut group: TaskGroup<(), ()> = TaskGroup::new().max_concurrency(3);
+        for _ in 0..12 {
+            let (in_flight, peak) = (Arc::clone(&in_flight), Arc::clone(&peak));
+            group.spawn(async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                sleep(ms(10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            });
+        }
+        let start = tokio::time::Instant::now();
+        group.join().await.unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(start.elapsed(), ms(40)); // 12 tasks / 3 slots × 10ms
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropping_the_group_aborts_its_tasks() {
+        let ran = Arc::new(AtomicUsize::new(0));
+        {
+            let mut group: TaskGroup<(), ()> = TaskGroup::new();
+            for _ in 0..3 {
+                let ran = Arc::clone(&ran);
+                group.spawn(async move {
+                    sleep(ms(10)).await;
+                    ran.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                });
+            }
+        }
+        sleep(ms(100)).await;
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+}
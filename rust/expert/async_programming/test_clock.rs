@@ -0,0 +1,495 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [dependencies]
+//! tokio = { version = "1", features = ["full"] }
+//! tokio-stream = "0.1"
+//! futures = "0.3"
+//! async-stream = "0.3"
+//! ```
+
+// Injectable clocks: testing timing code without waiting
+//
+// tokio_basics::timer_demo and async_streams::countdown / event_stream call
+// tokio::time::sleep directly. A test of "5 events, 5ms apart" then really takes
+// 25ms, and on a loaded CI machine the "exactly 2 events after 12ms" assertion
+// sometimes sees 1 or 3.
+//
+// Fix: production code never touches the global timer. It asks a Clock:
+//
+//   trait Clock { now(), sleep_until(deadline), sleep(d) }   + interval(), timeout()
+//
+//   SystemClock — real time, forwards to tokio::time
+//   ManualClock — time stands still until a test calls `clock.advance(d).await`,
+//                 which fires every sleep due in that window, in deadline order,
+//                 and lets the woken tasks run before returning
+//
+// So a test can say "after advancing 250ms, exactly 3 events fired" and it runs
+// in microseconds, identically every time.
+//
+// ManualClock::advance lets woken tasks run by yielding to the scheduler, so
+// tests should use the (default) current-thread runtime: #[tokio::test].
+//
+// tokio has a built-in variant of this (tokio::time::pause + advance behind the
+// "test-util" feature), but only for tokio's own timers and only globally; an
+// explicit Clock also works for code that reads "now" for timestamps or TTLs.
+
+use async_stream::stream;
+use futures::stream::Stream;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// --- The abstraction ---
+// Times are Durations since the clock was created: monotonic, easy to assert on
+
+trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Duration;
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture;
+
+    fn sleep(&self, d: Duration) -> BoxFuture {
+        self.sleep_until(self.now() + d)
+    }
+}
+
+type SharedClock = Arc<dyn Clock>;
+
+// Like tokio::time::Interval: first tick is immediate, later ticks every
+// `period` from the start. A consumer that falls behind gets the missed ticks
+// back-to-back (tokio's default "Burst" behaviour).
+struct Interval {
+    clock: SharedClock,
+    next: Duration,
+    period: Duration,
+}
+
+impl Interval {
+    // Resolves with the scheduled time of the tick
+    async fn tick(&mut self) -> Duration {
+        let at = self.next;
+        self.clock.sleep_until(at).await;
+        self.next += self.period;
+        at
+    }
+}
+
+fn interval(clock: &SharedClock, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "interval period must be non-zero");
+    Interval {
+        clock: Arc::clone(clock),
+        next: clock.now(),
+        period,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Elapsed;
+
+async fn timeout<F: Future>(
+    clock: &SharedClock,
+    d: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    tokio::select! {
+        biased; // finishing exactly at the deadline counts as success
+        v = future => Ok(v),
+        _ = clock.sleep(d) => Err(Elapsed),
+    }
+}
+
+// --- Real time ---
+
+struct SystemClock {
+    start: tokio::time::Instant,
+}
+
+impl SystemClock {
+    fn shared() -> SharedClock {
+        Arc::new(SystemClock {
+            start: tokio::time::Instant::now(),
+        })
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture {
+        Box::pin(tokio::time::sleep_until(self.start + deadline))
+    }
+}
+
+// --- Manual time ---
+
+struct ManualState {
+    now: Duration,
+    sleepers: BTreeMap<(Duration, u64), Waker>, // ordered by deadline, then creation
+    next_id: u64,
+    polls: u64, // activity counter used by settle()
+}
+
+#[derive(Clone)]
+struct ManualClock {
+    state: Arc<Mutex<ManualState>>,
+}
+
+struct ManualSleep {
+    state: Arc<Mutex<ManualState>>,
+    key: (Duration, u64),
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut s = self.state.lock().unwrap();
+        s.polls += 1;
+        if s.now >= self.key.0 {
+            s.sleepers.remove(&self.key);
+            Poll::Ready(())
+        } else {
+            s.sleepers.insert(self.key, cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        // A cancelled sleep (losing side of a select!) must not linger
+        self.state.lock().unwrap().sleepers.remove(&self.key);
+    }
+}
+
+impl ManualClock {
+    fn new() -> Self {
+        ManualClock {
+            state: Arc::new(Mutex::new(ManualState {
+                now: Duration::ZERO,
+                sleepers: BTreeMap::new(),
+                next_id: 0,
+                polls: 0,
+            })),
+        }
+    }
+
+    fn shared(&self) -> SharedClock {
+        Arc::new(self.clone())
+    }
+
+    fn pending_sleeps(&self) -> usize {
+        self.state.lock().unwrap().sleepers.len()
+    }
+
+    // Move time forward by `d`. Sleeps fire in deadline order, and `now()`
+    // reads each deadline while its sleepers run — so a task that sleeps again
+    // inside the window fires again inside the window.
+    async fn advance(&self, d: Duration) {
+        let target = self.state.lock().unwrap().now + d;
+        self.settle().await;
+        loop {
+            let due: Vec<Waker> = {
+                let mut s = self.state.lock().unwrap();
+                let Some(&(deadline, _)) = s.sleepers.keys().next() else {
+                    break;
+                };
+                if deadline > target {
+                    break;
+                }
+                s.now = s.now.max(deadline);
+                let later = s.sleepers.split_off(&(deadline, u64::MAX));
+                std::mem::replace(&mut s.sleepers, later)
+                    .into_values()
+                    .collect()
+            };
+            for w in due {
+                w.wake();
+            }
+            self.settle().await;
+        }
+        {
+            let mut s = self.state.lock().unwrap();
+            s.now = s.now.max(target);
+        }
+        self.settle().await;
+    }
+
+    // Yield until woken tasks stop touching the clock. On a current-thread
+    // runtime one yield_now() lets every already-woken task run; two quiet
+    // rounds in a row covers chains like timer -> channel -> consumer.
+    async fn settle(&self) {
+        let mut quiet = 0;
+        while quiet < 2 {
+            let before = self.state.lock().unwrap().polls;
+            tokio::task::yield_now().await;
+            if self.state.lock().unwrap().polls == before {
+                quiet += 1;
+            } else {
+                quiet = 0;
+            }
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> BoxFuture {
+        let id = {
+            let mut s = self.state.lock().unwrap();
+            s.next_id += 1;
+            s.next_id
+        };
+        Box::pin(ManualSleep {
+            state: Arc::clone(&self.state),
+            key: (deadline, id),
+        })
+    }
+}
+
+// --- Production code: same logic as the tokio lessons, clock injected ---
+
+// async_streams::countdown
+fn countdown(clock: SharedClock, from: i32, step: Duration) -> impl Stream<Item = i32> {
+    stream! {
+        for i in (1..=from).rev() {
+            clock.sleep(step).await;
+            yield i;
+        }
+    }
+}
+
+// async_streams::event_stream
+fn event_stream(
+    clock: SharedClock,
+    count: u32,
+    step: Duration,
+) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        for i in 0..count {
+            clock.sleep(step).await;
+            if tx.send(format!("event_{}", i)).await.is_err() {
+                break;
+            }
+        }
+    });
+    Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+}
+
+// tokio_basics::timer_demo's interval loop, reporting each tick's time
+async fn heartbeat(clock: SharedClock, period: Duration, beats: mpsc::UnboundedSender<Duration>) {
+    let mut ticker = interval(&clock, period);
+    loop {
+        let at = ticker.tick().await;
+        if beats.send(at).is_err() {
+            return; // nobody listening
+        }
+    }
+}
+
+// current_thread: ManualClock::advance relies on yielding to run woken tasks
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    // Production wiring: the real clock
+    let clock = SystemClock::shared();
+
+    let mut cd = std::pin::pin!(countdown(Arc::clone(&clock), 3, Duration::from_millis(5)));
+    while let Some(n) = cd.next().await {
+        print!("{} ", n);
+    }
+    println!("liftoff at ~{}ms", clock.now().as_millis()); // 3 2 1 liftoff at ~15ms
+
+    let events: Vec<String> = event_stream(Arc::clone(&clock), 3, Duration::from_millis(5))
+        .collect()
+        .await;
+    println!("{:?}", events); // ["event_0", "event_1", "event_2"]
+
+    let slow = timeout(
+        &clock,
+        Duration::from_millis(5),
+        clock.sleep(Duration::from_millis(50)),
+    )
+    .await;
+    println!("{:?}", slow); // Err(Elapsed)
+
+    // Test wiring: a manual clock — 1 hour of heartbeats, no waiting
+    let manual = ManualClock::new();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let task = tokio::spawn(heartbeat(manual.shared(), Duration::from_secs(60), tx));
+    let real = std::time::Instant::now();
+    manual.advance(Duration::from_secs(3600)).await;
+    let mut beats = 0;
+    while rx.try_recv().is_ok() {
+        beats += 1;
+    }
+    println!(
+        "{} beats in virtual {:?}, real {}ms",
+        beats,
+        manual.now(),
+        real.elapsed().as_millis()
+    ); // 61 beats in virtual 3600s, real 0ms
+    println!("waiting sleeps: {}", manual.pending_sleeps()); // waiting sleeps: 1 (the next beat)
+    task.abort();
+
+    println!("test clock done"); // test clock done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    fn drain<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        while let Ok(v) = rx.try_recv() {
+            out.push(v);
+        }
+        out
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[tokio::test]
+    async fn exactly_three_beats_in_250ms() {
+        let clock = ManualClock::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(heartbeat(clock.shared(), ms(100), tx));
+
+        let real = std::time::Instant::now();
+        clock.advance(ms(250)).await;
+        assert_eq!(drain(&mut rx), vec![ms(0), ms(100), ms(200)]);
+        assert!(real.elapsed() < ms(100)); // no real sleeping happened
+
+        clock.advance(ms(50)).await;
+        assert_eq!(drain(&mut rx), vec![ms(300)]);
+    }
+
+    #[tokio::test]
+    async fn nothing_fires_until_time_moves() {
+        let clock = ManualClock::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(heartbeat(clock.shared(), ms(10), tx));
+        clock.settle().await;
+        assert_eq!(drain(&mut rx), vec![ms(0)]); // the immediate first tick
+        clock.advance(ms(9)).await;
+        assert!(drain(&mut rx).is_empty());
+        clock.advance(ms(1)).await;
+        assert_eq!(drain(&mut rx), vec![ms(10)]);
+    }
+
+    #[tokio::test]
+    async fn countdown_steps_with_the_clock() {
+        let clock = ManualClock::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let shared = clock.shared();
+        tokio::spawn(async move {
+            let mut cd = std::pin::pin!(countdown(shared, 3, ms(5)));
+            while let Some(n) = cd.next().await {
+                tx.send(n).unwrap();
+            }
+        });
+        clock.advance(ms(4)).await;
+        assert!(drain(&mut rx).is_empty());
+        clock.advance(ms(6)).await; // t = 10
+        assert_eq!(drain(&mut rx), vec![3, 2]);
+        clock.advance(ms(100)).await;
+        assert_eq!(drain(&mut rx), vec![1]);
+        assert_eq!(clock.pending_sleeps(), 0);
+    }
+
+    #[tokio::test]
+    async fn event_stream_through_a_channel() {
+        let clock = ManualClock::new();
+        let mut events = event_stream(clock.shared(), 4, ms(5));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(e) = events.next().await {
+                tx.send(e).unwrap();
+            }
+        });
+        clock.advance(ms(12)).await;
+        assert_eq!(drain(&mut rx), vec!["event_0", "event_1"]);
+        clock.advance(ms(8)).await;
+        assert_eq!(drain(&mut rx), vec!["event_2", "event_3"]);
+    }
+
+    #[tokio::test]
+    async fn timeout_uses_the_injected_clock() {
+        let clock = ManualClock::new();
+        let shared = clock.shared();
+        let slow = {
+            let shared = Arc::clone(&shared);
+            tokio::spawn(async move {
+                let work = shared.sleep(ms(500));
+                timeout(&shared, ms(100), work).await
+            })
+        };
+        let fast = {
+            let shared = Arc::clone(&shared);
+            tokio::spawn(async move {
+                let work = shared.sleep(ms(50));
+                timeout(&shared, ms(100), work).await
+            })
+        };
+        clock.advance(ms(100)).await;
+        assert_eq!(fast.await.unwrap(), Ok(()));
+        assert_eq!(slow.await.unwrap(), Err(Elapsed));
+        assert_eq!(clock.pending_sleeps(), 0); // losers were dropped
+    }
+
+    #[tokio::test]
+    async fn interval_bursts_after_falling_behind() {
+        let clock = ManualClock::new();
+        let mut ticker = interval(&clock.shared(), ms(100));
+        assert_eq!(ticker.tick().now_or_never(), Some(ms(0)));
+        clock.advance(ms(350)).await; // nobody polled the ticker meanwhile
+        assert_eq!(ticker.tick().now_or_never(), Some(ms(100)));
+        assert_eq!(ticker.tick().now_or_never(), Some(ms(200)));
+        assert_eq!(ticker.tick().now_or_never(), Some(ms(300)));
+        assert_eq!(ticker.tick().now_or_never(), None);
+    }
+
+    #[tokio::test]
+    async fn same_deadline_fires_together() {
+        let clock = ManualClock::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for name in ["a", "b", "c"] {
+            let (shared, tx) = (clock.shared(), tx.clone());
+            tokio::spawn(async move {
+                shared.sleep(ms(10)).await;
+                tx.send((name, shared.now())).unwrap();
+            });
+        }
+        clock.advance(ms(30)).await;
+        let mut got = drain(&mut rx);
+        got.sort();
+        assert_eq!(got, vec![("a", ms(10)), ("b", ms(10)), ("c", ms(10))]);
+    }
+
+    #[tokio::test]
+    async fn system_clock_really_sleeps() {
+        let clock = SystemClock::shared();
+        let start = clock.now();
+        clock.sleep(ms(10)).await;
+        assert!(clock.now() - start >= ms(10));
+    }
+}
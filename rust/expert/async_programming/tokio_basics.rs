@@ -21,6 +21,7 @@ use tokio::sync::{Mutex, RwLock, mpsc, oneshot};
 use tokio::time::{Instant, sleep, timeout};
 
 // --- Tasks ---
+// (these tasks can't be told to stop: cancellation.rs adds tokens and graceful shutdown)
 async fn task_demo() {
     // tokio::spawn: schedule a task on the runtime, runs concurrently
     // Returns JoinHandle<T> — like thread::spawn
@@ -32,6 +33,7 @@ async fn task_demo() {
     println!("{}", handle.await.unwrap()); // 42
 
     // Spawning multiple tasks
+    // (one failure or panic here leaves the rest running: task_group.rs fixes that)
     let handles: Vec<_> = (0..5)
         .map(|i| {
             tokio::spawn(async move {
@@ -51,6 +53,7 @@ async fn task_demo() {
 }
 
 // --- Timers ---
+// Real sleeps make tests slow; test_clock.rs injects a Clock that tests advance by hand
 async fn timer_demo() {
     let start = Instant::now();
 
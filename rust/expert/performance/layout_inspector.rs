@@ -0,0 +1,380 @@
+// Struct layout inspector: offsets, padding holes, and a better field order
+//
+// memory_layout.rs and os_concepts.rs compare BadOrder/GoodOrder and
+// Unpadded/Padded/Optimized by printing size_of and doing the arithmetic in
+// comments. This file makes the compiler do the bookkeeping:
+//
+//   inspect_layout! { struct ... }   — define a struct and record, per field,
+//                                      its offset (mem::offset_of!), size and align
+//   TypeLayout                       — holes, total padding, a pretty report and
+//                                      a suggested field order with its size
+//   assert_layout!(T, size <= 24, no_padding)
+//                                    — fail a test when a hot struct regresses
+//
+// Offsets are what the compiler actually chose. With the default repr(Rust)
+// fields are already reordered, so the report mostly matters for #[repr(C)]
+// types (FFI, on-disk formats), where declaration order is the layout.
+//
+// A proc-macro derive (#[derive(Layout)]) would look nicer but needs its own
+// crate; macro_rules! wrapping the definition works in a single file.
+
+use std::fmt;
+use std::mem;
+
+#[derive(Debug, Clone, PartialEq)]
+struct FieldInfo {
+    name: &'static str,
+    type_name: &'static str,
+    offset: usize,
+    size: usize,
+    align: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Hole {
+    offset: usize,
+    size: usize,
+}
+
+#[derive(Debug, Clone)]
+struct TypeLayout {
+    name: &'static str,
+    size: usize,
+    align: usize,
+    fields: Vec<FieldInfo>, // sorted by offset
+}
+
+trait Inspect {
+    fn layout() -> TypeLayout;
+}
+
+impl TypeLayout {
+    // Gaps between fields, plus trailing padding up to the struct's size
+    fn holes(&self) -> Vec<Hole> {
+        let mut holes = Vec::new();
+        let mut end = 0;
+        for f in &self.fields {
+            if f.offset > end {
+                holes.push(Hole {
+                    offset: end,
+                    size: f.offset - end,
+                });
+            }
+            end = end.max(f.offset + f.size);
+        }
+        if self.size > end {
+            holes.push(Hole {
+                offset: end,
+                size: self.size - end,
+            });
+        }
+        holes
+    }
+
+    fn padding(&self) -> usize {
+        self.holes().iter().map(|h| h.size).sum()
+    }
+
+    // Largest alignment first — a field never needs padding in front of it
+    // when everything before it is at least as aligned
+    fn suggested_order(&self) -> Vec<&FieldInfo> {
+        let mut order: Vec<&FieldInfo> = self.fields.iter().collect();
+        order.sort_by(|a, b| b.align.cmp(&a.align).then(b.size.cmp(&a.size)));
+        order
+    }
+
+    // Size the struct would have as repr(C) with the suggested order
+    fn suggested_size(&self) -> usize {
+        let mut end: usize = 0;
+        for f in self.suggested_order() {
+            end = end.next_multiple_of(f.align) + f.size;
+        }
+        end.next_multiple_of(self.align)
+    }
+}
+
+impl fmt::Display for TypeLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: size {}, align {}, padding {}",
+            self.name,
+            self.size,
+            self.align,
+            self.padding()
+        )?;
+        writeln!(f, "  offset  size  align  field")?;
+
+        // Merge fields and holes into one offset-ordered listing
+        let holes = self.holes();
+        let mut rows: Vec<(usize, String)> = self
+            .fields
+            .iter()
+            .map(|fi| {
+                let line = format!(
+                    "  {:>6}  {:>4}  {:>5}  {}: {}",
+                    fi.offset, fi.size, fi.align, fi.name, fi.type_name
+                );
+                (fi.offset, line)
+            })
+            .collect();
+        rows.extend(holes.iter().map(|h| {
+            (
+                h.offset,
+                format!("  {:>6}  {:>4}         <padding>", h.offset, h.size),
+            )
+        }));
+        rows.sort_by_key(|(offset, _)| *offset);
+        for (_, line) in rows {
+            writeln!(f, "{}", line)?;
+        }
+
+        let suggested = self.suggested_size();
+        let names: Vec<&str> = self.suggested_order().iter().map(|fi| fi.name).collect();
+        if suggested < self.size {
+            write!(
+                f,
+                "  suggested order: {} -> size {} (saves {} bytes)",
+                names.join(", "),
+                suggested,
+                self.size - suggested
+            )
+        } else {
+            write!(f, "  field order is already optimal")
+        }
+    }
+}
+
+// Wraps a struct definition and implements Inspect for it
+macro_rules! inspect_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($fvis:vis $field:ident : $ty:ty),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($fvis $field: $ty),+
+        }
+
+        impl Inspect for $name {
+            fn layout() -> TypeLayout {
+                let mut fields = vec![
+                    $(FieldInfo {
+                        name: stringify!($field),
+                        type_name: stringify!($ty),
+                        offset: mem::offset_of!($name, $field),
+                        size: mem::size_of::<$ty>(),
+                        align: mem::align_of::<$ty>(),
+                    }),+
+                ];
+                fields.sort_by_key(|f| f.offset);
+                TypeLayout {
+                    name: stringify!($name),
+                    size: mem::size_of::<$name>(),
+                    align: mem::align_of::<$name>(),
+                    fields,
+                }
+            }
+        }
+    };
+}
+
+// assert_layout!(T, size <= 24, no_padding, align == 8, padding <= 2)
+// Each check is consumed in turn (a "tt muncher"); failures print the full report.
+macro_rules! assert_layout {
+    ($t:ty, $($checks:tt)+) => {{
+        let layout = <$t as Inspect>::layout();
+        assert_layout!(@check layout, $($checks)+);
+    }};
+
+    (@check $l:ident $(,)?) => {};
+    (@check $l:ident, size <= $n:expr $(, $($rest:tt)*)?) => {
+        assert!($l.size <= $n, "size {} > {}\n{}", $l.size, $n, $l);
+        assert_layout!(@check $l $(, $($rest)*)?);
+    };
+    (@check $l:ident, size == $n:expr $(, $($rest:tt)*)?) => {
+        assert!($l.size == $n, "size {} != {}\n{}", $l.size, $n, $l);
+        assert_layout!(@check $l $(, $($rest)*)?);
+    };
+    (@check $l:ident, align == $n:expr $(, $($rest:tt)*)?) => {
+        assert!($l.align == $n, "align {} != {}\n{}", $l.align, $n, $l);
+        assert_layout!(@check $l $(, $($rest)*)?);
+    };
+    (@check $l:ident, padding <= $n:expr $(, $($rest:tt)*)?) => {
+        assert!($l.padding() <= $n, "padding {} > {}\n{}", $l.padding(), $n, $l);
+        assert_layout!(@check $l $(, $($rest)*)?);
+    };
+    (@check $l:ident, no_padding $(, $($rest:tt)*)?) => {
+        assert!($l.padding() == 0, "expected no padding\n{}", $l);
+        assert_layout!(@check $l $(, $($rest)*)?);
+    };
+}
+
+// --- The structs from memory_layout.rs and os_concepts.rs ---
+
+inspect_layout! {
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct BadOrder {
+        a: u8,
+        b: u64,
+        c: u8,
+        d: u32,
+    }
+}
+
+inspect_layout! {
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct GoodOrder {
+        b: u64,
+        d: u32,
+        a: u8,
+        c: u8,
+    }
+}
+
+inspect_layout! {
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct Padded {
+        a: u8,
+        b: u32,
+        c: u8,
+    }
+}
+
+// Same fields, default repr: the compiler has already reordered them
+inspect_layout! {
+    #[allow(dead_code)]
+    struct Optimized {
+        a: u8,
+        b: u32,
+        c: u8,
+    }
+}
+
+inspect_layout! {
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct Unpadded {
+        a: u8,
+        b: u8,
+        c: u16,
+        d: u32,
+    }
+}
+
+fn main() {
+    println!("{}\n", BadOrder::layout());
+    // BadOrder: size 24, align 8, padding 10
+    //   offset  size  align  field
+    //        0     1      1  a: u8
+    //        1     7         <padding>
+    //        8     8      8  b: u64
+    //       16     1      1  c: u8
+    //       17     3         <padding>
+    //       20     4      4  d: u32
+    //   suggested order: b, d, a, c -> size 16 (saves 8 bytes)
+
+    println!("{}\n", GoodOrder::layout());
+    // GoodOrder: size 16, align 8, padding 2 ... field order is already optimal
+
+    println!("{}\n", Padded::layout()); // size 12, padding 6 -> suggested size 8
+    println!("{}\n", Optimized::layout()); // size 8: repr(Rust) reordered the fields (which order is up to the compiler)
+    println!("{}", Unpadded::layout()); // size 8, padding 0
+
+    // The same checks you'd put in a test
+    assert_layout!(GoodOrder, size <= 16, align == 8, padding <= 2);
+    assert_layout!(Unpadded, size == 8, no_padding);
+    println!("\nlayout assertions passed"); // layout assertions passed
+}
+
+// ============================================================
+// TESTS — these are the layout regression checks CI would run
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_follow_declaration_order_for_repr_c() {
+        let l = BadOrder::layout();
+        let offsets: Vec<(&str, usize)> = l.fields.iter().map(|f| (f.name, f.offset)).collect();
+        assert_eq!(offsets, vec![("a", 0), ("b", 8), ("c", 16), ("d", 20)]);
+    }
+
+    #[test]
+    fn holes_include_trailing_padding() {
+        assert_eq!(
+            BadOrder::layout().holes(),
+            vec![
+                Hole { offset: 1, size: 7 },
+                Hole {
+                    offset: 17,
+                    size: 3
+                }
+            ]
+        );
+        assert_eq!(
+            Padded::layout().holes(),
+            vec![Hole { offset: 1, size: 3 }, Hole { offset: 9, size: 3 }]
+        );
+        assert_eq!(
+            GoodOrder::layout().holes(),
+            vec![Hole {
+                offset: 14,
+                size: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn suggested_order_matches_the_hand_written_good_order() {
+        let l = BadOrder::layout();
+        let names: Vec<&str> = l.suggested_order().iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["b", "d", "a", "c"]);
+        assert_eq!(l.suggested_size(), mem::size_of::<GoodOrder>());
+        assert_eq!(Padded::layout().suggested_size(), 8);
+    }
+
+    #[test]
+    fn default_repr_is_already_packed() {
+        let l = Optimized::layout();
+        // repr(Rust) field order is unspecified — only check the outcome
+        assert_eq!(l.size, l.suggested_size());
+        assert_eq!((l.size, l.align, l.padding()), (8, 4, 2));
+    }
+
+    #[test]
+    fn report_lists_fields_and_padding() {
+        let report = BadOrder::layout().to_string();
+        assert!(report.contains("padding 10"));
+        assert!(report.contains("<padding>"));
+        assert!(report.contains("b: u64"));
+        assert!(report.contains("saves 8 bytes"));
+        assert!(GoodOrder::layout().to_string().contains("already optimal"));
+    }
+
+    #[test]
+    fn hot_structs_stay_small() {
+        assert_layout!(GoodOrder, size <= 16, align == 8);
+        assert_layout!(Unpadded, size == 8, no_padding);
+        assert_layout!(Optimized, size <= 8, padding <= 2,);
+    }
+
+    #[test]
+    #[should_panic(expected = "size 24 > 16")]
+    fn size_regression_fails() {
+        assert_layout!(BadOrder, size <= 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected no padding")]
+    fn padding_regression_fails() {
+        assert_layout!(Padded, size <= 12, no_padding);
+    }
+}
@@ -42,6 +42,7 @@ struct CacheAligned {
     value: u64,
 }
 // Prevents false sharing in concurrent code
+// (striped_counter.rs uses padded slots for per-thread counters and histograms)
 
 // --- repr(transparent): for newtypes, same layout as inner type ---
 #[repr(transparent)]
@@ -67,6 +68,7 @@ fn enum_layout() {
 }
 
 // --- Field ordering for minimal size ---
+// layout_inspector.rs prints these offsets/holes and can assert on them in tests
 #[allow(dead_code)]
 struct BadOrder {
     a: u8,  // 1 + 7 padding
@@ -100,6 +102,7 @@ struct ParticleAOS {
 }
 
 // SOA: separate arrays for each field — much more cache-friendly for SIMD/batched ops
+// (soa_macro.rs generates this kind of struct from ParticleAOS-style definitions)
 #[allow(dead_code)]
 struct ParticlesSOA {
     x: Vec<f32>,
@@ -0,0 +1,796 @@
+// A process-wide metrics registry with a Prometheus endpoint
+//
+// So far our long-running binaries count things with loose atomics (the
+// `peak.fetch_max` in atomic_types.rs, GlobalCounter in mutable_statics.rs).
+// Nobody outside the process can see them. This file gives them names,
+// labels and a standard wire format:
+//
+//   counter    only goes up             http_requests_total{method="GET"} 1027
+//   gauge      goes up and down         queue_depth 3
+//   histogram  distribution of values   request_seconds_bucket{le="0.1"} 17
+//                                       request_seconds_sum 2.31
+//                                       request_seconds_count 20
+//
+// Design:
+//   - registering looks up (name, labels) under a lock and hands back a
+//     cheap handle (an Arc'd atomic); recording through the handle is
+//     lock-free, so keep handles around instead of re-registering per event
+//   - registering the same name + labels again returns the same series
+//   - render() writes the Prometheus text exposition format (version 0.0.4)
+//   - serve() answers `GET /metrics` on a loopback address from one thread
+//
+// For counters bumped by many threads at once, swap the AtomicU64 for the
+// StripedCounter in striped_counter.rs.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// --- Errors ---
+
+#[derive(Debug, PartialEq)]
+enum MetricsError {
+    InvalidName(String),
+    InvalidLabel(String),
+    // Same name registered as a different kind, or with other label names
+    Conflict { name: String, reason: String },
+}
+
+impl fmt::Display for MetricsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricsError::InvalidName(n) => write!(f, "invalid metric name `{}`", n),
+            MetricsError::InvalidLabel(n) => write!(f, "invalid label name `{}`", n),
+            MetricsError::Conflict { name, reason } => {
+                write!(f, "metric `{}` already registered {}", name, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MetricsError {}
+
+// --- Metric handles ---
+
+#[derive(Clone, Default)]
+struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    fn inc(&self) {
+        self.add(1);
+    }
+
+    fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Prometheus gauges are floats: an f64 stored as bits (0u64 is 0.0)
+#[derive(Clone, Default)]
+struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    fn set(&self, v: f64) {
+        self.0.store(v.to_bits(), Ordering::Relaxed);
+    }
+
+    fn add(&self, n: f64) {
+        self.update(|v| v + n);
+    }
+
+    fn sub(&self, n: f64) {
+        self.update(|v| v - n);
+    }
+
+    // High-water mark, like atomic_types.rs's `peak.fetch_max`
+    fn set_max(&self, v: f64) {
+        self.update(|old| old.max(v));
+    }
+
+    fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    // No atomic f64 arithmetic: CAS on the bit pattern, as Histogram::observe
+    fn update(&self, f: impl Fn(f64) -> f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                Some(f(f64::from_bits(old)).to_bits())
+            });
+    }
+}
+
+// Seconds, from 1ms to 10s: a reasonable start for request latencies
+const DEFAULT_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 10.0];
+
+struct HistogramCore {
+    bounds: Vec<f64>,       // sorted upper bounds; +Inf is implied
+    counts: Vec<AtomicU64>, // bounds.len() + 1, *not* cumulative
+    sum_bits: AtomicU64,    // f64 stored as bits
+}
+
+#[derive(Clone)]
+struct Histogram(Arc<HistogramCore>);
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.retain(|b| b.is_finite());
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+        Histogram(Arc::new(HistogramCore {
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            bounds,
+            sum_bits: AtomicU64::new(0f64.to_bits()),
+        }))
+    }
+
+    fn observe(&self, v: f64) {
+        let h = &self.0;
+        // `le` is inclusive: v lands in the first bucket with v <= bound
+        let i = h.bounds.partition_point(|&b| b < v);
+        h.counts[i].fetch_add(1, Ordering::Relaxed);
+        // No atomic f64 add: CAS on the bit pattern
+        let mut old = h.sum_bits.load(Ordering::Relaxed);
+        loop {
+            let new = (f64::from_bits(old) + v).to_bits();
+            match h
+                .sum_bits
+                .compare_exchange_weak(old, new, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(actual) => old = actual,
+            }
+        }
+    }
+
+    fn time<R>(&self, f: impl FnOnce() -> R) -> R {
+        let start = std::time::Instant::now();
+        let r = f();
+        self.observe(start.elapsed().as_secs_f64());
+        r
+    }
+
+    fn count(&self) -> u64 {
+        self.0
+            .counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    fn sum(&self) -> f64 {
+        f64::from_bits(self.0.sum_bits.load(Ordering::Relaxed))
+    }
+}
+
+// --- Registry ---
+
+type Labels = Vec<(String, String)>; // sorted by label name
+
+#[derive(Clone)]
+enum Series {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+impl Series {
+    fn kind(&self) -> &'static str {
+        match self {
+            Series::Counter(_) => "counter",
+            Series::Gauge(_) => "gauge",
+            Series::Histogram(_) => "histogram",
+        }
+    }
+}
+
+struct Family {
+    help: String,
+    kind: &'static str,
+    label_names: Vec<String>,
+    series: BTreeMap<Labels, Series>,
+}
+
+#[derive(Default)]
+struct Registry {
+    families: Mutex<BTreeMap<String, Family>>,
+}
+
+// The process-wide registry
+fn global() -> Arc<Registry> {
+    static GLOBAL: OnceLock<Arc<Registry>> = OnceLock::new();
+    Arc::clone(GLOBAL.get_or_init(Default::default))
+}
+
+// [a-zA-Z_:][a-zA-Z0-9_:]* for metrics; labels: no ':' and no "__" prefix
+fn valid_name(name: &str, colons: bool) -> bool {
+    let ok = |c: char, first: bool| {
+        c.is_ascii_alphabetic()
+            || c == '_'
+            || (colons && c == ':')
+            || (!first && c.is_ascii_digit())
+    };
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| ok(c, true)) && chars.all(|c| ok(c, false))
+}
+
+impl Registry {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn counter(
+        &self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+    ) -> Result<Counter, MetricsError> {
+        match self.register(name, help, labels, || Series::Counter(Counter::default()))? {
+            Series::Counter(c) => Ok(c),
+            _ => unreachable!("kind checked in register"),
+        }
+    }
+
+    fn gauge(
+        &self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+    ) -> Result<Gauge, MetricsError> {
+        match self.register(name, help, labels, || Series::Gauge(Gauge::default()))? {
+            Series::Gauge(g) => Ok(g),
+            _ => unreachable!("kind checked in register"),
+        }
+    }
+
+    // `buckets` only matters for the first registration of a family member
+    fn histogram(
+        &self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+        buckets: &[f64],
+    ) -> Result<Histogram, MetricsError> {
+        match self.register(name, help, labels, || {
+            Series::Histogram(Histogram::new(buckets))
+        })? {
+            Series::Histogram(h) => Ok(h),
+            _ => unreachable!("kind checked in register"),
+        }
+    }
+
+    fn register(
+        &self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+        make: impl FnOnce() -> Series,
+    ) -> Result<Series, MetricsError> {
+        if !valid_name(name, true) {
+            return Err(MetricsError::InvalidName(name.to_string()));
+        }
+        let mut sorted: Labels = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        sorted.sort();
+        for (k, _) in &sorted {
+            if !valid_name(k, false) || k.starts_with("__") || k == "le" {
+                return Err(MetricsError::InvalidLabel(k.clone()));
+            }
+        }
+        let label_names: Vec<String> = sorted.iter().map(|(k, _)| k.clone()).collect();
+
+        let mut families = self.families.lock().unwrap();
+        let series = make();
+        let family = families.entry(name.to_string()).or_insert_with(|| Family {
+            help: help.to_string(),
+            kind: series.kind(),
+            label_names: label_names.clone(),
+            series: BTreeMap::new(),
+        });
+        let conflict = |reason: String| MetricsError::Conflict {
+            name: name.to_string(),
+            reason,
+        };
+        if family.kind != series.kind() {
+            return Err(conflict(format!("as a {}", family.kind)));
+        }
+        if family.label_names != label_names {
+            return Err(conflict(format!("with labels {:?}", family.label_names)));
+        }
+        Ok(family.series.entry(sorted).or_insert(series).clone())
+    }
+
+    // Prometheus text exposition format
+    fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            let _ = writeln!(out, "# HELP {} {}", name, escape(&family.help, false));
+            let _ = writeln!(out, "# TYPE {} {}", name, family.kind);
+            for (labels, series) in &family.series {
+                match series {
+                    Series::Counter(c) => {
+                        let _ = writeln!(out, "{}{} {}", name, label_set(labels, None), c.get());
+                    }
+                    Series::Gauge(g) => {
+                        let value = fmt_value(g.get());
+                        let _ = writeln!(out, "{}{} {}", name, label_set(labels, None), value);
+                    }
+                    Series::Histogram(h) => render_histogram(&mut out, name, labels, h),
+                }
+            }
+        }
+        out
+    }
+}
+
+// Buckets are cumulative on the wire: `le="0.1"` counts everything <= 0.1
+fn render_histogram(out: &mut String, name: &str, labels: &Labels, h: &Histogram) {
+    let core = &h.0;
+    let mut cumulative = 0;
+    for (i, count) in core.counts.iter().enumerate() {
+        cumulative += count.load(Ordering::Relaxed);
+        let le = core
+            .bounds
+            .get(i)
+            .map_or("+Inf".to_string(), |&b| fmt_value(b));
+        let _ = writeln!(
+            out,
+            "{}_bucket{} {}",
+            name,
+            label_set(labels, Some(&le)),
+            cumulative
+        );
+    }
+    let sum = fmt_value(h.sum());
+    let _ = writeln!(out, "{}_sum{} {}", name, label_set(labels, None), sum);
+    let _ = writeln!(
+        out,
+        "{}_count{} {}",
+        name,
+        label_set(labels, None),
+        cumulative
+    );
+}
+
+// Rust prints inf / -inf / NaN; the text format spells them +Inf / -Inf / NaN
+fn fmt_value(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+// `{a="1",le="0.5"}`, or nothing at all when there are no labels
+fn label_set(labels: &Labels, le: Option<&str>) -> String {
+    let mut parts: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape(v, true)))
+        .collect();
+    if let Some(le) = le {
+        parts.push(format!("le=\"{}\"", le));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", parts.join(","))
+    }
+}
+
+// HELP text escapes \ and newline; label values also escape "
+fn escape(s: &str, quotes: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '"' if quotes => out.push_str("\\\""),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// --- HTTP endpoint ---
+
+// Stops the server thread when dropped
+struct MetricsServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// Bind `addr` (must be loopback: the endpoint has no auth) and answer
+// `GET /metrics`. Port 0 picks a free port; see MetricsServer::addr.
+fn serve(registry: Arc<Registry>, addr: SocketAddr) -> io::Result<MetricsServer> {
+    if !addr.ip().is_loopback() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("refusing to expose metrics on non-loopback {}", addr),
+        ));
+    }
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = thread::Builder::new().name("metrics-http".into()).spawn({
+        let stop = Arc::clone(&stop);
+        move || {
+            for conn in listener.incoming() {
+                if stop.load(Ordering::Acquire) {
+                    break;
+                }
+                // One bad client mustn't kill the endpoint
+                if let Ok(conn) = conn {
+                    let _ = handle(conn, &registry);
+                }
+            }
+        }
+    })?;
+    Ok(MetricsServer {
+        addr,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+fn handle(mut conn: TcpStream, registry: &Registry) -> io::Result<()> {
+    conn.set_read_timeout(Some(Duration::from_secs(2)))?;
+    // Read up to the end of the headers; we never need a body
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = conn.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&chunk[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.lines().next().unwrap_or("").split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", registry.render()),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    write!(
+        conn,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+impl MetricsServer {
+    fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        // accept() is blocking: a throwaway connection wakes it to see `stop`
+        let _ = TcpStream::connect(self.addr);
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+// Minimal client, enough for the demo and tests
+fn http_get(addr: SocketAddr, path: &str) -> io::Result<String> {
+    let mut conn = TcpStream::connect(addr)?;
+    write!(conn, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path)?;
+    let mut response = String::new();
+    conn.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let registry = global();
+
+    // --- Register once, keep the handles ---
+    let get = registry.counter(
+        "http_requests_total",
+        "Requests served.",
+        &[("method", "GET")],
+    )?;
+    let post = registry.counter(
+        "http_requests_total",
+        "Requests served.",
+        &[("method", "POST")],
+    )?;
+    let in_flight = registry.gauge("http_in_flight", "Requests being handled.", &[])?;
+    let peak = registry.gauge("http_in_flight_peak", "Most requests at once.", &[])?;
+    let latency = registry.histogram(
+        "http_request_seconds",
+        "Request latency.",
+        &[],
+        &DEFAULT_BUCKETS,
+    )?;
+
+    // --- Record from many threads, lock-free ---
+    thread::scope(|s| {
+        for t in 0..4u64 {
+            let (get, post, in_flight, peak, latency) = (&get, &post, &in_flight, &peak, &latency);
+            s.spawn(move || {
+                for i in 0..25 {
+                    in_flight.add(1.0);
+                    peak.set_max(in_flight.get());
+                    latency.observe(0.002 * (t * 25 + i) as f64 / 10.0);
+                    if i % 5 == 0 {
+                        post.inc()
+                    } else {
+                        get.inc()
+                    }
+                    in_flight.sub(1.0);
+                }
+            });
+        }
+    });
+    println!("{} {} {}", get.get(), post.get(), in_flight.get()); // 80 20 0
+    println!("{}", peak.get() >= 1.0); // true
+    println!("{} {:.2}", latency.count(), latency.sum()); // 100 0.99
+
+    // Registering again hands back the same series
+    registry
+        .counter(
+            "http_requests_total",
+            "Requests served.",
+            &[("method", "GET")],
+        )?
+        .add(5);
+    println!("{}", get.get()); // 85
+
+    // Mistakes are errors, not silent duplicates
+    println!(
+        "{}",
+        registry
+            .gauge("http_requests_total", "", &[("method", "GET")])
+            .err()
+            .unwrap()
+    );
+    // metric `http_requests_total` already registered as a counter
+    println!("{}", registry.counter("bad-name", "", &[]).err().unwrap());
+    // invalid metric name `bad-name`
+
+    // --- Text format ---
+    let text = registry.render();
+    for line in text
+        .lines()
+        .filter(|l| l.starts_with("http_requests") || l.contains("le=\"0.01\""))
+    {
+        println!("{}", line);
+    }
+    // http_request_seconds_bucket{le="0.01"} 51
+    // http_requests_total{method="GET"} 85
+    // http_requests_total{method="POST"} 20
+
+    // A private registry (per component, or per test) renders the same way
+    let local = Registry::new();
+    local
+        .gauge("build_info", "Build metadata.", &[("version", "1.2.0")])?
+        .set(1.0);
+    print!("{}", local.render());
+    // # HELP build_info Build metadata.
+    // # TYPE build_info gauge
+    // build_info{version="1.2.0"} 1
+
+    // --- Scrape it over HTTP ---
+    let server = serve(Arc::clone(&registry), "127.0.0.1:0".parse()?)?;
+    let response = http_get(server.addr(), "/metrics")?;
+    println!("{}", response.lines().next().unwrap_or("")); // HTTP/1.1 200 OK
+    println!("{}", response.contains("http_in_flight_peak")); // true
+    println!(
+        "{}",
+        http_get(server.addr(), "/")
+            .unwrap_or_default()
+            .lines()
+            .next()
+            .unwrap_or("")
+    );
+    // HTTP/1.1 404 Not Found
+    println!(
+        "{}",
+        serve(Arc::clone(&registry), "0.0.0.0:0".parse()?).is_err()
+    ); // true
+    drop(server); // stops the listener thread
+
+    // Latency in practice: wrap the work
+    let answer = latency.time(|| 6 * 7);
+    println!("{} {}", answer, latency.count()); // 42 101
+
+    println!("metrics done"); // metrics done
+    Ok(())
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_gauges_in_text_format() {
+        let r = Registry::new();
+        r.counter(
+            "jobs_total",
+            "Jobs run.",
+            &[("queue", "fast"), ("host", "a")],
+        )
+        .unwrap()
+        .add(3);
+        r.gauge("temperature", "Degrees.", &[]).unwrap().set(-4.0);
+        assert_eq!(
+            r.render(),
+            "# HELP jobs_total Jobs run.\n\
+             # TYPE jobs_total counter\n\
+             jobs_total{host=\"a\",queue=\"fast\"} 3\n\
+             # HELP temperature Degrees.\n\
+             # TYPE temperature gauge\n\
+             temperature -4\n"
+        );
+    }
+
+    #[test]
+    fn escapes_label_values_and_help() {
+        let r = Registry::new();
+        r.counter("x", "line\\one\nline two", &[("path", "C:\\\"q\"\n")])
+            .unwrap()
+            .inc();
+        let text = r.render();
+        assert!(
+            text.contains("# HELP x line\\\\one\\nline two\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("x{path=\"C:\\\\\\\"q\\\"\\n\"} 1\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let r = Registry::new();
+        let h = r
+            .histogram(
+                "size_bytes",
+                "Sizes.",
+                &[("kind", "img")],
+                &[100.0, 10.0, 1000.0],
+            )
+            .unwrap();
+        for v in [5.0, 10.0, 50.0, 500.0, 5000.0] {
+            h.observe(v);
+        }
+        let text = r.render();
+        let expected = "size_bytes_bucket{kind=\"img\",le=\"10\"} 2\n\
+                        size_bytes_bucket{kind=\"img\",le=\"100\"} 3\n\
+                        size_bytes_bucket{kind=\"img\",le=\"1000\"} 4\n\
+                        size_bytes_bucket{kind=\"img\",le=\"+Inf\"} 5\n\
+                        size_bytes_sum{kind=\"img\"} 5565\n\
+                        size_bytes_count{kind=\"img\"} 5\n";
+        assert!(text.ends_with(expected), "{}", text);
+    }
+
+    #[test]
+    fn non_finite_values_use_prometheus_spelling() {
+        let r = Registry::new();
+        r.gauge("hi", "", &[]).unwrap().set(f64::INFINITY);
+        r.gauge("lo", "", &[]).unwrap().set(f64::NEG_INFINITY);
+        r.gauge("nan", "", &[]).unwrap().set(f64::NAN);
+        r.histogram("h", "", &[], &[1.0])
+            .unwrap()
+            .observe(f64::INFINITY);
+        let text = r.render();
+        for line in [
+            "\nhi +Inf\n",
+            "\nlo -Inf\n",
+            "\nnan NaN\n",
+            "\nh_sum +Inf\n",
+        ] {
+            assert!(text.contains(line), "{:?} missing from\n{}", line, text);
+        }
+    }
+
+    #[test]
+    fn same_series_is_shared_and_conflicts_are_errors() {
+        let r = Registry::new();
+        let a = r.counter("hits", "", &[("k", "v")]).unwrap();
+        let b = r.counter("hits", "", &[("k", "v")]).unwrap();
+        a.inc();
+        b.inc();
+        assert_eq!(a.get(), 2);
+        assert!(matches!(
+            r.gauge("hits", "", &[("k", "v")]),
+            Err(MetricsError::Conflict { .. })
+        ));
+        assert!(matches!(
+            r.counter("hits", "", &[("other", "v")]),
+            Err(MetricsError::Conflict { .. })
+        ));
+        assert_eq!(
+            r.counter("9lives", "", &[]).err(),
+            Some(MetricsError::InvalidName("9lives".into()))
+        );
+        assert_eq!(
+            r.counter("ok", "", &[("__reserved", "x")]).err(),
+            Some(MetricsError::InvalidLabel("__reserved".into()))
+        );
+        assert!(r.counter("ns:subsystem_total", "", &[]).is_ok());
+    }
+
+    #[test]
+    fn concurrent_recording_adds_up() {
+        let r = Registry::new();
+        let c = r.counter("n", "", &[]).unwrap();
+        let h = r.histogram("v", "", &[], &[0.5]).unwrap();
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1_000 {
+                        c.inc();
+                        h.observe(0.25);
+                    }
+                });
+            }
+        });
+        assert_eq!(c.get(), 4_000);
+        assert_eq!(h.count(), 4_000);
+        assert_eq!(h.sum(), 1_000.0); // 0.25 sums exactly in binary
+    }
+
+    #[test]
+    fn http_endpoint_serves_metrics() {
+        let r = Arc::new(Registry::new());
+        r.gauge("up", "Alive.", &[]).unwrap().set(1.0);
+        let server = serve(Arc::clone(&r), "127.0.0.1:0".parse().unwrap()).unwrap();
+        let response = http_get(server.addr(), "/metrics").unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.ends_with("\r\n\r\n# HELP up Alive.\n# TYPE up gauge\nup 1\n"));
+
+        let mut conn = TcpStream::connect(server.addr()).unwrap();
+        conn.write_all(b"POST /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 405"));
+
+        let addr = server.addr();
+        drop(server);
+        assert!(
+            http_get(addr, "/metrics").is_err(),
+            "listener closed on drop"
+        );
+    }
+
+    #[test]
+    fn refuses_non_loopback_addresses() {
+        let err = serve(Arc::new(Registry::new()), "0.0.0.0:0".parse().unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -25,6 +25,7 @@ use std::hint::black_box;
 use std::time::Instant;
 
 // --- Manual timing (poor man's benchmark) ---
+// Flat and single-level; span_profiler.rs nests spans into a call tree with self time
 fn time_it<F: Fn() -> R, R>(label: &str, f: F) -> R {
     let start = Instant::now();
     let result = f();
@@ -32,6 +32,7 @@ fn scale_auto(v: &mut [f32], factor: f32) {
 }
 
 // --- Checking if SIMD is available at runtime ---
+// Printing is only half the story — simd_kernels.rs uses the result to pick a kernel
 fn cpu_features() {
     // is_x86_feature_detected! — runtime detection (stable)
     #[cfg(target_arch = "x86_64")]
@@ -0,0 +1,515 @@
+// Runtime-dispatched SIMD kernels
+//
+// simd.rs shows individual SSE intrinsics and prints which features the CPU has.
+// A real kernel library puts those pieces together:
+//
+//   1. Detect the best instruction set once (is_x86_feature_detected!)
+//   2. Compile each kernel several times with #[target_feature(enable = "...")]
+//   3. Dispatch to the best version at runtime — one binary runs everywhere
+//
+// Kernels: sum, dot, axpy (y = a*x + y), min, max, argmax — for f32 and f64.
+// Tiers:   AVX2 (256-bit) > SSE4.1 (128-bit) > scalar
+//
+// Slices can have any length and alignment: the vector loop uses unaligned
+// loads (loadu) over full chunks, and the leftover tail is handled in scalar code.
+//
+// SIMD sums add in a different order than a left-to-right loop, so results
+// match the scalar path only within floating point tolerance, not bit-for-bit.
+// Inputs are assumed NaN-free (min/max intrinsics don't propagate NaN like f32::min).
+
+use std::hint::black_box;
+use std::ops::{Add, Mul};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Isa {
+    Scalar,
+    Sse41,
+    Avx2,
+}
+
+impl Isa {
+    fn is_supported(self) -> bool {
+        match self {
+            Isa::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Isa::Sse41 => is_x86_feature_detected!("sse4.1"),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    // Feature detection is a CPUID instruction — cheap, but do it once
+    fn best() -> Isa {
+        static BEST: OnceLock<Isa> = OnceLock::new();
+        *BEST.get_or_init(|| {
+            [Isa::Avx2, Isa::Sse41]
+                .into_iter()
+                .find(|isa| isa.is_supported())
+                .unwrap_or(Isa::Scalar)
+        })
+    }
+}
+
+// --- Scalar reference kernels (also used for tails) ---
+
+mod scalar {
+    use std::ops::{Add, Mul};
+
+    pub fn sum<T: Copy + Default + Add<Output = T>>(x: &[T]) -> T {
+        x.iter().fold(T::default(), |acc, &v| acc + v)
+    }
+
+    pub fn dot<T: Copy + Default + Add<Output = T> + Mul<Output = T>>(a: &[T], b: &[T]) -> T {
+        a.iter()
+            .zip(b)
+            .fold(T::default(), |acc, (&x, &y)| acc + x * y)
+    }
+
+    pub fn axpy<T: Copy + Add<Output = T> + Mul<Output = T>>(alpha: T, x: &[T], y: &mut [T]) {
+        for (yi, &xi) in y.iter_mut().zip(x) {
+            *yi = alpha * xi + *yi;
+        }
+    }
+
+    pub fn min<T: Copy + PartialOrd>(x: &[T]) -> Option<T> {
+        x.iter().copied().reduce(|m, v| if v < m { v } else { m })
+    }
+
+    pub fn max<T: Copy + PartialOrd>(x: &[T]) -> Option<T> {
+        x.iter().copied().reduce(|m, v| if v > m { v } else { m })
+    }
+}
+
+// --- SIMD kernels, stamped out once per (instruction set, element type) ---
+// The four variants only differ in register type, lane count and intrinsic names.
+
+#[cfg(target_arch = "x86_64")]
+macro_rules! simd_kernels {
+    (
+        $name:ident, $feature:literal, $t:ty, $v:ty, $lanes:expr,
+        $load:ident, $store:ident, $set1:ident, $zero:ident,
+        $add:ident, $mul:ident, $min:ident, $max:ident
+    ) => {
+        mod $name {
+            use super::scalar;
+            use std::arch::x86_64::*;
+
+            const LANES: usize = $lanes;
+
+            // Spill a register to memory and finish the reduction in scalar code
+            #[target_feature(enable = $feature)]
+            unsafe fn lanes(v: $v) -> [$t; LANES] {
+                let mut out = [0.0; LANES];
+                $store(out.as_mut_ptr(), v);
+                out
+            }
+
+            #[target_feature(enable = $feature)]
+            pub unsafe fn sum(x: &[$t]) -> $t {
+                let chunks = x.chunks_exact(LANES);
+                let tail = chunks.remainder();
+                let mut acc = $zero();
+                for c in chunks {
+                    acc = $add(acc, $load(c.as_ptr()));
+                }
+                scalar::sum(&lanes(acc)) + scalar::sum(tail)
+            }
+
+            #[target_feature(enable = $feature)]
+            pub unsafe fn dot(a: &[$t], b: &[$t]) -> $t {
+                let (ca, cb) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+                let (ta, tb) = (ca.remainder(), cb.remainder());
+                let mut acc = $zero();
+                for (x, y) in ca.zip(cb) {
+                    acc = $add(acc, $mul($load(x.as_ptr()), $load(y.as_ptr())));
+                }
+                scalar::sum(&lanes(acc)) + scalar::dot(ta, tb)
+            }
+
+            #[target_feature(enable = $feature)]
+            pub unsafe fn axpy(alpha: $t, x: &[$t], y: &mut [$t]) {
+                let va = $set1(alpha);
+                let mut cy = y.chunks_exact_mut(LANES);
+                let mut cx = x.chunks_exact(LANES);
+                for (yc, xc) in (&mut cy).zip(&mut cx) {
+                    let r = $add($mul(va, $load(xc.as_ptr())), $load(yc.as_ptr()));
+                    $store(yc.as_mut_ptr(), r);
+                }
+                scalar::axpy(alpha, cx.remainder(), cy.into_remainder());
+            }
+
+            #[target_feature(enable = $feature)]
+            pub unsafe fn min(x: &[$t]) -> Option<$t> {
+                if x.len() < LANES {
+                    return scalar::min(x);
+                }
+                let chunks = x.chunks_exact(LANES);
+                let tail = chunks.remainder();
+                let mut acc = $load(x.as_ptr());
+                for c in chunks {
+                    acc = $min(acc, $load(c.as_ptr()));
+                }
+                scalar::min(&lanes(acc)).map(|m| scalar::min(tail).map_or(m, |t| m.min(t)))
+            }
+
+            #[target_feature(enable = $feature)]
+            pub unsafe fn max(x: &[$t]) -> Option<$t> {
+                if x.len() < LANES {
+                    return scalar::max(x);
+                }
+                let chunks = x.chunks_exact(LANES);
+                let tail = chunks.remainder();
+                let mut acc = $load(x.as_ptr());
+                for c in chunks {
+                    acc = $max(acc, $load(c.as_ptr()));
+                }
+                scalar::max(&lanes(acc)).map(|m| scalar::max(tail).map_or(m, |t| m.max(t)))
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+#[rustfmt::skip]
+simd_kernels!(
+    avx2_f32, "avx2", f32, __m256, 8,
+    _mm256_loadu_ps, _mm256_storeu_ps, _mm256_set1_ps, _mm256_setzero_ps,
+    _mm256_add_ps, _mm256_mul_ps, _mm256_min_ps, _mm256_max_ps
+);
+#[cfg(target_arch = "x86_64")]
+#[rustfmt::skip]
+simd_kernels!(
+    avx2_f64, "avx2", f64, __m256d, 4,
+    _mm256_loadu_pd, _mm256_storeu_pd, _mm256_set1_pd, _mm256_setzero_pd,
+    _mm256_add_pd, _mm256_mul_pd, _mm256_min_pd, _mm256_max_pd
+);
+#[cfg(target_arch = "x86_64")]
+#[rustfmt::skip]
+simd_kernels!(
+    sse41_f32, "sse4.1", f32, __m128, 4,
+    _mm_loadu_ps, _mm_storeu_ps, _mm_set1_ps, _mm_setzero_ps,
+    _mm_add_ps, _mm_mul_ps, _mm_min_ps, _mm_max_ps
+);
+#[cfg(target_arch = "x86_64")]
+#[rustfmt::skip]
+simd_kernels!(
+    sse41_f64, "sse4.1", f64, __m128d, 2,
+    _mm_loadu_pd, _mm_storeu_pd, _mm_set1_pd, _mm_setzero_pd,
+    _mm_add_pd, _mm_mul_pd, _mm_min_pd, _mm_max_pd
+);
+
+// --- Element trait: routes a kernel call for f32/f64 to the right module ---
+// Methods are unsafe because calling an AVX2 kernel on a CPU without AVX2 is UB.
+// Safety contract: `isa.is_supported()` must be true (Kernels guarantees this).
+
+trait Element: Copy + Default + PartialOrd + Add<Output = Self> + Mul<Output = Self> {
+    unsafe fn sum(isa: Isa, x: &[Self]) -> Self;
+    unsafe fn dot(isa: Isa, a: &[Self], b: &[Self]) -> Self;
+    unsafe fn axpy(isa: Isa, alpha: Self, x: &[Self], y: &mut [Self]);
+    unsafe fn min(isa: Isa, x: &[Self]) -> Option<Self>;
+    unsafe fn max(isa: Isa, x: &[Self]) -> Option<Self>;
+}
+
+macro_rules! impl_element {
+    ($t:ty, $avx2:ident, $sse41:ident) => {
+        impl Element for $t {
+            unsafe fn sum(isa: Isa, x: &[$t]) -> $t {
+                match isa {
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Avx2 => $avx2::sum(x),
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Sse41 => $sse41::sum(x),
+                    _ => scalar::sum(x),
+                }
+            }
+
+            unsafe fn dot(isa: Isa, a: &[$t], b: &[$t]) -> $t {
+                match isa {
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Avx2 => $avx2::dot(a, b),
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Sse41 => $sse41::dot(a, b),
+                    _ => scalar::dot(a, b),
+                }
+            }
+
+            unsafe fn axpy(isa: Isa, alpha: $t, x: &[$t], y: &mut [$t]) {
+                match isa {
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Avx2 => $avx2::axpy(alpha, x, y),
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Sse41 => $sse41::axpy(alpha, x, y),
+                    _ => scalar::axpy(alpha, x, y),
+                }
+            }
+
+            unsafe fn min(isa: Isa, x: &[$t]) -> Option<$t> {
+                match isa {
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Avx2 => $avx2::min(x),
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Sse41 => $sse41::min(x),
+                    _ => scalar::min(x),
+                }
+            }
+
+            unsafe fn max(isa: Isa, x: &[$t]) -> Option<$t> {
+                match isa {
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Avx2 => $avx2::max(x),
+                    #[cfg(target_arch = "x86_64")]
+                    Isa::Sse41 => $sse41::max(x),
+                    _ => scalar::max(x),
+                }
+            }
+        }
+    };
+}
+
+impl_element!(f32, avx2_f32, sse41_f32);
+impl_element!(f64, avx2_f64, sse41_f64);
+
+// --- Public entry point ---
+// A Kernels value can only be built for an instruction set the CPU supports,
+// which is what makes the safe methods below sound.
+
+#[derive(Debug, Clone, Copy)]
+struct Kernels {
+    isa: Isa,
+}
+
+impl Kernels {
+    fn detect() -> Kernels {
+        Kernels { isa: Isa::best() }
+    }
+
+    // Force a specific tier (for tests and benchmarks); None if the CPU lacks it
+    fn with_isa(isa: Isa) -> Option<Kernels> {
+        isa.is_supported().then_some(Kernels { isa })
+    }
+
+    fn sum<T: Element>(&self, x: &[T]) -> T {
+        // SAFETY: self.isa was checked by detect/with_isa
+        unsafe { T::sum(self.isa, x) }
+    }
+
+    fn dot<T: Element>(&self, a: &[T], b: &[T]) -> T {
+        assert_eq!(a.len(), b.len(), "dot: length mismatch");
+        // SAFETY: self.isa was checked by detect/with_isa
+        unsafe { T::dot(self.isa, a, b) }
+    }
+
+    // y[i] = alpha * x[i] + y[i]
+    fn axpy<T: Element>(&self, alpha: T, x: &[T], y: &mut [T]) {
+        assert_eq!(x.len(), y.len(), "axpy: length mismatch");
+        // SAFETY: self.isa was checked by detect/with_isa
+        unsafe { T::axpy(self.isa, alpha, x, y) }
+    }
+
+    fn min<T: Element>(&self, x: &[T]) -> Option<T> {
+        // SAFETY: self.isa was checked by detect/with_isa
+        unsafe { T::min(self.isa, x) }
+    }
+
+    fn max<T: Element>(&self, x: &[T]) -> Option<T> {
+        // SAFETY: self.isa was checked by detect/with_isa
+        unsafe { T::max(self.isa, x) }
+    }
+
+    // Index of the first maximum: vectorized max, then a scan for its position
+    fn argmax<T: Element>(&self, x: &[T]) -> Option<usize> {
+        let m = self.max(x)?;
+        x.iter().position(|&v| v == m)
+    }
+}
+
+fn bench(label: &str, k: Kernels, data: &[f32]) {
+    let start = Instant::now();
+    let mut total = 0.0;
+    for _ in 0..100 {
+        total += k.sum(black_box(data));
+    }
+    black_box(total);
+    println!("{:<8} {:?}", label, start.elapsed() / 100);
+}
+
+fn main() {
+    let k = Kernels::detect();
+    println!("--- Dispatch ---");
+    println!("best isa: {:?}", k.isa); // best isa: Avx2 (on most x86_64 machines)
+
+    // 13 elements: one or more full vectors plus an unaligned tail
+    println!("\n--- Kernels ---");
+    let x: Vec<f32> = (1..=13).map(|i| i as f32).collect();
+    let mut y = vec![1.0f32; 13];
+    println!("{}", k.sum(&x)); // 91
+    println!("{}", k.dot(&x, &x)); // 819
+    k.axpy(2.0, &x, &mut y);
+    println!("{:?}", &y[..4]); // [3.0, 5.0, 7.0, 9.0]
+    println!("{:?} {:?}", k.min(&x), k.max(&x)); // Some(1.0) Some(13.0)
+
+    let readings = [0.5f64, 3.25, -1.0, 7.5, 2.0, 7.5];
+    println!("{:?}", k.argmax(&readings)); // Some(3) — first of the two maxima
+    println!("{:?}", k.argmax::<f64>(&[])); // None
+
+    // Starting at offset 1 makes every load unaligned — still fine with loadu
+    println!("{}", k.sum(&x[1..])); // 90
+
+    // Only meaningful in an optimized build (rustc -O / --release)
+    println!("\n--- Benchmark: sum of 1M f32 ---");
+    let data: Vec<f32> = (0..1_000_000).map(|i| (i % 100) as f32).collect();
+    for isa in [Isa::Scalar, Isa::Sse41, Isa::Avx2] {
+        match Kernels::with_isa(isa) {
+            Some(k) => bench(&format!("{:?}", isa), k, &data),
+            None => println!("{:<8} not supported", format!("{:?}", isa)),
+        }
+    }
+
+    println!("simd kernels done"); // simd kernels done
+}
+
+// ============================================================
+// TESTS — every supported tier must agree with the scalar path
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiers() -> Vec<Kernels> {
+        [Isa::Sse41, Isa::Avx2]
+            .into_iter()
+            .filter_map(Kernels::with_isa)
+            .collect()
+    }
+
+    fn scalar() -> Kernels {
+        Kernels::with_isa(Isa::Scalar).unwrap()
+    }
+
+    // Deterministic pseudo-random values in [-50, 50)
+    fn data(len: usize, seed: u64) -> Vec<f64> {
+        let mut s = seed;
+        (0..len)
+            .map(|_| {
+                s = s
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (s >> 11) as f64 / (1u64 << 53) as f64 * 100.0 - 50.0
+            })
+            .collect()
+    }
+
+    // Reordered sums differ by rounding error proportional to the magnitudes involved
+    fn close(a: f64, b: f64, scale: f64, eps: f64) -> bool {
+        (a - b).abs() <= eps * scale.max(1.0)
+    }
+
+    #[test]
+    fn scalar_is_always_available() {
+        assert!(Kernels::with_isa(Isa::Scalar).is_some());
+        assert!(Kernels::detect().isa.is_supported());
+    }
+
+    #[test]
+    fn sum_and_dot_match_scalar_for_all_lengths_and_offsets() {
+        let s = scalar();
+        for k in tiers() {
+            for len in 0..70 {
+                for offset in 0..3 {
+                    let a64 = data(len + offset, len as u64);
+                    let b64 = data(len + offset, len as u64 + 1000);
+                    let (a64, b64) = (&a64[offset..], &b64[offset..]);
+                    let a32: Vec<f32> = a64.iter().map(|&v| v as f32).collect();
+                    let b32: Vec<f32> = b64.iter().map(|&v| v as f32).collect();
+
+                    let mag: f64 = a64.iter().map(|v| v.abs()).sum();
+                    assert!(
+                        close(k.sum(a64), s.sum(a64), mag, 1e-12),
+                        "{:?} len {}",
+                        k,
+                        len
+                    );
+                    assert!(close(k.sum(&a32) as f64, s.sum(&a32) as f64, mag, 1e-5));
+
+                    let dmag: f64 = a64.iter().zip(b64).map(|(x, y)| (x * y).abs()).sum();
+                    assert!(close(k.dot(a64, b64), s.dot(a64, b64), dmag, 1e-12));
+                    assert!(close(
+                        k.dot(&a32, &b32) as f64,
+                        s.dot(&a32, &b32) as f64,
+                        dmag,
+                        1e-5
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn axpy_matches_scalar_exactly() {
+        // Element-wise: no reordering, so results are bit-identical
+        let s = scalar();
+        for k in tiers() {
+            for len in 0..40 {
+                let x = data(len, 7);
+                let mut y1 = data(len, 8);
+                let mut y2 = y1.clone();
+                k.axpy(1.5, &x, &mut y1);
+                s.axpy(1.5, &x, &mut y2);
+                assert_eq!(y1, y2);
+
+                let x32: Vec<f32> = x.iter().map(|&v| v as f32).collect();
+                let mut z1 = vec![0.25f32; len];
+                let mut z2 = z1.clone();
+                k.axpy(-3.0, &x32, &mut z1);
+                s.axpy(-3.0, &x32, &mut z2);
+                assert_eq!(z1, z2);
+            }
+        }
+    }
+
+    #[test]
+    fn min_max_argmax_match_scalar() {
+        let s = scalar();
+        for k in tiers() {
+            for len in 0..70 {
+                let x = data(len, 99 + len as u64);
+                let x32: Vec<f32> = x.iter().map(|&v| v as f32).collect();
+                assert_eq!(k.min(&x), s.min(&x));
+                assert_eq!(k.max(&x), s.max(&x));
+                assert_eq!(k.argmax(&x), s.argmax(&x));
+                assert_eq!(k.min(&x32), s.min(&x32));
+                assert_eq!(k.max(&x32), s.max(&x32));
+                assert_eq!(k.argmax(&x32), s.argmax(&x32));
+            }
+        }
+    }
+
+    #[test]
+    fn max_in_tail_is_found() {
+        for k in tiers().into_iter().chain([scalar()]) {
+            let mut x = vec![0.0f32; 19];
+            x[18] = 5.0; // lands in the remainder for every lane count
+            assert_eq!(k.argmax(&x), Some(18));
+            x[2] = -5.0;
+            assert_eq!(k.min(&x), Some(-5.0));
+        }
+    }
+
+    #[test]
+    fn argmax_returns_first_of_ties() {
+        let k = Kernels::detect();
+        assert_eq!(k.argmax(&[1.0f32, 9.0, 3.0, 9.0, 9.0]), Some(1));
+        assert_eq!(k.argmax::<f32>(&[]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "length mismatch")]
+    fn dot_rejects_mismatched_lengths() {
+        Kernels::detect().dot(&[1.0f32, 2.0], &[1.0]);
+    }
+}
@@ -0,0 +1,429 @@
+// Generating Struct-of-Arrays containers with a macro
+//
+// memory_layout.rs writes ParticlesSOA by hand next to ParticleAOS. That gets
+// tedious fast: every new field means touching the struct, the constructor,
+// push, pop, and every loop that keeps the columns in sync.
+//
+// soa! takes a plain struct and generates, next to it:
+//
+//   ParticlesSoa          — one Vec per field: push/pop/get/len/iter/sort_by_key
+//   ParticleRef<'a>       — a "view" of one element: a reference per field
+//   ParticleSlices<'a>    — all columns as shared slices
+//   ParticleSlicesMut<'a> — all columns as disjoint &mut slices at once
+//
+// Because each column is a separate Vec, the borrow checker can hand out
+// `&mut x` and `&mut y` simultaneously — so different threads can update
+// different fields with no locking (see slices_mut + thread::scope below).
+//
+// macro_rules! can't glue identifiers together (ParticleRef from Particle),
+// so the generated type names are passed in after `=>`.
+// The `soa_derive` crate is the proc-macro version of this idea.
+
+use std::mem;
+use std::time::Instant;
+
+// Reorder a column so that new[i] = old[order[i]] — moves values, no Clone needed
+fn permute<T>(column: &mut Vec<T>, order: &[usize]) {
+    let mut old: Vec<Option<T>> = mem::take(column).into_iter().map(Some).collect();
+    *column = order.iter().map(|&i| old[i].take().unwrap()).collect();
+}
+
+macro_rules! soa {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($fvis:vis $field:ident : $ty:ty),+ $(,)?
+        }
+        => $soa:ident, $item_ref:ident, $slices:ident, $slices_mut:ident;
+    ) => {
+        // The original struct, unchanged
+        $(#[$meta])*
+        $vis struct $name {
+            $($fvis $field: $ty),+
+        }
+
+        // No derives that need anything from the field types: Debug, Clone,
+        // PartialEq... would stop the macro working for a field without them.
+        // Clone + Copy on the views are fine — they only copy references.
+        $vis struct $soa {
+            $($field: Vec<$ty>),+
+        }
+
+        #[derive(Clone, Copy)]
+        $vis struct $item_ref<'a> {
+            $($fvis $field: &'a $ty),+
+        }
+
+        // Generated for every field; a program rarely reads all of them
+        #[allow(dead_code)]
+        #[derive(Clone, Copy)]
+        $vis struct $slices<'a> {
+            $($fvis $field: &'a [$ty]),+
+        }
+
+        #[allow(dead_code)]
+        $vis struct $slices_mut<'a> {
+            $($fvis $field: &'a mut [$ty]),+
+        }
+
+        impl $item_ref<'_> {
+            #[allow(dead_code)]
+            // `for<'x>` keeps the bound from being checked up front: a plain
+            // `String: Clone` on a non-generic impl is an error when it doesn't
+            // hold, this way only calling cloned() on such a struct is
+            fn cloned(self) -> $name
+            where
+                $(for<'x> $ty: Clone),+
+            {
+                $name {
+                    $($field: self.$field.clone()),+
+                }
+            }
+        }
+
+        impl Default for $soa {
+            fn default() -> Self {
+                $soa {
+                    $($field: Vec::new()),+
+                }
+            }
+        }
+
+        #[allow(dead_code)]
+        impl $soa {
+            fn new() -> Self {
+                Self::default()
+            }
+
+            fn with_capacity(capacity: usize) -> Self {
+                $soa {
+                    $($field: Vec::with_capacity(capacity)),+
+                }
+            }
+
+            // All columns always have the same length; the first one is as good as any
+            fn len(&self) -> usize {
+                [$(self.$field.len()),+][0]
+            }
+
+            fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            // Split the struct into its fields and push each onto its column
+            fn push(&mut self, item: $name) {
+                let $name { $($field),+ } = item;
+                $(self.$field.push($field);)+
+            }
+
+            fn pop(&mut self) -> Option<$name> {
+                if self.is_empty() {
+                    return None;
+                }
+                Some($name {
+                    $($field: self.$field.pop().unwrap()),+
+                })
+            }
+
+            fn swap_remove(&mut self, index: usize) -> $name {
+                $name {
+                    $($field: self.$field.swap_remove(index)),+
+                }
+            }
+
+            fn get(&self, index: usize) -> Option<$item_ref<'_>> {
+                if index >= self.len() {
+                    return None;
+                }
+                Some($item_ref {
+                    $($field: &self.$field[index]),+
+                })
+            }
+
+            fn iter(&self) -> impl Iterator<Item = $item_ref<'_>> + '_ {
+                (0..self.len()).map(move |i| $item_ref {
+                    $($field: &self.$field[i]),+
+                })
+            }
+
+            fn slices(&self) -> $slices<'_> {
+                $slices {
+                    $($field: &self.$field),+
+                }
+            }
+
+            // Disjoint mutable borrows of every column — each is Send on its own
+            fn slices_mut(&mut self) -> $slices_mut<'_> {
+                $slices_mut {
+                    $($field: &mut self.$field),+
+                }
+            }
+
+            // Stable sort of all columns together by a key computed from each element
+            fn sort_by_key<K: Ord>(&mut self, mut key: impl FnMut($item_ref<'_>) -> K) {
+                let mut order: Vec<usize> = (0..self.len()).collect();
+                order.sort_by_cached_key(|&i| key(self.get(i).unwrap()));
+                $(permute(&mut self.$field, &order);)+
+            }
+        }
+
+        impl FromIterator<$name> for $soa {
+            fn from_iter<I: IntoIterator<Item = $name>>(iter: I) -> Self {
+                let mut soa = $soa::new();
+                for item in iter {
+                    soa.push(item);
+                }
+                soa
+            }
+        }
+    };
+}
+
+// --- Same particle as memory_layout.rs, SoA boilerplate generated ---
+
+soa! {
+    #[derive(Debug, Clone, PartialEq)]
+    struct Particle {
+        x: f32,
+        y: f32,
+        z: f32,
+        vx: f32,
+        vy: f32,
+        vz: f32,
+        mass: f32,
+    }
+    => ParticlesSoa, ParticleRef, ParticleSlices, ParticleSlicesMut;
+}
+
+// Non-Copy fields work too
+soa! {
+    #[derive(Debug, Clone, PartialEq)]
+    struct Employee {
+        name: String,
+        age: u32,
+    }
+    => EmployeesSoa, EmployeeRef, EmployeeSlices, EmployeeSlicesMut;
+}
+
+// Field types don't need any traits at all
+#[allow(dead_code)]
+struct Handle(u32); // no Debug, no Clone, no PartialEq
+
+soa! {
+    struct Resource {
+        handle: Handle,
+        refs: u32,
+    }
+    => ResourcesSoa, ResourceRef, ResourceSlices, ResourceSlicesMut;
+}
+
+fn particle(x: f32, vx: f32) -> Particle {
+    Particle {
+        x,
+        y: 0.0,
+        z: 0.0,
+        vx,
+        vy: vx / 2.0,
+        vz: 0.0,
+        mass: 1.0,
+    }
+}
+
+// The hot loop from memory_layout.rs — now only two columns are ever touched
+fn update_x_soa(p: &mut ParticlesSoa, dt: f32) {
+    let s = p.slices_mut();
+    for (x, vx) in s.x.iter_mut().zip(s.vx.iter()) {
+        *x += vx * dt;
+    }
+}
+
+fn update_x_aos(particles: &mut [Particle], dt: f32) {
+    for p in particles {
+        p.x += p.vx * dt;
+    }
+}
+
+// Integrate x and y on two threads at once: the column borrows are disjoint
+fn update_xy_parallel(p: &mut ParticlesSoa, dt: f32) {
+    let s = p.slices_mut();
+    let (x, vx, y, vy) = (s.x, &*s.vx, s.y, &*s.vy);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            for (x, vx) in x.iter_mut().zip(vx) {
+                *x += vx * dt;
+            }
+        });
+        scope.spawn(|| {
+            for (y, vy) in y.iter_mut().zip(vy) {
+                *y += vy * dt;
+            }
+        });
+    });
+}
+
+fn main() {
+    println!("--- Generated SoA ---");
+    let mut particles: ParticlesSoa = (0..3).map(|i| particle(i as f32, i as f32 + 1.0)).collect();
+    println!("{}", particles.len()); // 3
+    update_x_soa(&mut particles, 0.1);
+    println!("{:?}", particles.slices().x); // [0.1, 1.2, 2.3]
+
+    // Element view: one reference per field
+    let p1 = particles.get(1).unwrap();
+    println!("x={} vx={}", p1.x, p1.vx); // x=1.2 vx=2
+
+    // Round trip through push/pop
+    particles.push(particle(9.0, -1.0));
+    println!("{:?}", particles.pop().map(|p| p.x)); // Some(9.0)
+
+    // Sorting keeps all columns in step
+    particles.sort_by_key(|p| std::cmp::Reverse((*p.x * 10.0) as i32));
+    let xs: Vec<f32> = particles.iter().map(|p| *p.x).collect();
+    let vxs: Vec<f32> = particles.iter().map(|p| *p.vx).collect();
+    println!("{:?} {:?}", xs, vxs); // [2.3, 1.2, 0.1] [3.0, 2.0, 1.0]
+
+    // Two threads, two columns, no locks
+    update_xy_parallel(&mut particles, 1.0);
+    println!("{:?}", particles.slices().y); // [1.5, 1.0, 0.5]
+
+    println!("\n--- Non-Copy fields ---");
+    let mut staff = EmployeesSoa::with_capacity(4);
+    staff.push(Employee {
+        name: "Carol".into(),
+        age: 41,
+    });
+    staff.push(Employee {
+        name: "Alice".into(),
+        age: 30,
+    });
+    staff.sort_by_key(|e| e.name.clone());
+    println!("{:?}", staff.slices().name); // ["Alice", "Carol"]
+    let avg = staff.slices().age.iter().sum::<u32>() / staff.len() as u32;
+    println!("avg age {}", avg); // avg age 35
+    println!("{:?}", staff.swap_remove(0)); // Employee { name: "Alice", age: 30 }
+    println!("{:?}", staff.get(0).map(|e| e.cloned())); // Some(Employee { name: "Carol", age: 41 })
+
+    // AoS vs SoA on a big batch (only meaningful in an optimized build)
+    println!("\n--- AoS vs SoA ---");
+    let n = 1_000_000;
+    let mut aos: Vec<Particle> = (0..n).map(|i| particle(i as f32, 1.0)).collect();
+    let mut soa: ParticlesSoa = aos.iter().cloned().collect();
+
+    let start = Instant::now();
+    update_x_aos(&mut aos, 0.1);
+    println!("aos: {:?}", start.elapsed());
+    let start = Instant::now();
+    update_x_soa(&mut soa, 0.1);
+    println!("soa: {:?}", start.elapsed());
+    println!("{}", aos[42].x == soa.slices().x[42]); // true
+
+    println!("soa done"); // soa done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn employee(name: &str, age: u32) -> Employee {
+        Employee {
+            name: name.to_string(),
+            age,
+        }
+    }
+
+    #[test]
+    fn push_pop_round_trip() {
+        let mut soa = EmployeesSoa::new();
+        assert!(soa.is_empty());
+        assert_eq!(soa.pop(), None);
+        soa.push(employee("a", 1));
+        soa.push(employee("b", 2));
+        assert_eq!(soa.len(), 2);
+        assert_eq!(soa.pop(), Some(employee("b", 2)));
+        assert_eq!(soa.pop(), Some(employee("a", 1)));
+        assert!(soa.is_empty());
+    }
+
+    #[test]
+    fn columns_hold_each_field() {
+        let soa: EmployeesSoa = [employee("x", 10), employee("y", 20)].into_iter().collect();
+        let s = soa.slices();
+        assert_eq!(s.name, &["x".to_string(), "y".to_string()]);
+        assert_eq!(s.age, &[10, 20]);
+    }
+
+    #[test]
+    fn iter_yields_references_in_order() {
+        let soa: EmployeesSoa = [employee("x", 10), employee("y", 20)].into_iter().collect();
+        let owned: Vec<Employee> = soa.iter().map(|e| e.cloned()).collect();
+        assert_eq!(owned, vec![employee("x", 10), employee("y", 20)]);
+        assert!(soa.get(2).is_none());
+    }
+
+    #[test]
+    fn sort_by_key_moves_all_columns_together() {
+        let mut soa: EmployeesSoa = [employee("c", 3), employee("a", 1), employee("b", 2)]
+            .into_iter()
+            .collect();
+        soa.sort_by_key(|e| e.name.clone());
+        assert_eq!(soa.slices().age, &[1, 2, 3]);
+
+        soa.sort_by_key(|e| std::cmp::Reverse(*e.age));
+        let names: Vec<&str> = soa.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        let mut soa: EmployeesSoa = [employee("b", 1), employee("a", 1), employee("c", 0)]
+            .into_iter()
+            .collect();
+        soa.sort_by_key(|e| *e.age);
+        assert_eq!(soa.slices().name, &["c", "b", "a"]);
+    }
+
+    #[test]
+    fn fields_without_common_traits() {
+        let mut soa = ResourcesSoa::new();
+        soa.push(Resource {
+            handle: Handle(7),
+            refs: 1,
+        });
+        soa.push(Resource {
+            handle: Handle(9),
+            refs: 2,
+        });
+        soa.sort_by_key(|r| std::cmp::Reverse(*r.refs));
+        let view = soa.get(0).unwrap();
+        let copy = view; // views are Copy whatever the fields are
+        assert_eq!((view.handle.0, copy.handle.0), (9, 9));
+        let last = soa.pop().unwrap();
+        assert_eq!((last.handle.0, last.refs), (7, 1));
+    }
+
+    #[test]
+    fn slices_mut_allows_parallel_field_updates() {
+        let mut particles: ParticlesSoa = (0..1000).map(|i| particle(0.0, i as f32)).collect();
+        update_xy_parallel(&mut particles, 2.0);
+        for (i, p) in particles.iter().enumerate() {
+            assert_eq!(*p.x, i as f32 * 2.0);
+            assert_eq!(*p.y, i as f32);
+        }
+    }
+
+    #[test]
+    fn soa_and_aos_updates_agree() {
+        let mut aos: Vec<Particle> = (0..50)
+            .map(|i| particle(i as f32, 0.5 * i as f32))
+            .collect();
+        let mut soa: ParticlesSoa = aos.iter().cloned().collect();
+        update_x_aos(&mut aos, 0.25);
+        update_x_soa(&mut soa, 0.25);
+        let back: Vec<Particle> = soa.iter().map(|p| p.cloned()).collect();
+        assert_eq!(aos, back);
+    }
+}
@@ -0,0 +1,641 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [features]
+//! default = ["profiling"]
+//! profiling = []
+//! ```
+
+// Hierarchical span profiler
+//
+// profiling_tools.rs has `time_it`, which prints one flat "label: duration" line.
+// That stops being useful as soon as functions call each other: you want to know
+// *where inside* `parse` the time went, and how often each piece ran.
+//
+//   let _g = span!("parse");   // timing starts here...
+//   ...                        // ...nested span!s become children...
+//                              // ...and stops when _g is dropped
+//
+// Each thread keeps its own call tree behind its own Mutex. Enter and exit
+// still lock it, but only a reporter ever competes for that lock, so the hot
+// path pays for an uncontended lock rather than a shared one.
+// Per node we aggregate: call count, total time, and self time
+// (total minus time spent in child spans). The tree only grows with the
+// number of distinct call paths, not with the number of calls.
+//
+// Exports:
+//   report()        — indented tree, one per thread
+//   folded()        — "thread;parse;lex 1234" lines for flamegraph.pl / inferno
+//   chrome_trace()  — JSON for chrome://tracing or https://ui.perfetto.dev
+//
+// chrome_trace() needs every individual span, which grows with the number of
+// calls — so it is opt-in: enable_trace(n) keeps the last n spans per thread
+// in a ring. Threads that have exited are kept for the report until reset()
+// (or until more than MAX_EXITED of them pile up).
+//
+// Build without the feature to compile everything out:
+//   cargo build --no-default-features
+// span! then expands to a zero-sized guard with an empty Drop — nothing is timed,
+// nothing is recorded, and the optimizer removes it entirely.
+
+use std::hint::black_box;
+
+#[cfg(feature = "profiling")]
+mod profiler {
+    use std::cell::OnceCell;
+    use std::collections::VecDeque;
+    use std::fmt::Write;
+    use std::marker::PhantomData;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::time::{Duration, Instant};
+
+    // One node of a thread's call tree. Node 0 is an unnamed root.
+    #[derive(Debug, Clone)]
+    pub struct Node {
+        pub name: &'static str,
+        pub calls: u64,
+        pub total: Duration,
+        pub children: Vec<usize>,
+    }
+
+    // A finished span, kept for the Chrome trace timeline
+    #[derive(Debug, Clone)]
+    pub struct Event {
+        pub name: &'static str,
+        pub start: Duration, // since the profiler epoch
+        pub duration: Duration,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ThreadProfile {
+        pub thread_name: String,
+        pub tid: u64,
+        pub nodes: Vec<Node>,
+        pub events: VecDeque<Event>, // the last trace_capacity spans
+        pub dropped_events: u64,     // pushed out of the ring
+        pub exited: bool,
+        stack: Vec<(usize, Instant)>,
+    }
+
+    impl ThreadProfile {
+        pub fn self_time(&self, idx: usize) -> Duration {
+            let node = &self.nodes[idx];
+            let children: Duration = node.children.iter().map(|&c| self.nodes[c].total).sum();
+            node.total.saturating_sub(children)
+        }
+
+        // Walk from the root by span names, e.g. ["job", "parse"]
+        #[allow(dead_code)]
+        pub fn find(&self, path: &[&str]) -> Option<&Node> {
+            let mut idx = 0;
+            for name in path {
+                idx = *self.nodes[idx]
+                    .children
+                    .iter()
+                    .find(|&&c| self.nodes[c].name == *name)?;
+            }
+            Some(&self.nodes[idx])
+        }
+    }
+
+    // Threads that opened a span; entries outlive their threads so work done
+    // on joined threads still shows up in the report
+    static THREADS: Mutex<Vec<Arc<Mutex<ThreadProfile>>>> = Mutex::new(Vec::new());
+    static NEXT_TID: AtomicU64 = AtomicU64::new(1);
+    static TRACE_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+    // Exited threads kept around when nobody calls reset()
+    const MAX_EXITED: usize = 64;
+
+    // Keep the last `capacity` spans of each thread for chrome_trace(); 0 turns it off
+    pub fn enable_trace(capacity: usize) {
+        TRACE_CAPACITY.store(capacity, Ordering::Relaxed);
+    }
+
+    fn epoch() -> Instant {
+        static EPOCH: OnceLock<Instant> = OnceLock::new();
+        *EPOCH.get_or_init(Instant::now)
+    }
+
+    // Marks the profile as exited when the thread's TLS is torn down
+    struct Local(Arc<Mutex<ThreadProfile>>);
+
+    impl Drop for Local {
+        fn drop(&mut self) {
+            if let Ok(mut p) = self.0.lock() {
+                p.exited = true;
+            }
+        }
+    }
+
+    thread_local! {
+        static LOCAL: OnceCell<Local> = const { OnceCell::new() };
+    }
+
+    // Drop exited threads, oldest first, until at most `keep` remain
+    fn prune(threads: &mut Vec<Arc<Mutex<ThreadProfile>>>, keep: usize) {
+        let exited = threads.iter().filter(|t| t.lock().unwrap().exited).count();
+        let mut excess = exited.saturating_sub(keep);
+        threads.retain(|t| {
+            let remove = excess > 0 && t.lock().unwrap().exited;
+            excess -= remove as usize;
+            !remove
+        });
+    }
+
+    // Forget threads that have exited (their spans are gone from every export)
+    pub fn prune_exited() {
+        prune(&mut THREADS.lock().unwrap(), 0);
+    }
+
+    fn with_local<R>(f: impl FnOnce(&mut ThreadProfile) -> R) -> R {
+        LOCAL.with(|cell| {
+            let profile = cell.get_or_init(|| {
+                let current = std::thread::current();
+                let tid = NEXT_TID.fetch_add(1, Ordering::Relaxed);
+                let profile = Arc::new(Mutex::new(ThreadProfile {
+                    thread_name: current
+                        .name()
+                        .map_or_else(|| format!("thread-{}", tid), String::from),
+                    tid,
+                    nodes: vec![Node {
+                        name: "",
+                        calls: 0,
+                        total: Duration::ZERO,
+                        children: Vec::new(),
+                    }],
+                    events: VecDeque::new(),
+                    dropped_events: 0,
+                    exited: false,
+                    stack: Vec::new(),
+                }));
+                let mut threads = THREADS.lock().unwrap();
+                prune(&mut threads, MAX_EXITED);
+                threads.push(Arc::clone(&profile));
+                Local(profile)
+            });
+            // Only this thread and a reporter ever lock it: practically uncontended
+            f(&mut profile.0.lock().unwrap())
+        })
+    }
+
+    // Returned by span!; closes the span when dropped.
+    // !Send: a span must end on the thread that started it.
+    pub struct SpanGuard {
+        _not_send: PhantomData<*const ()>,
+    }
+
+    pub fn enter(name: &'static str) -> SpanGuard {
+        epoch();
+        with_local(|p| {
+            let parent = p.stack.last().map_or(0, |&(idx, _)| idx);
+            let existing = p.nodes[parent]
+                .children
+                .iter()
+                .copied()
+                .find(|&c| p.nodes[c].name == name);
+            let idx = existing.unwrap_or_else(|| {
+                p.nodes.push(Node {
+                    name,
+                    calls: 0,
+                    total: Duration::ZERO,
+                    children: Vec::new(),
+                });
+                let idx = p.nodes.len() - 1;
+                p.nodes[parent].children.push(idx);
+                idx
+            });
+            // Read the clock last so bookkeeping isn't counted in the span
+            p.stack.push((idx, Instant::now()));
+        });
+        SpanGuard {
+            _not_send: PhantomData,
+        }
+    }
+
+    impl Drop for SpanGuard {
+        fn drop(&mut self) {
+            let end = Instant::now();
+            with_local(|p| {
+                let (idx, start) = p.stack.pop().expect("span stack underflow");
+                let duration = end - start;
+                let node = &mut p.nodes[idx];
+                node.calls += 1;
+                node.total += duration;
+                let name = node.name;
+                let capacity = TRACE_CAPACITY.load(Ordering::Relaxed);
+                if capacity == 0 {
+                    return;
+                }
+                while p.events.len() >= capacity {
+                    p.events.pop_front();
+                    p.dropped_events += 1;
+                }
+                p.events.push_back(Event {
+                    name,
+                    start: start - epoch(),
+                    duration,
+                });
+            });
+        }
+    }
+
+    // Copy of every thread's data at this moment
+    pub fn snapshot() -> Vec<ThreadProfile> {
+        let threads = THREADS.lock().unwrap();
+        threads.iter().map(|t| t.lock().unwrap().clone()).collect()
+    }
+
+    pub fn reset() {
+        prune_exited();
+        for t in THREADS.lock().unwrap().iter() {
+            let mut p = t.lock().unwrap();
+            // Keep nodes of still-open spans valid: only clear stats and events
+            for node in &mut p.nodes {
+                node.calls = 0;
+                node.total = Duration::ZERO;
+            }
+            p.events.clear();
+            p.dropped_events = 0;
+        }
+    }
+
+    fn fmt_dur(d: Duration) -> String {
+        format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+    }
+
+    pub fn report_of(threads: &[ThreadProfile]) -> String {
+        fn walk(p: &ThreadProfile, idx: usize, depth: usize, out: &mut String) {
+            let node = &p.nodes[idx];
+            if node.calls > 0 {
+                let label = format!("{}{}", "  ".repeat(depth), node.name);
+                let _ = writeln!(
+                    out,
+                    "{:<24} calls {:>6}  total {:>10}  self {:>10}",
+                    label,
+                    node.calls,
+                    fmt_dur(node.total),
+                    fmt_dur(p.self_time(idx))
+                );
+            }
+            for &c in &node.children {
+                walk(p, c, depth + 1, out);
+            }
+        }
+
+        let mut out = String::new();
+        for p in threads.iter().filter(|p| p.nodes.len() > 1) {
+            let _ = writeln!(out, "thread {}", p.thread_name);
+            for &c in &p.nodes[0].children {
+                walk(p, c, 1, &mut out);
+            }
+        }
+        out
+    }
+
+    // Brendan Gregg's folded format: one line per stack, weight = self time in µs
+    pub fn folded_of(threads: &[ThreadProfile]) -> String {
+        fn walk(p: &ThreadProfile, idx: usize, path: &mut Vec<&'static str>, out: &mut String) {
+            path.push(p.nodes[idx].name);
+            let micros = p.self_time(idx).as_micros();
+            if p.nodes[idx].calls > 0 && micros > 0 {
+                let _ = writeln!(out, "{};{} {}", p.thread_name, path.join(";"), micros);
+            }
+            for &c in &p.nodes[idx].children {
+                walk(p, c, path, out);
+            }
+            path.pop();
+        }
+
+        let mut out = String::new();
+        for p in threads {
+            for &c in &p.nodes[0].children {
+                walk(p, c, &mut Vec::new(), &mut out);
+            }
+        }
+        out
+    }
+
+    fn json_escape(s: &str) -> String {
+        s.chars()
+            .flat_map(|c| match c {
+                '"' => vec!['\\', '"'],
+                '\\' => vec!['\\', '\\'],
+                c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32).chars().collect(),
+                c => vec![c],
+            })
+            .collect()
+    }
+
+    // Trace Event Format "complete" events (ph: "X"), timestamps in µs
+    pub fn chrome_trace_of(threads: &[ThreadProfile]) -> String {
+        let mut events = Vec::new();
+        for p in threads {
+            events.push(format!(
+                r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{},"args":{{"name":"{}"}}}}"#,
+                p.tid,
+                json_escape(&p.thread_name)
+            ));
+            for e in &p.events {
+                events.push(format!(
+                    r#"{{"name":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":1,"tid":{}}}"#,
+                    json_escape(e.name),
+                    e.start.as_secs_f64() * 1e6,
+                    e.duration.as_secs_f64() * 1e6,
+                    p.tid
+                ));
+            }
+        }
+        format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+    }
+
+    pub fn report() -> String {
+        report_of(&snapshot())
+    }
+
+    pub fn folded() -> String {
+        folded_of(&snapshot())
+    }
+
+    pub fn chrome_trace() -> String {
+        chrome_trace_of(&snapshot())
+    }
+}
+
+// Same API, nothing behind it
+#[cfg(not(feature = "profiling"))]
+mod profiler {
+    pub struct SpanGuard;
+
+    // An empty Drop keeps `let _g = span!(..)` meaning the same thing in both builds
+    impl Drop for SpanGuard {
+        #[inline(always)]
+        fn drop(&mut self) {}
+    }
+
+    #[inline(always)]
+    pub fn enter(_name: &'static str) -> SpanGuard {
+        SpanGuard
+    }
+
+    pub fn enable_trace(_capacity: usize) {}
+
+    pub fn reset() {}
+
+    pub fn report() -> String {
+        String::from("(profiling feature disabled)\n")
+    }
+
+    pub fn folded() -> String {
+        String::new()
+    }
+
+    pub fn chrome_trace() -> String {
+        String::from("{\"traceEvents\":[]}\n")
+    }
+}
+
+macro_rules! span {
+    ($name:expr) => {
+        profiler::enter($name)
+    };
+}
+
+// --- A toy workload with nested phases ---
+
+fn tokenize(src: &str) -> Vec<&str> {
+    let _g = span!("tokenize");
+    src.split_whitespace().collect()
+}
+
+fn parse_number(tok: &str) -> Option<u64> {
+    let _g = span!("parse_number");
+    tok.parse().ok()
+}
+
+fn parse(src: &str) -> Vec<u64> {
+    let _g = span!("parse");
+    let tokens = tokenize(src);
+    tokens.iter().filter_map(|t| parse_number(t)).collect()
+}
+
+fn checksum(values: &[u64]) -> u64 {
+    let _g = span!("checksum");
+    values
+        .iter()
+        .fold(0u64, |acc, &v| acc.wrapping_mul(31).wrapping_add(v))
+}
+
+fn run_job(src: &str) -> u64 {
+    let _g = span!("job");
+    let values = parse(src);
+    checksum(&values)
+}
+
+fn main() {
+    let src: String = (0..2000).map(|i| format!("{} x ", i)).collect();
+
+    // Per-span events for the Chrome trace: the last 100k spans per thread
+    profiler::enable_trace(100_000);
+
+    // Warm-up run, then throw its numbers away
+    black_box(run_job(&src));
+    profiler::reset();
+
+    {
+        let _g = span!("main");
+        for _ in 0..3 {
+            black_box(run_job(black_box(&src)));
+        }
+
+        // Spans on other threads get their own tree
+        std::thread::scope(|s| {
+            for i in 0..2 {
+                let src = &src;
+                std::thread::Builder::new()
+                    .name(format!("worker-{}", i))
+                    .spawn_scoped(s, move || black_box(run_job(src)))
+                    .unwrap();
+            }
+        });
+    }
+
+    println!("--- Report ---");
+    print!("{}", profiler::report());
+    // thread main
+    //   main                   calls      1  total    ...ms  self   ...ms
+    //     job                  calls      3  ...
+    //       parse              calls      3  ...
+    //         tokenize         calls      3  ...
+    //         parse_number     calls  12000  ...
+    //       checksum           calls      3  ...
+    // thread worker-0
+    //   job                    calls      1  ...
+
+    println!("\n--- Folded stacks (first lines) ---");
+    for line in profiler::folded().lines().take(3) {
+        println!("{}", line); // main;main;job;parse;tokenize 123
+    }
+
+    let trace = profiler::chrome_trace();
+    println!("\nchrome trace: {} bytes", trace.len());
+    // To view: write `trace` to trace.json and open it in ui.perfetto.dev
+
+    println!("span profiler done"); // span profiler done
+}
+
+// ============================================================
+// TESTS — run with the default `profiling` feature
+// ============================================================
+
+#[cfg(all(test, feature = "profiling"))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Every test that traces uses the same capacity: the setting is global
+    const TRACE_CAPACITY: usize = 8;
+
+    // Test threads run in parallel; each test looks only at its own thread
+    fn this_thread() -> profiler::ThreadProfile {
+        let name = thread::current().name().unwrap().to_string();
+        profiler::snapshot()
+            .into_iter()
+            .find(|p| p.thread_name == name)
+            .expect("no spans recorded on this thread")
+    }
+
+    #[test]
+    fn nested_spans_build_a_tree() {
+        {
+            let _a = span!("outer");
+            for _ in 0..3 {
+                let _b = span!("inner");
+            }
+            let _c = span!("other");
+        }
+        let p = this_thread();
+        assert_eq!(p.find(&["outer"]).unwrap().calls, 1);
+        assert_eq!(p.find(&["outer", "inner"]).unwrap().calls, 3);
+        assert_eq!(p.find(&["outer", "other"]).unwrap().calls, 1);
+        assert!(p.find(&["inner"]).is_none()); // only under outer
+    }
+
+    #[test]
+    fn self_time_excludes_children() {
+        {
+            let _a = span!("parent");
+            let _b = span!("child");
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let p = this_thread();
+        let parent_idx = p.nodes.iter().position(|n| n.name == "parent").unwrap();
+        let parent = &p.nodes[parent_idx];
+        let child = p.find(&["parent", "child"]).unwrap();
+        assert!(child.total >= std::time::Duration::from_millis(5));
+        assert!(parent.total >= child.total);
+        assert_eq!(p.self_time(parent_idx), parent.total - child.total);
+    }
+
+    #[test]
+    fn same_name_under_different_parents_is_separate() {
+        {
+            let _a = span!("a");
+            let _x = span!("shared");
+        }
+        {
+            let _b = span!("b");
+            let _x = span!("shared");
+            let _y = span!("shared");
+        }
+        let p = this_thread();
+        assert_eq!(p.find(&["a", "shared"]).unwrap().calls, 1);
+        assert_eq!(p.find(&["b", "shared"]).unwrap().calls, 1);
+        assert_eq!(p.find(&["b", "shared", "shared"]).unwrap().calls, 1);
+    }
+
+    #[test]
+    fn exited_threads_are_kept_until_pruned() {
+        drop(span!("still_running"));
+        thread::Builder::new()
+            .name("profiled-helper".into())
+            .spawn(|| {
+                let _g = span!("helper_work");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let helper = profiler::snapshot()
+            .into_iter()
+            .find(|p| p.thread_name == "profiled-helper")
+            .unwrap();
+        assert_eq!(helper.find(&["helper_work"]).unwrap().calls, 1);
+        assert!(helper.exited);
+
+        profiler::prune_exited();
+        let names: Vec<String> = profiler::snapshot()
+            .into_iter()
+            .map(|p| p.thread_name)
+            .collect();
+        assert!(!names.iter().any(|n| n == "profiled-helper"));
+        assert!(names.contains(&thread::current().name().unwrap().to_string()));
+    }
+
+    #[test]
+    fn trace_events_are_a_bounded_ring() {
+        profiler::enable_trace(TRACE_CAPACITY);
+        for _ in 0..20 {
+            let _g = span!("ring");
+        }
+        let p = this_thread();
+        let ring: Vec<_> = p.events.iter().filter(|e| e.name == "ring").collect();
+        assert_eq!(ring.len(), TRACE_CAPACITY);
+        assert!(p.dropped_events >= 12);
+        assert!(ring.windows(2).all(|w| w[0].start <= w[1].start));
+        assert_eq!(p.find(&["ring"]).unwrap().calls, 20); // stats are unaffected
+    }
+
+    #[test]
+    fn folded_output_lists_full_stacks() {
+        {
+            let _a = span!("fold_root");
+            let _b = span!("fold_leaf");
+            thread::sleep(std::time::Duration::from_millis(2));
+        }
+        let p = this_thread();
+        let folded = profiler::folded_of(std::slice::from_ref(&p));
+        let line = folded
+            .lines()
+            .find(|l| l.contains("fold_root;fold_leaf "))
+            .expect("leaf stack missing");
+        let micros: u64 = line.rsplit(' ').next().unwrap().parse().unwrap();
+        assert!(micros >= 2000);
+        assert!(line.starts_with(&p.thread_name));
+    }
+
+    #[test]
+    fn chrome_trace_has_one_event_per_span() {
+        profiler::enable_trace(TRACE_CAPACITY);
+        {
+            let _a = span!("trace_outer");
+            let _b = span!("trace \"quoted\"");
+        }
+        let p = this_thread();
+        let json = profiler::chrome_trace_of(std::slice::from_ref(&p));
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert_eq!(json.matches("\"name\":\"trace_outer\"").count(), 1);
+        assert!(json.contains(r#""name":"trace \"quoted\"","ph":"X""#));
+    }
+
+    #[test]
+    fn report_shows_counts() {
+        for _ in 0..4 {
+            let _g = span!("report_me");
+        }
+        let report = profiler::report_of(&[this_thread()]);
+        let line = report.lines().find(|l| l.contains("report_me")).unwrap();
+        assert!(line.contains("calls      4"), "{}", line);
+    }
+}
@@ -0,0 +1,623 @@
+// Fixed-point arithmetic for targets without an FPU
+//
+// Many microcontrollers (Cortex-M0/M3, small RISC-V cores) have no floating
+// point unit: every f32/f64 operation becomes a slow software routine.
+// Fixed-point stores a real number as an integer scaled by 2^FRAC:
+//
+//   value = raw / 2^FRAC
+//
+//   Q16.16 = Fixed<i32, 16>  range ±32768,  step 1/65536  ≈ 0.0000153
+//   Q1.15  = Fixed<i16, 15>  range [-1, 1), step 1/32768  ≈ 0.0000305 (audio/DSP)
+//
+// Add/sub are plain integer add/sub. Mul/div go through the next-wider integer
+// type and shift back, rounding to nearest (ties away from zero).
+//
+// Operators (+ - * /) saturate, like most DSP hardware; wrapping_* and
+// checked_* variants are there when you want the other behavior.
+// The `fixed` crate is the production version of this idea.
+
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+struct Fixed<I, const FRAC: u32> {
+    raw: I,
+}
+
+type Q16_16 = Fixed<i32, 16>;
+type Q1_15 = Fixed<i16, 15>;
+type Q8_8 = Fixed<i16, 8>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseFixedError {
+    Empty,
+    InvalidDigit,
+    OutOfRange,
+}
+
+impl fmt::Display for ParseFixedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseFixedError::Empty => write!(f, "cannot parse fixed-point from empty string"),
+            ParseFixedError::InvalidDigit => write!(f, "invalid digit in fixed-point literal"),
+            ParseFixedError::OutOfRange => write!(f, "fixed-point literal out of range"),
+        }
+    }
+}
+
+// --- Rounding helpers (on the wide type, shared by every width) ---
+
+// x / 2^shift, rounded to nearest, ties away from zero
+fn round_shr(x: i128, shift: u32) -> i128 {
+    if shift == 0 {
+        return x;
+    }
+    let half = 1i128 << (shift - 1);
+    if x >= 0 {
+        (x + half) >> shift
+    } else {
+        -((-x + half) >> shift)
+    }
+}
+
+// n / d, rounded to nearest, ties away from zero (d != 0)
+fn round_div(n: i128, d: i128) -> i128 {
+    let (n_abs, d_abs) = (n.unsigned_abs(), d.unsigned_abs());
+    let q = ((n_abs + d_abs / 2) / d_abs) as i128;
+    if (n < 0) == (d < 0) {
+        q
+    } else {
+        -q
+    }
+}
+
+// --- Formatting and parsing (integer-only, no floats involved) ---
+
+fn fmt_raw(
+    raw: i128,
+    frac: u32,
+    precision: Option<usize>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    let neg = raw < 0;
+    let mag = raw.unsigned_abs();
+    let mut int_part = mag >> frac;
+    let frac_bits = mag & ((1u128 << frac) - 1);
+
+    // Default: enough digits to tell neighbouring values apart, trailing zeros trimmed
+    let digits = precision.unwrap_or(((frac as usize) * 30103).div_ceil(100000));
+    let exact_digits = digits.min(19); // 10^19 * 2^63 still fits in u128
+    let scale = 10u128.pow(exact_digits as u32);
+    let mut frac_dec = if frac == 0 {
+        0
+    } else {
+        (frac_bits * scale + (1u128 << (frac - 1))) >> frac
+    };
+    if frac_dec == scale {
+        // 0.99999.. rounded up into the integer part
+        int_part += 1;
+        frac_dec = 0;
+    }
+
+    // The sign stays out of `buf`: pad_integral places it before any
+    // zero padding and handles `+`
+    let nonneg = !neg || (int_part == 0 && frac_dec == 0);
+    let mut buf = int_part.to_string();
+    if exact_digits > 0 {
+        let mut frac_str = format!("{:0width$}", frac_dec, width = exact_digits);
+        if precision.is_none() {
+            while frac_str.ends_with('0') {
+                frac_str.pop();
+            }
+        } else {
+            frac_str.extend(std::iter::repeat_n('0', digits - exact_digits));
+        }
+        if !frac_str.is_empty() {
+            buf.push('.');
+            buf.push_str(&frac_str);
+        }
+    }
+    f.pad_integral(nonneg, "", &buf)
+}
+
+fn parse_raw(s: &str, frac: u32) -> Result<i128, ParseFixedError> {
+    let (neg, body) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let (int_str, frac_str) = body.split_once('.').unwrap_or((body, ""));
+    if int_str.is_empty() && frac_str.is_empty() {
+        return Err(ParseFixedError::Empty);
+    }
+
+    let mut int_part: i128 = 0;
+    for c in int_str.bytes() {
+        let d = (c as char)
+            .to_digit(10)
+            .ok_or(ParseFixedError::InvalidDigit)?;
+        int_part = int_part
+            .checked_mul(10)
+            .and_then(|v| v.checked_add(d as i128))
+            .filter(|&v| v < (1i128 << (126 - frac)))
+            .ok_or(ParseFixedError::OutOfRange)?;
+    }
+
+    // Decimal → binary fraction by repeated doubling: each pass moves one
+    // bit out of the digit string. FRAC bits plus one more for rounding is
+    // exact for any FRAC — later bits can't change a round-half-up.
+    let mut decimal = frac_str
+        .bytes()
+        .map(|c| (c as char).to_digit(10).map(|d| d as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(ParseFixedError::InvalidDigit)?;
+    while decimal.last() == Some(&0) {
+        decimal.pop();
+    }
+    let mut bits: i128 = 0;
+    for _ in 0..=frac {
+        let mut carry = 0;
+        for d in decimal.iter_mut().rev() {
+            let v = *d * 2 + carry;
+            *d = v % 10;
+            carry = v / 10;
+        }
+        bits = bits << 1 | carry as i128;
+    }
+    let frac_raw = (bits + 1) >> 1; // ties away from zero (on the magnitude)
+
+    let raw = (int_part << frac) + frac_raw;
+    Ok(if neg { -raw } else { raw })
+}
+
+// --- One impl block per backing integer, each with its double-width partner ---
+
+macro_rules! impl_fixed {
+    ($int:ty, $wide:ty) => {
+        // The same API exists for every width, but main() doesn't exercise all of it
+        #[allow(dead_code)]
+        impl<const FRAC: u32> Fixed<$int, FRAC> {
+            // Compile-time check: evaluated whenever the type is used
+            const VALID: () = assert!(
+                FRAC < <$int>::BITS,
+                "FRAC must be smaller than the bit width"
+            );
+
+            const MIN: Self = Self::from_bits(<$int>::MIN);
+            const MAX: Self = Self::from_bits(<$int>::MAX);
+            const EPSILON: Self = Self::from_bits(1);
+
+            const fn from_bits(raw: $int) -> Self {
+                #[allow(clippy::let_unit_value)]
+                let _ = Self::VALID;
+                Fixed { raw }
+            }
+
+            const fn to_bits(self) -> $int {
+                self.raw
+            }
+
+            fn saturate(wide: i128) -> Self {
+                Self::from_bits(wide.clamp(<$int>::MIN as i128, <$int>::MAX as i128) as $int)
+            }
+
+            fn from_int(n: $int) -> Self {
+                Self::saturate((n as i128) << FRAC)
+            }
+
+            // Integer part, rounded towards negative infinity (like floor)
+            fn to_int(self) -> $int {
+                self.raw >> FRAC
+            }
+
+            // --- Addition / subtraction: plain integer ops on the raw value ---
+            fn saturating_add(self, rhs: Self) -> Self {
+                Self::from_bits(self.raw.saturating_add(rhs.raw))
+            }
+            fn wrapping_add(self, rhs: Self) -> Self {
+                Self::from_bits(self.raw.wrapping_add(rhs.raw))
+            }
+            fn checked_add(self, rhs: Self) -> Option<Self> {
+                self.raw.checked_add(rhs.raw).map(Self::from_bits)
+            }
+            fn saturating_sub(self, rhs: Self) -> Self {
+                Self::from_bits(self.raw.saturating_sub(rhs.raw))
+            }
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                Self::from_bits(self.raw.wrapping_sub(rhs.raw))
+            }
+            fn checked_sub(self, rhs: Self) -> Option<Self> {
+                self.raw.checked_sub(rhs.raw).map(Self::from_bits)
+            }
+
+            // --- Multiplication: widen, multiply, shift back with rounding ---
+            // (a / 2^F) * (b / 2^F) = (a * b / 2^F) / 2^F
+            fn mul_wide(self, rhs: Self) -> i128 {
+                let prod = (self.raw as $wide) * (rhs.raw as $wide);
+                round_shr(prod as i128, FRAC)
+            }
+            fn saturating_mul(self, rhs: Self) -> Self {
+                Self::saturate(self.mul_wide(rhs))
+            }
+            fn wrapping_mul(self, rhs: Self) -> Self {
+                Self::from_bits(self.mul_wide(rhs) as $int)
+            }
+            fn checked_mul(self, rhs: Self) -> Option<Self> {
+                <$int>::try_from(self.mul_wide(rhs))
+                    .ok()
+                    .map(Self::from_bits)
+            }
+
+            // --- Division: pre-shift the dividend so the quotient keeps FRAC bits ---
+            // (a / 2^F) / (b / 2^F) = (a * 2^F / b) / 2^F
+            fn div_wide(self, rhs: Self) -> Option<i128> {
+                if rhs.raw == 0 {
+                    return None;
+                }
+                let num = ((self.raw as $wide) << FRAC) as i128;
+                Some(round_div(num, rhs.raw as i128))
+            }
+            fn saturating_div(self, rhs: Self) -> Self {
+                Self::saturate(self.div_wide(rhs).expect("attempt to divide by zero"))
+            }
+            fn wrapping_div(self, rhs: Self) -> Self {
+                Self::from_bits(self.div_wide(rhs).expect("attempt to divide by zero") as $int)
+            }
+            fn checked_div(self, rhs: Self) -> Option<Self> {
+                self.div_wide(rhs)
+                    .and_then(|q| <$int>::try_from(q).ok())
+                    .map(Self::from_bits)
+            }
+
+            fn abs(self) -> Self {
+                Self::from_bits(self.raw.saturating_abs())
+            }
+
+            // --- Float conversions (for I/O and tests — not for the hot path) ---
+            fn from_f32(x: f32) -> Self {
+                if x.is_nan() {
+                    return Self::from_bits(0);
+                }
+                let scaled = (x as f64) * (1u64 << FRAC) as f64;
+                // `as` from float saturates at the integer bounds
+                Self::saturate(scaled.round() as i128)
+            }
+
+            fn to_f32(self) -> f32 {
+                (self.raw as f64 / (1u64 << FRAC) as f64) as f32
+            }
+        }
+
+        impl<const FRAC: u32> Add for Fixed<$int, FRAC> {
+            type Output = Self;
+            fn add(self, rhs: Self) -> Self {
+                self.saturating_add(rhs)
+            }
+        }
+
+        impl<const FRAC: u32> Sub for Fixed<$int, FRAC> {
+            type Output = Self;
+            fn sub(self, rhs: Self) -> Self {
+                self.saturating_sub(rhs)
+            }
+        }
+
+        impl<const FRAC: u32> Mul for Fixed<$int, FRAC> {
+            type Output = Self;
+            fn mul(self, rhs: Self) -> Self {
+                self.saturating_mul(rhs)
+            }
+        }
+
+        // Panics on division by zero, like integer division
+        impl<const FRAC: u32> Div for Fixed<$int, FRAC> {
+            type Output = Self;
+            fn div(self, rhs: Self) -> Self {
+                self.saturating_div(rhs)
+            }
+        }
+
+        impl<const FRAC: u32> Neg for Fixed<$int, FRAC> {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self::from_bits(self.raw.saturating_neg())
+            }
+        }
+
+        impl<const FRAC: u32> fmt::Display for Fixed<$int, FRAC> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt_raw(self.raw as i128, FRAC, f.precision(), f)
+            }
+        }
+
+        impl<const FRAC: u32> fmt::Debug for Fixed<$int, FRAC> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}(raw {:#x})", self, self.raw)
+            }
+        }
+
+        impl<const FRAC: u32> FromStr for Fixed<$int, FRAC> {
+            type Err = ParseFixedError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let raw = parse_raw(s, FRAC)?;
+                <$int>::try_from(raw)
+                    .map(Self::from_bits)
+                    .map_err(|_| ParseFixedError::OutOfRange)
+            }
+        }
+    };
+}
+
+impl_fixed!(i16, i32);
+impl_fixed!(i32, i64);
+impl_fixed!(i64, i128);
+
+// --- Example: a low-pass filter with no floating point at runtime ---
+// y[n] = y[n-1] + alpha * (x[n] - y[n-1])
+fn low_pass(samples: &[Q16_16], alpha: Q16_16) -> Vec<Q16_16> {
+    let mut y = Q16_16::from_int(0);
+    samples
+        .iter()
+        .map(|&x| {
+            y = y + alpha * (x - y);
+            y
+        })
+        .collect()
+}
+
+fn main() {
+    // Q16.16 basics
+    println!("--- Q16.16 ---");
+    let a: Q16_16 = "3.25".parse().unwrap();
+    let b = Q16_16::from_int(2);
+    println!("{} + {} = {}", a, b, a + b); // 3.25 + 2 = 5.25
+    println!("{} * {} = {}", a, b, a * b); // 3.25 * 2 = 6.5
+    println!("{} / {} = {}", a, b, a / b); // 3.25 / 2 = 1.625
+    println!("{:.3}", Q16_16::from_int(1) / Q16_16::from_int(3)); // 0.333
+    println!("{:?}", a); // 3.25(raw 0x34000)
+    println!("{}", a.to_int()); // 3
+
+    // Resolution: 0.1 is not exactly representable in binary
+    let tenth: Q16_16 = "0.1".parse().unwrap();
+    println!("{} raw={}", tenth, tenth.to_bits()); // 0.10001 raw=6554
+    println!("{}", Q16_16::EPSILON); // 0.00002
+
+    // Saturation vs wrapping
+    println!("\n--- Overflow ---");
+    let big = Q16_16::from_int(30000);
+    println!("{}", big + big); // 32767.99998 (saturated at MAX)
+    println!("{}", big.wrapping_add(big)); // -5536
+    println!("{:?}", big.checked_mul(big)); // None
+    println!("{}", Q16_16::MIN); // -32768
+
+    // Q1.15: the classic audio sample format, range [-1, 1)
+    println!("\n--- Q1.15 ---");
+    let half = Q1_15::from_f32(0.5);
+    let gain = Q1_15::from_f32(-0.75);
+    println!("{} * {} = {}", half, gain, half * gain); // 0.5 * -0.75 = -0.375
+    println!("{}", Q1_15::from_f32(2.0)); // 0.99997 (saturated)
+    println!("{}", (-Q1_15::MIN).to_f32()); // 0.9999695
+
+    // Q8.8 fits a sensor reading in two bytes
+    let temp: Q8_8 = "23.7".parse().unwrap();
+    println!("{:.1}C in {} bytes", temp, std::mem::size_of::<Q8_8>()); // 23.7C in 2 bytes
+    println!("{:?}", "500".parse::<Q8_8>()); // Err(OutOfRange)
+
+    // Filtering
+    println!("\n--- Low-pass filter ---");
+    let step: Vec<Q16_16> = [0, 10, 10, 10, 10]
+        .iter()
+        .map(|&n| Q16_16::from_int(n))
+        .collect();
+    let alpha: Q16_16 = "0.5".parse().unwrap();
+    let out: Vec<String> = low_pass(&step, alpha)
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    println!("{}", out.join(" ")); // 0 5 7.5 8.75 9.375
+
+    println!("{}", Fixed::<i64, 32>::from_int(-7).abs()); // 7
+}
+
+// ============================================================
+// TESTS — every operation is checked against an f64 reference
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift64*: deterministic "random" inputs without external crates
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        // Uniform in [-2^bits, 2^bits)
+        fn signed(&mut self, bits: u32) -> i64 {
+            (self.next() >> (64 - bits - 1)) as i64 - (1i64 << bits)
+        }
+    }
+
+    fn q16(raw: i64) -> Q16_16 {
+        Q16_16::from_bits(raw as i32)
+    }
+
+    fn f(x: Q16_16) -> f64 {
+        x.to_bits() as f64 / 65536.0
+    }
+
+    // Rounding to nearest means the error is at most half a step
+    const HALF_ULP: f64 = 0.5 / 65536.0;
+
+    #[test]
+    fn add_sub_match_f64_exactly() {
+        let mut rng = Rng(0x1234_5678);
+        for _ in 0..10_000 {
+            let (a, b) = (q16(rng.signed(30)), q16(rng.signed(30)));
+            assert_eq!(f(a + b), f(a) + f(b));
+            assert_eq!(f(a - b), f(a) - f(b));
+        }
+    }
+
+    #[test]
+    fn mul_is_correctly_rounded() {
+        let mut rng = Rng(0xDEAD_BEEF);
+        for _ in 0..10_000 {
+            // ±64 keeps the product in range and exact in f64
+            let (a, b) = (q16(rng.signed(22)), q16(rng.signed(22)));
+            let exact = f(a) * f(b);
+            let got = f(a * b);
+            assert!(
+                (got - exact).abs() <= HALF_ULP,
+                "{} * {} = {} (want {})",
+                a,
+                b,
+                got,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn div_is_correctly_rounded() {
+        let mut rng = Rng(0xC0FFEE);
+        for _ in 0..10_000 {
+            let a = q16(rng.signed(24));
+            let b = q16(rng.signed(24));
+            if b.to_bits().abs() < 65536 {
+                continue; // keep quotients in range
+            }
+            let exact = f(a) / f(b);
+            let got = f(a / b);
+            assert!(
+                (got - exact).abs() <= HALF_ULP + 1e-12,
+                "{} / {} = {} (want {})",
+                a,
+                b,
+                got,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn ties_round_away_from_zero() {
+        let eps = Q16_16::EPSILON;
+        let half: Q16_16 = "0.5".parse().unwrap();
+        assert_eq!(eps * half, eps); // 0.5 ulp -> 1 ulp
+        assert_eq!((-eps) * half, -eps); // -0.5 ulp -> -1 ulp
+        assert_eq!(Q16_16::from_bits(1) / Q16_16::from_int(2), eps);
+    }
+
+    #[test]
+    fn saturating_and_wrapping_overflow() {
+        let big = Q16_16::from_int(20000);
+        assert_eq!(big + big, Q16_16::MAX);
+        assert_eq!(-big - big, Q16_16::MIN);
+        assert_eq!(big * big, Q16_16::MAX);
+        assert_eq!(big * -big, Q16_16::MIN);
+        assert_eq!(big.checked_add(big), None);
+        assert_eq!(big.wrapping_add(big).to_int(), 40000 - 65536);
+        assert_eq!(-Q16_16::MIN, Q16_16::MAX);
+        assert_eq!(Q16_16::MIN.checked_div(-Q16_16::from_int(1)), None);
+        assert_eq!(big.checked_div(Q16_16::from_int(0)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "divide by zero")]
+    fn div_by_zero_panics() {
+        let _ = Q16_16::from_int(1) / Q16_16::from_int(0);
+    }
+
+    #[test]
+    fn q1_15_matches_f64_reference() {
+        let mut rng = Rng(42);
+        for _ in 0..10_000 {
+            let a = Q1_15::from_bits(rng.signed(15) as i16);
+            let b = Q1_15::from_bits(rng.signed(15) as i16);
+            let (fa, fb) = (a.to_bits() as f64 / 32768.0, b.to_bits() as f64 / 32768.0);
+            let got = (a * b).to_bits() as f64 / 32768.0;
+            let want = (fa * fb).clamp(-1.0, 32767.0 / 32768.0);
+            assert!((got - want).abs() <= 0.5 / 32768.0);
+        }
+    }
+
+    #[test]
+    fn f32_conversion_round_trips() {
+        let mut rng = Rng(7);
+        for _ in 0..10_000 {
+            let x = q16(rng.signed(31));
+            // f32 has 24 bits of mantissa, so compare at that precision
+            let back = Q16_16::from_f32(x.to_f32());
+            let tolerance = (x.to_bits().unsigned_abs() >> 23).max(1) as i64;
+            assert!((back.to_bits() as i64 - x.to_bits() as i64).abs() <= tolerance);
+        }
+        assert_eq!(Q16_16::from_f32(1e9), Q16_16::MAX);
+        assert_eq!(Q16_16::from_f32(f32::NEG_INFINITY), Q16_16::MIN);
+        assert_eq!(Q16_16::from_f32(f32::NAN), Q16_16::from_int(0));
+    }
+
+    #[test]
+    fn display_then_parse_round_trips() {
+        let mut rng = Rng(99);
+        for _ in 0..10_000 {
+            let x = q16(rng.signed(31));
+            let s = x.to_string();
+            assert_eq!(s.parse::<Q16_16>(), Ok(x), "{}", s);
+            // Display agrees with f64 formatting at a fixed precision
+            assert_eq!(
+                format!("{:.3}", x),
+                format!("{:.3}", f(x)).replace("-0.000", "0.000")
+            );
+        }
+    }
+
+    #[test]
+    fn display_formats() {
+        let x: Q16_16 = "-1.5".parse().unwrap();
+        assert_eq!(x.to_string(), "-1.5");
+        assert_eq!(format!("{:.2}", x), "-1.50");
+        assert_eq!(format!("{:>8}", x), "    -1.5");
+        assert_eq!(format!("{:08}", x), "-00001.5");
+        assert_eq!(format!("{:+}", x), "-1.5");
+        assert_eq!(format!("{:+}", -x), "+1.5");
+        assert_eq!(format!("{:<8}|", x), "-1.5    |");
+        assert_eq!(format!("{:+08.2}", Q16_16::from_bits(-1)), "+0000.00");
+        assert_eq!(format!("{:.0}", Q16_16::from_f32(2.5)), "3");
+        assert_eq!(format!("{:.2}", Q16_16::from_f32(0.999)), "1.00");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("".parse::<Q16_16>(), Err(ParseFixedError::Empty));
+        assert_eq!("-.".parse::<Q16_16>(), Err(ParseFixedError::Empty));
+        assert_eq!("1.2x".parse::<Q16_16>(), Err(ParseFixedError::InvalidDigit));
+        assert_eq!("40000".parse::<Q16_16>(), Err(ParseFixedError::OutOfRange));
+        assert_eq!("1".parse::<Q1_15>(), Err(ParseFixedError::OutOfRange));
+        assert_eq!("-1".parse::<Q1_15>(), Ok(Q1_15::MIN));
+        assert_eq!(".5".parse::<Q16_16>(), Ok(Q16_16::from_f32(0.5)));
+    }
+
+    #[test]
+    fn parse_is_exact_for_wide_frac() {
+        type Q0_63 = Fixed<i64, 63>;
+        // 2^-63 ≈ 1.08e-19: digits past the 18th still decide the result
+        assert_eq!("0.0000000000000000001".parse(), Ok(Q0_63::from_bits(1)));
+        assert_eq!("0.00000000000000000005".parse(), Ok(Q0_63::from_bits(0)));
+        assert_eq!("-0.5".parse(), Ok(Q0_63::from_bits(-(1 << 62))));
+        // exactly half a step (2^-64) rounds away from zero; a hair less doesn't
+        let half = "0.0000000000000000000542101086242752217003726400434970855712890625";
+        assert_eq!(half.parse(), Ok(Q0_63::from_bits(1)));
+        let below = "0.00000000000000000005421010862427522170037264004349708557128906249";
+        assert_eq!(below.parse(), Ok(Q0_63::from_bits(0)));
+        let eps = Q0_63::EPSILON.to_string();
+        assert_eq!(eps.parse(), Ok(Q0_63::EPSILON), "{}", eps);
+    }
+}
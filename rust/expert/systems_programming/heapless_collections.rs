@@ -0,0 +1,510 @@
+// Heapless collections: String / Vec / Map with a fixed capacity
+//
+// no_std_intro.rs has a one-off FixedBuffer for formatting without a heap.
+// Firmware usually needs a small family of these, all backed by inline arrays:
+//
+//   ArrayString<N>     — UTF-8 text, at most N bytes, Deref<Target = str>
+//   ArrayVec<T, N>     — up to N elements, Deref<Target = [T]>
+//   ArrayMap<K, V, N>  — up to N key/value pairs, linear lookup (fast for small N)
+//
+// Everything below only uses `core` — no Vec, String or Box — so it would
+// compile unchanged under #![no_std]. main() and the tests use std for printing.
+//
+// Fallible by design: push/insert return Err instead of reallocating.
+// The crates `heapless` and `arrayvec` are the production versions of this idea.
+
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+
+// --- Capacity error ---
+// Hands the rejected value back so the caller doesn't lose it
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CapacityError<T>(T);
+
+impl<T> CapacityError<T> {
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "insufficient capacity")
+    }
+}
+
+// ============================================================
+// ArrayVec<T, N>
+// ============================================================
+// [MaybeUninit<T>; N] lets us hold up to N values without requiring T: Default
+// or T: Copy. Only the first `len` slots are initialized.
+
+struct ArrayVec<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    const fn new() -> Self {
+        ArrayVec {
+            // An array of MaybeUninit needs no initialization
+            buf: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+
+    const fn capacity(&self) -> usize {
+        N
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    fn try_push(&mut self, value: T) -> Result<(), CapacityError<T>> {
+        if self.len == N {
+            return Err(CapacityError(value));
+        }
+        self.buf[self.len].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: slot `len` was initialized and is now outside the live range,
+        // so it is read exactly once
+        Some(unsafe { self.buf[self.len].assume_init_read() })
+    }
+
+    fn swap_remove(&mut self, index: usize) -> T {
+        let last = self.len - 1;
+        self.as_mut_slice().swap(index, last);
+        self.pop().unwrap()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            self.pop();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialized
+        unsafe { core::slice::from_raw_parts(self.buf.as_ptr() as *const T, self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: the first `len` elements are initialized
+        unsafe { core::slice::from_raw_parts_mut(self.buf.as_mut_ptr() as *mut T, self.len) }
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayVec<T, N> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for ArrayVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// Collect from an iterator — stops silently at capacity, like `take(N)`
+impl<T, const N: usize> FromIterator<T> for ArrayVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut v = ArrayVec::new();
+        for item in iter.into_iter().take(N) {
+            let _ = v.try_push(item);
+        }
+        v
+    }
+}
+
+// ============================================================
+// ArrayString<N>
+// ============================================================
+// Backed by ArrayVec<u8, N>; the only way in is through &str, so the bytes
+// are always valid UTF-8.
+
+// What `write!` does when the text doesn't fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    Error,    // reject the whole write!, leave the buffer unchanged
+    Truncate, // keep as much as fits (on a char boundary), report success
+}
+
+struct ArrayString<const N: usize> {
+    bytes: ArrayVec<u8, N>,
+    overflow: Overflow,
+    truncated: bool,
+}
+
+impl<const N: usize> ArrayString<N> {
+    const fn new() -> Self {
+        ArrayString {
+            bytes: ArrayVec::new(),
+            overflow: Overflow::Error,
+            truncated: false,
+        }
+    }
+
+    const fn truncating() -> Self {
+        ArrayString {
+            bytes: ArrayVec::new(),
+            overflow: Overflow::Truncate,
+            truncated: false,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // SAFETY: only whole &str values or char-boundary prefixes are ever pushed
+        unsafe { core::str::from_utf8_unchecked(self.bytes.as_slice()) }
+    }
+
+    fn remaining(&self) -> usize {
+        N - self.bytes.len()
+    }
+
+    // True if a Truncate-mode write had to drop text
+    fn was_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn try_push_str(&mut self, s: &str) -> Result<(), CapacityError<()>> {
+        if s.len() > self.remaining() {
+            return Err(CapacityError(()));
+        }
+        for &b in s.as_bytes() {
+            let _ = self.bytes.try_push(b);
+        }
+        Ok(())
+    }
+
+    fn try_push(&mut self, c: char) -> Result<(), CapacityError<char>> {
+        let mut tmp = [0u8; 4];
+        self.try_push_str(c.encode_utf8(&mut tmp))
+            .map_err(|_| CapacityError(c))
+    }
+
+    // Push the longest prefix of `s` that fits without splitting a char
+    fn push_str_truncating(&mut self, s: &str) -> usize {
+        let mut end = s.len().min(self.remaining());
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        let _ = self.try_push_str(&s[..end]);
+        end
+    }
+
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.truncated = false;
+    }
+}
+
+impl<const N: usize> fmt::Write for ArrayString<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.overflow {
+            Overflow::Error => self.try_push_str(s).map_err(|_| fmt::Error),
+            Overflow::Truncate => {
+                if self.push_str_truncating(s) < s.len() {
+                    self.truncated = true;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // write! calls write_str once per piece ("abc{}" is "abc", then the
+    // argument), so a failure can come after earlier pieces went in.
+    // Roll those back so the whole write! is all-or-nothing.
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        let len = self.bytes.len();
+        let result = fmt::write(self, args);
+        if result.is_err() {
+            self.bytes.truncate(len);
+        }
+        result
+    }
+}
+
+impl<const N: usize> Deref for ArrayString<N> {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Display for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for ArrayString<N> {
+    type Error = CapacityError<()>;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let mut out = ArrayString::new();
+        out.try_push_str(s)?;
+        Ok(out)
+    }
+}
+
+// ============================================================
+// ArrayMap<K, V, N>
+// ============================================================
+// Unsorted pairs with linear search. For the N ≤ ~32 typical in firmware
+// this beats hashing: no hasher state, everything in one cache-friendly array.
+
+struct ArrayMap<K, V, const N: usize> {
+    entries: ArrayVec<(K, V), N>,
+}
+
+impl<K: PartialEq, V, const N: usize> ArrayMap<K, V, N> {
+    const fn new() -> Self {
+        ArrayMap {
+            entries: ArrayVec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn position(&self, key: &K) -> Option<usize> {
+        self.entries.iter().position(|(k, _)| k == key)
+    }
+
+    // Ok(Some(old)) if the key existed, Ok(None) if newly inserted,
+    // Err((key, value)) if the map is full and the key is new
+    fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, CapacityError<(K, V)>> {
+        if let Some(i) = self.position(&key) {
+            return Ok(Some(core::mem::replace(&mut self.entries[i].1, value)));
+        }
+        self.entries.try_push((key, value)).map(|()| None)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.position(key).map(|i| &mut self.entries[i].1)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.position(key).map(|i| self.entries.swap_remove(i).1)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.position(key).is_some()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const N: usize> fmt::Debug for ArrayMap<K, V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+fn main() {
+    use core::fmt::Write;
+
+    // ArrayString — formatting without a heap
+    println!("--- ArrayString ---");
+    let mut s: ArrayString<16> = ArrayString::new();
+    write!(s, "x={}, y={}", 10, 20).unwrap();
+    println!("{} (len {}, upper: {})", s, s.len(), s.to_uppercase()); // x=10, y=20 (len 10, upper: X=10, Y=20)
+
+    // Error policy: the write that doesn't fit is rejected entirely
+    let result = write!(s, " overflow!");
+    println!("{:?} {:?}", result, s); // Err(Error) "x=10, y=20"
+
+    // Truncate policy: keep what fits, remember that we dropped something
+    let mut t: ArrayString<8> = ArrayString::truncating();
+    write!(t, "temp={:.2}C", 23.456).unwrap();
+    println!("{:?} truncated={}", t, t.was_truncated()); // "temp=23." truncated=true
+    t.clear();
+    t.try_push('°').unwrap();
+    println!("{:?} remaining={}", t, t.remaining()); // "°" remaining=6
+
+    // ArrayVec — Deref to a slice gives you all the slice methods
+    println!("\n--- ArrayVec ---");
+    let mut v: ArrayVec<i32, 4> = ArrayVec::new();
+    for x in [3, 1, 2] {
+        v.try_push(x).unwrap();
+    }
+    v.sort();
+    println!("{:?} sum={}", v, v.iter().sum::<i32>()); // [1, 2, 3] sum=6
+    v.try_push(4).unwrap();
+    println!("full: {}", v.is_full()); // full: true
+    let err = v.try_push(5).unwrap_err();
+    println!("{} (got back {})", err, err.into_inner()); // insufficient capacity (got back 5)
+    println!("{}/{}", v.len(), v.capacity()); // 4/4
+
+    let evens: ArrayVec<u32, 8> = (0..100).filter(|n| n % 2 == 0).collect();
+    println!("{:?}", evens); // [0, 2, 4, 6, 8, 10, 12, 14]
+
+    // ArrayMap — small fixed-capacity lookup table
+    println!("\n--- ArrayMap ---");
+    let mut sensors: ArrayMap<&str, i16, 3> = ArrayMap::new();
+    sensors.try_insert("temp", 21).unwrap();
+    sensors.try_insert("humidity", 40).unwrap();
+    println!("{:?}", sensors.try_insert("temp", 22)); // Ok(Some(21))
+    if let Some(h) = sensors.get_mut(&"humidity") {
+        *h += 5;
+    }
+    sensors.try_insert("pressure", 1013).unwrap();
+    println!("{:?}", sensors.try_insert("light", 300).is_err()); // true
+    println!("{:?}", sensors); // {"temp": 22, "humidity": 45, "pressure": 1013}
+    println!("{:?}", sensors.get(&"humidity")); // Some(45)
+    let total: i32 = sensors.iter().map(|(_, &v)| v as i32).sum();
+    println!("{}", total); // 1080
+    println!("{:?}", sensors.remove(&"temp")); // Some(22)
+    println!("{} {}", sensors.len(), sensors.contains_key(&"temp")); // 2 false
+
+    println!("{}", core::mem::size_of::<ArrayString<16>>()); // 32 (16 bytes + len + flags)
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+    use std::rc::Rc;
+
+    #[test]
+    fn string_error_policy_leaves_buffer_unchanged() {
+        let mut s: ArrayString<5> = ArrayString::new();
+        write!(s, "abc").unwrap();
+        assert!(write!(s, "def").is_err());
+        assert_eq!(&*s, "abc");
+        assert!(!s.was_truncated());
+    }
+
+    #[test]
+    fn string_error_policy_rolls_back_multi_piece_write() {
+        // "abc" fits on its own; the runtime argument after it doesn't
+        let mut s: ArrayString<5> = ArrayString::new();
+        assert!(write!(s, "abc{}", std::hint::black_box(12345)).is_err());
+        assert_eq!(&*s, "");
+
+        write!(s, "ok").unwrap();
+        assert!(write!(s, "-{}-{}", 1, std::hint::black_box(2)).is_err());
+        assert_eq!(&*s, "ok");
+    }
+
+    #[test]
+    fn string_truncate_respects_char_boundaries() {
+        // "é" is two bytes; only one byte of room is left after "abc"
+        let mut s: ArrayString<4> = ArrayString::truncating();
+        write!(s, "abcé").unwrap();
+        assert_eq!(s.as_str(), "abc");
+        assert!(s.was_truncated());
+
+        s.clear();
+        assert!(!s.was_truncated());
+        write!(s, "ab").unwrap();
+        assert!(!s.was_truncated());
+    }
+
+    #[test]
+    fn string_push_char_and_try_from() {
+        let mut s: ArrayString<3> = ArrayString::try_from("ab").unwrap();
+        assert_eq!(s.try_push('€'), Err(CapacityError('€')));
+        s.try_push('c').unwrap();
+        assert_eq!(&*s, "abc");
+        assert!(ArrayString::<2>::try_from("abc").is_err());
+    }
+
+    #[test]
+    fn vec_push_pop_and_capacity() {
+        let mut v: ArrayVec<u8, 2> = ArrayVec::new();
+        assert!(v.try_push(1).is_ok());
+        assert!(v.try_push(2).is_ok());
+        assert!(v.is_full());
+        assert_eq!(v.try_push(3), Err(CapacityError(3)));
+        assert_eq!(v.pop(), Some(2));
+        assert_eq!(&*v, &[1]);
+        assert_eq!(v.pop(), Some(1));
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn vec_drops_remaining_elements() {
+        let tracker = Rc::new(());
+        {
+            let mut v: ArrayVec<Rc<()>, 4> = ArrayVec::new();
+            for _ in 0..3 {
+                v.try_push(Rc::clone(&tracker)).unwrap();
+            }
+            drop(v.pop());
+            assert_eq!(Rc::strong_count(&tracker), 3);
+        }
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+
+    #[test]
+    fn vec_deref_mut_allows_slice_methods() {
+        let mut v: ArrayVec<i32, 8> = [5, 3, 9, 1].into_iter().collect();
+        v.sort_unstable();
+        v[0] = 0;
+        assert_eq!(&*v, &[0, 3, 5, 9]);
+    }
+
+    #[test]
+    fn map_insert_replace_remove() {
+        let mut m: ArrayMap<u8, &str, 2> = ArrayMap::new();
+        assert_eq!(m.try_insert(1, "a"), Ok(None));
+        assert_eq!(m.try_insert(2, "b"), Ok(None));
+        assert_eq!(m.try_insert(1, "A"), Ok(Some("a")));
+        assert_eq!(m.try_insert(3, "c"), Err(CapacityError((3, "c"))));
+        assert_eq!(m.get(&1), Some(&"A"));
+        assert_eq!(m.remove(&1), Some("A"));
+        assert_eq!(m.remove(&1), None);
+        assert_eq!(m.try_insert(3, "c"), Ok(None));
+        let mut keys: Vec<u8> = m.iter().map(|(k, _)| *k).collect();
+        keys.sort();
+        assert_eq!(keys, vec![2, 3]);
+    }
+}
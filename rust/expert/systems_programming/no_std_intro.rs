@@ -57,6 +57,7 @@ fn demo_core_numerics() {
     println!("{:?}", a.overflowing_add(100)); // (44, true)
 
     // Power, log, etc. available in core
+    // (f32 math needs an FPU to be fast — fixed_point.rs shows the integer alternative)
     let x: f32 = 2.0;
     println!("{}", x.powi(10)); // 1024
     println!("{:.4}", x.sqrt()); // 1.4142
@@ -94,6 +95,8 @@ fn demo_fixed_types() {
 // --- core::fmt without std ---
 // In no_std, you can still use write! and format_args! with core::fmt
 // but String doesn't exist — write to fixed buffers instead
+// Overflow policy here: a write that doesn't fit fails and leaves the buffer as it was.
+// See heapless_collections.rs for ArrayString/ArrayVec/ArrayMap with a choice of policy.
 
 use core::fmt::Write;
 
@@ -125,6 +128,16 @@ impl Write for FixedBuffer {
         self.pos = end;
         Ok(())
     }
+
+    // write! calls write_str once per piece, so undo the pieces that did fit
+    fn write_fmt(&mut self, args: core::fmt::Arguments<'_>) -> core::fmt::Result {
+        let start = self.pos;
+        let result = core::fmt::write(self, args);
+        if result.is_err() {
+            self.pos = start;
+        }
+        result
+    }
 }
 
 fn demo_no_heap_formatting() {
@@ -0,0 +1,494 @@
+// A simulated board for developing embedded logic without hardware
+//
+// embedded_basics.rs pokes global `static` registers and `blink` just loops,
+// so nothing about timing can be observed. Here the peripherals hang off a
+// `Board` value that owns:
+//   - a virtual clock (advances only when you call tick/advance)
+//   - GPIO pins with edge-triggered interrupt callbacks
+//   - a UART that moves scripted bytes into a receive buffer on each tick
+//
+// Every pin change is recorded with its timestamp, so tests can script the
+// inputs and then assert on the output waveform — no sleeps, fully deterministic.
+//
+// Real HALs (embedded-hal, embassy) are shaped similarly: drivers take the
+// peripheral they talk to, instead of reaching for global state.
+
+use std::collections::VecDeque;
+
+// --- Virtual clock ---
+// One tick is whatever unit the firmware cares about (here: 1 ms).
+// Time never moves on its own — the board advances it explicitly.
+
+#[derive(Debug, Default)]
+struct VirtualClock {
+    now: u64,
+}
+
+impl VirtualClock {
+    fn now(&self) -> u64 {
+        self.now
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.now += 1;
+        self.now
+    }
+}
+
+// --- GPIO with edge interrupts ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+impl Edge {
+    fn matches(self, from: bool, to: bool) -> bool {
+        match self {
+            Edge::Rising => !from && to,
+            Edge::Falling => from && !to,
+            Edge::Both => from != to,
+        }
+    }
+}
+
+// What an interrupt handler receives (like reading the IRQ status register)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PinEvent {
+    pin: u8,
+    rising: bool,
+    at: u64,
+}
+
+type IrqHandler = Box<dyn FnMut(PinEvent)>;
+
+struct Pin {
+    direction: Direction,
+    level: bool,
+    waveform: Vec<(u64, bool)>, // (tick, new level) — one entry per transition
+    irq: Option<(Edge, IrqHandler)>,
+}
+
+impl Pin {
+    fn new() -> Self {
+        Pin {
+            direction: Direction::Input,
+            level: false,
+            waveform: Vec::new(),
+            irq: None,
+        }
+    }
+}
+
+// --- UART ---
+// The "wire" side holds bytes scripted by the test; each tick delivers one
+// byte into the receive buffer, like a very slow baud rate.
+
+struct Uart {
+    wire: VecDeque<(u64, u8)>, // (earliest tick it may arrive, byte)
+    rx: VecDeque<u8>,
+    rx_capacity: usize,
+    tx: Vec<u8>,
+    overruns: u32,
+}
+
+impl Uart {
+    fn new(rx_capacity: usize) -> Self {
+        Uart {
+            wire: VecDeque::new(),
+            rx: VecDeque::with_capacity(rx_capacity),
+            rx_capacity,
+            tx: Vec::new(),
+            overruns: 0,
+        }
+    }
+
+    fn on_tick(&mut self, now: u64) {
+        if let Some(&(at, byte)) = self.wire.front() {
+            if at <= now {
+                self.wire.pop_front();
+                if self.rx.len() == self.rx_capacity {
+                    self.overruns += 1; // real UARTs drop the byte and set an OVR flag
+                } else {
+                    self.rx.push_back(byte);
+                }
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        self.rx.pop_front()
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.tx.extend_from_slice(bytes);
+    }
+}
+
+// --- The board ---
+
+struct Board {
+    clock: VirtualClock,
+    pins: Vec<Pin>,
+    uart: Uart,
+    script: Vec<(u64, u8, bool)>, // scheduled external input changes: (tick, pin, level)
+}
+
+impl Board {
+    fn new(pin_count: u8) -> Self {
+        Board {
+            clock: VirtualClock::default(),
+            pins: (0..pin_count).map(|_| Pin::new()).collect(),
+            uart: Uart::new(16),
+            script: Vec::new(),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    fn pin_mode(&mut self, pin: u8, direction: Direction) {
+        self.pins[pin as usize].direction = direction;
+    }
+
+    fn read(&self, pin: u8) -> bool {
+        self.pins[pin as usize].level
+    }
+
+    // Firmware side: drive an output pin
+    fn write(&mut self, pin: u8, level: bool) {
+        assert_eq!(
+            self.pins[pin as usize].direction,
+            Direction::Output,
+            "pin {} is not an output",
+            pin
+        );
+        self.set_level(pin, level);
+    }
+
+    fn on_edge(&mut self, pin: u8, edge: Edge, handler: impl FnMut(PinEvent) + 'static) {
+        self.pins[pin as usize].irq = Some((edge, Box::new(handler)));
+    }
+
+    // Test side: the outside world changes an input at a given tick
+    fn schedule_input(&mut self, at: u64, pin: u8, level: bool) {
+        self.script.push((at, pin, level));
+        self.script.sort_by_key(|&(t, _, _)| t);
+    }
+
+    // Test side: bytes arrive on the UART wire starting at a given tick.
+    // Kept in time order (on_tick only looks at the front); a batch goes after
+    // anything already due at the same tick.
+    fn schedule_uart_rx(&mut self, at: u64, bytes: &[u8]) {
+        let start = self.uart.wire.partition_point(|&(t, _)| t <= at);
+        for (i, &b) in bytes.iter().enumerate() {
+            self.uart.wire.insert(start + i, (at, b));
+        }
+    }
+
+    fn set_level(&mut self, pin: u8, level: bool) {
+        let now = self.clock.now();
+        let p = &mut self.pins[pin as usize];
+        let old = p.level;
+        if old == level {
+            return;
+        }
+        p.level = level;
+        p.waveform.push((now, level));
+        if let Some((edge, handler)) = p.irq.as_mut() {
+            if edge.matches(old, level) {
+                handler(PinEvent {
+                    pin,
+                    rising: level,
+                    at: now,
+                });
+            }
+        }
+    }
+
+    // Advance time by one tick: apply due inputs, then let the UART shift a byte
+    fn tick(&mut self) {
+        let now = self.clock.tick();
+        while let Some(&(at, pin, level)) = self.script.first() {
+            if at > now {
+                break;
+            }
+            self.script.remove(0);
+            self.set_level(pin, level);
+        }
+        self.uart.on_tick(now);
+    }
+
+    fn advance(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    fn waveform(&self, pin: u8) -> &[(u64, bool)] {
+        &self.pins[pin as usize].waveform
+    }
+
+    // Level of a pin at an arbitrary past tick, reconstructed from the waveform
+    fn level_at(&self, pin: u8, at: u64) -> bool {
+        self.waveform(pin)
+            .iter()
+            .take_while(|&&(t, _)| t <= at)
+            .last()
+            .map(|&(_, level)| level)
+            .unwrap_or(false)
+    }
+}
+
+// --- Firmware written against the board ---
+// Same state machine as embedded_basics.rs, but blink now takes real (virtual) time
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum LedState {
+    Off,
+    On,
+    Blinking { count: u32 },
+}
+
+struct Led {
+    pin: u8,
+    state: LedState,
+}
+
+impl Led {
+    fn new(board: &mut Board, pin: u8) -> Self {
+        board.pin_mode(pin, Direction::Output);
+        Led {
+            pin,
+            state: LedState::Off,
+        }
+    }
+
+    fn turn_on(&mut self, board: &mut Board) {
+        board.write(self.pin, true);
+        self.state = LedState::On;
+    }
+
+    fn turn_off(&mut self, board: &mut Board) {
+        board.write(self.pin, false);
+        self.state = LedState::Off;
+    }
+
+    // `half_period` ticks high, then `half_period` ticks low, `times` times
+    fn blink(&mut self, board: &mut Board, times: u32, half_period: u64) {
+        self.state = LedState::Blinking { count: times };
+        for _ in 0..times {
+            board.write(self.pin, true);
+            board.advance(half_period);
+            board.write(self.pin, false);
+            board.advance(half_period);
+        }
+        self.state = LedState::Off;
+    }
+}
+
+// A tiny "main loop": echo UART input back out, upper-cased
+fn uart_echo(board: &mut Board) {
+    while let Some(b) = board.uart.read_byte() {
+        board.uart.write_bytes(&[b.to_ascii_uppercase()]);
+    }
+}
+
+fn main() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut board = Board::new(16);
+
+    // LED waveform
+    println!("--- LED ---");
+    let mut led = Led::new(&mut board, 13);
+    led.turn_on(&mut board);
+    board.advance(5);
+    led.turn_off(&mut board);
+    led.blink(&mut board, 2, 10);
+    println!("{:?}", led.state); // Off
+    println!("{:?}", board.waveform(13));
+    // [(0, true), (5, false), (5, true), (15, false), (25, true), (35, false)]
+    println!("now = {}", board.now()); // now = 45
+    println!("at t=10: {}", board.level_at(13, 10)); // at t=10: true
+
+    // Button on pin 2 with a falling-edge interrupt
+    println!("\n--- Button IRQ ---");
+    let presses = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&presses);
+    board.on_edge(2, Edge::Falling, move |ev| log.borrow_mut().push(ev.at));
+
+    let t = board.now();
+    board.schedule_input(t + 3, 2, true);
+    board.schedule_input(t + 8, 2, false); // press
+    board.schedule_input(t + 20, 2, true);
+    board.schedule_input(t + 22, 2, false); // press
+    board.advance(30);
+    println!("presses at {:?}", presses.borrow()); // presses at [53, 67]
+    println!("pin 2 now: {}", board.read(2)); // pin 2 now: false
+
+    // UART
+    println!("\n--- UART ---");
+    let t = board.now();
+    board.schedule_uart_rx(t + 1, b"hi!");
+    board.advance(1);
+    println!("{:?}", board.uart.rx); // [104] — one byte per tick
+    board.advance(5);
+    uart_echo(&mut board);
+    println!("{}", String::from_utf8_lossy(&board.uart.tx)); // HI!
+
+    println!("simulated board done"); // simulated board done
+}
+
+// ============================================================
+// TESTS — script the inputs, then assert on what the pins did over time
+// Run with: rustc --test simulated_board.rs && ./simulated_board
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn blink_produces_expected_waveform() {
+        let mut board = Board::new(16);
+        let mut led = Led::new(&mut board, 13);
+        led.blink(&mut board, 3, 4);
+
+        assert_eq!(
+            board.waveform(13),
+            &[
+                (0, true),
+                (4, false),
+                (8, true),
+                (12, false),
+                (16, true),
+                (20, false)
+            ]
+        );
+        assert_eq!(board.now(), 24);
+        assert_eq!(led.state, LedState::Off);
+    }
+
+    #[test]
+    fn level_at_reconstructs_history() {
+        let mut board = Board::new(4);
+        let mut led = Led::new(&mut board, 1);
+        board.advance(2);
+        led.turn_on(&mut board);
+        board.advance(3);
+        led.turn_off(&mut board);
+
+        assert!(!board.level_at(1, 1));
+        assert!(board.level_at(1, 2));
+        assert!(board.level_at(1, 4));
+        assert!(!board.level_at(1, 5));
+    }
+
+    #[test]
+    fn edge_interrupts_fire_only_on_matching_edges() {
+        let mut board = Board::new(4);
+        let rising = Rc::new(RefCell::new(Vec::new()));
+        let both = Rc::new(RefCell::new(Vec::new()));
+        let r = Rc::clone(&rising);
+        let b = Rc::clone(&both);
+        board.on_edge(0, Edge::Rising, move |ev| r.borrow_mut().push(ev.at));
+        board.on_edge(1, Edge::Both, move |ev| {
+            b.borrow_mut().push((ev.at, ev.rising))
+        });
+
+        for (at, level) in [(2, true), (4, false), (6, true)] {
+            board.schedule_input(at, 0, level);
+            board.schedule_input(at, 1, level);
+        }
+        board.advance(10);
+
+        assert_eq!(*rising.borrow(), vec![2, 6]);
+        assert_eq!(*both.borrow(), vec![(2, true), (4, false), (6, true)]);
+    }
+
+    #[test]
+    fn repeated_level_is_not_an_edge() {
+        let mut board = Board::new(2);
+        let count = Rc::new(RefCell::new(0));
+        let c = Rc::clone(&count);
+        board.on_edge(0, Edge::Both, move |_| *c.borrow_mut() += 1);
+
+        board.schedule_input(1, 0, true);
+        board.schedule_input(2, 0, true);
+        board.advance(3);
+
+        assert_eq!(*count.borrow(), 1);
+        assert_eq!(board.waveform(0), &[(1, true)]);
+    }
+
+    #[test]
+    fn uart_delivers_one_byte_per_tick() {
+        let mut board = Board::new(1);
+        board.schedule_uart_rx(2, b"abc");
+
+        board.advance(1);
+        assert_eq!(board.uart.read_byte(), None);
+        board.advance(1);
+        assert_eq!(board.uart.read_byte(), Some(b'a'));
+        board.advance(2);
+        assert_eq!(board.uart.read_byte(), Some(b'b'));
+        assert_eq!(board.uart.read_byte(), Some(b'c'));
+        assert_eq!(board.uart.read_byte(), None);
+    }
+
+    #[test]
+    fn uart_batches_scheduled_out_of_order() {
+        let mut board = Board::new(1);
+        board.schedule_uart_rx(10, b"late");
+        board.schedule_uart_rx(1, b"ab");
+
+        board.advance(3);
+        assert_eq!(board.uart.read_byte(), Some(b'a'));
+        assert_eq!(board.uart.read_byte(), Some(b'b'));
+        assert_eq!(board.uart.read_byte(), None);
+        board.advance(10);
+        assert_eq!(board.uart.rx, b"late");
+    }
+
+    #[test]
+    fn uart_overrun_drops_bytes() {
+        let mut board = Board::new(1);
+        board.uart = Uart::new(2);
+        board.schedule_uart_rx(0, b"wxyz");
+        board.advance(4);
+
+        assert_eq!(board.uart.overruns, 2);
+        assert_eq!(board.uart.read_byte(), Some(b'w'));
+        assert_eq!(board.uart.read_byte(), Some(b'x'));
+    }
+
+    #[test]
+    fn echo_loop_processes_scripted_input() {
+        let mut board = Board::new(1);
+        board.schedule_uart_rx(1, b"ok");
+        board.advance(2);
+        uart_echo(&mut board);
+        assert_eq!(board.uart.tx, b"OK");
+    }
+
+    #[test]
+    #[should_panic(expected = "not an output")]
+    fn writing_an_input_pin_panics() {
+        let mut board = Board::new(1);
+        board.write(0, true);
+    }
+}
@@ -22,6 +22,8 @@ extern "C" {
 // --- Exposing Rust functions to C ---
 // #[no_mangle]: keep the exact function name in the compiled binary
 // extern "C": use C calling convention so C code can call this
+// (a full C API with opaque handles, error codes and a generated header:
+//  advanced/project_organization/full_project/src/ffi.rs)
 #[no_mangle]
 pub extern "C" fn rust_add(a: c_int, b: c_int) -> c_int {
     a + b
@@ -96,6 +98,7 @@ pub extern "C" fn rect_area(rect: Rectangle) -> f64 {
 
 // --- Callbacks: passing Rust closures to C-like function pointers ---
 // C uses function pointers; Rust can pass fn pointers (not closures) via FFI
+// (closures with captured state via a user_data trampoline: trampoline.rs)
 type Predicate = extern "C" fn(c_int) -> bool;
 
 extern "C" fn is_even(n: c_int) -> bool {
@@ -0,0 +1,803 @@
+#!/usr/bin/env rust-script
+//! ```cargo
+//! [package]
+//! edition = "2021"
+//! [target.'cfg(loom)'.dependencies]
+//! loom = "0.7"
+//! [lints.rust]
+//! unexpected_cfgs = { level = "warn", check-cfg = ["cfg(loom)"] }
+//! ```
+
+// Lock-free data structures: Treiber stack, bounded MPMC queue, epoch reclamation
+//
+// atomic_types.rs shows compare_exchange on an integer. The same CAS loop
+// can publish pointers, which gives data structures where no thread ever
+// holds a lock: a stalled thread can't block the others.
+//
+// Treiber stack: `head` is an AtomicPtr to the top node.
+//   push: new.next = head; CAS(head: old → new)        retry if someone beat us
+//   pop:  top = head; CAS(head: top → top.next)        retry if someone beat us
+//
+// The hard part is freeing a popped node. Another thread may have loaded
+// `top` just before our CAS and still be about to read `top.next`:
+//   - free it at once       → that read is a use-after-free
+//   - the allocator reuses it → their CAS can succeed on a *different* node at
+//     the same address (the ABA problem) and corrupt the stack
+//
+// Epoch-based reclamation (EBR) fixes both:
+//   - every operation runs inside `collector.pin()`, which registers the
+//     thread in the current global epoch
+//   - unlinked nodes are *retired*, tagged with the epoch, not freed
+//   - the epoch only advances when no thread is pinned in the previous one
+//   - after three advances nobody can still see a retired node → free it
+//
+// Bounded MPMC queue (Dmitry Vyukov's design): a ring of slots, each with a
+// sequence number saying whose turn it is. Producers and consumers claim
+// positions with a CAS and never touch freed memory, so no EBR is needed.
+//
+// Model checking: `RUSTFLAGS="--cfg loom" cargo test --release loom_` runs the
+// loom tests, which try every interleaving of small scenarios.
+
+// Under loom only the model tests run; the demo is compiled out
+#![cfg_attr(loom, allow(dead_code))]
+
+use std::marker::PhantomData;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ptr;
+
+// std or loom atomics, chosen at compile time
+#[cfg(loom)]
+mod sync {
+    pub use loom::cell::UnsafeCell;
+    pub use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+    pub use loom::thread::yield_now as spin_loop;
+}
+
+#[cfg(not(loom))]
+mod sync {
+    pub use std::hint::spin_loop;
+    pub use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+    // loom's UnsafeCell API (with_mut), so the queue compiles either way
+    pub struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub fn new(value: T) -> Self {
+            UnsafeCell(std::cell::UnsafeCell::new(value))
+        }
+
+        pub fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+            f(self.0.get())
+        }
+    }
+}
+
+use sync::{AtomicPtr, AtomicUsize, Ordering, UnsafeCell};
+
+// --- Epoch-based reclamation ---
+
+struct Garbage {
+    ptr: *mut u8,
+    free: unsafe fn(*mut u8),
+    epoch: usize,
+    next: *mut Garbage,
+}
+
+struct Collector {
+    epoch: AtomicUsize,
+    // Threads pinned in epoch e are counted in pinned[e % 3]. At any moment
+    // pins are only in the current epoch or the one before, so 3 buckets
+    // never alias a live epoch.
+    pinned: [AtomicUsize; 3],
+    garbage: AtomicPtr<Garbage>, // lock-free list of retired pointers
+    retired: AtomicUsize,
+    pending: AtomicUsize, // retired but not yet freed; a count, not a walk
+}
+
+// Retired pointers are only freed by whoever wins them in collect()
+unsafe impl Send for Collector {}
+unsafe impl Sync for Collector {}
+
+struct Guard<'c> {
+    collector: &'c Collector,
+    epoch: usize,
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        self.collector.pinned[self.epoch % 3].fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Under loom, collect on every retire so the models reach the free path
+#[cfg(not(loom))]
+const COLLECT_EVERY: usize = 32;
+#[cfg(loom)]
+const COLLECT_EVERY: usize = 1;
+
+impl Collector {
+    fn new() -> Self {
+        Collector {
+            epoch: AtomicUsize::new(0),
+            pinned: [
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+            ],
+            garbage: AtomicPtr::new(ptr::null_mut()),
+            retired: AtomicUsize::new(0),
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    fn pin(&self) -> Guard<'_> {
+        loop {
+            let e = self.epoch.load(Ordering::SeqCst);
+            self.pinned[e % 3].fetch_add(1, Ordering::SeqCst);
+            // If the epoch moved between the load and our increment, the
+            // advancing thread may not have seen us: back off and retry
+            if self.epoch.load(Ordering::SeqCst) == e {
+                return Guard {
+                    collector: self,
+                    epoch: e,
+                };
+            }
+            self.pinned[e % 3].fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // Safety: `ptr` must be unlinked (no new thread can reach it) and `free`
+    // must be the right way to release it
+    unsafe fn retire(&self, guard: &Guard<'_>, ptr: *mut u8, free: unsafe fn(*mut u8)) {
+        let node = Box::into_raw(Box::new(Garbage {
+            ptr,
+            free,
+            epoch: guard.epoch,
+            next: ptr::null_mut(),
+        }));
+        // Count before publishing, so a racing collect() can't free the node
+        // and subtract it first
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        self.push_garbage(node, node);
+        let retired = self.retired.fetch_add(1, Ordering::Relaxed) + 1;
+        if retired.is_multiple_of(COLLECT_EVERY) {
+            self.collect();
+        }
+    }
+
+    // Push the chain first..=last onto the garbage list
+    fn push_garbage(&self, first: *mut Garbage, last: *mut Garbage) {
+        let mut head = self.garbage.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*last).next = head };
+            match self.garbage.compare_exchange_weak(
+                head,
+                first,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn try_advance(&self) -> usize {
+        let e = self.epoch.load(Ordering::SeqCst);
+        // (e + 2) % 3 is the previous epoch's bucket
+        if self.pinned[(e + 2) % 3].load(Ordering::SeqCst) == 0 {
+            let _ = self
+                .epoch
+                .compare_exchange(e, e + 1, Ordering::SeqCst, Ordering::SeqCst);
+        }
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    // Free what's old enough; returns how many pointers were freed
+    fn collect(&self) -> usize {
+        let now = self.try_advance();
+        // swap() takes the whole list: it's ours alone, no ABA on the way out
+        let mut node = self.garbage.swap(ptr::null_mut(), Ordering::Acquire);
+        let (mut keep_first, mut keep_last): (*mut Garbage, *mut Garbage) =
+            (ptr::null_mut(), ptr::null_mut());
+        let mut freed = 0;
+        while !node.is_null() {
+            let next = unsafe { (*node).next };
+            if unsafe { (*node).epoch } + 3 <= now {
+                unsafe {
+                    let g = Box::from_raw(node);
+                    (g.free)(g.ptr);
+                }
+                freed += 1;
+            } else {
+                unsafe { (*node).next = keep_first };
+                if keep_last.is_null() {
+                    keep_last = node;
+                }
+                keep_first = node;
+            }
+            node = next;
+        }
+        if !keep_first.is_null() {
+            self.push_garbage(keep_first, keep_last);
+        }
+        self.pending.fetch_sub(freed, Ordering::Relaxed);
+        freed
+    }
+
+    // Walking the list here could race a collect() freeing its nodes
+    fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Collector {
+    // &mut self: no thread can be pinned any more, so everything goes
+    fn drop(&mut self) {
+        let mut node = self.garbage.swap(ptr::null_mut(), Ordering::Acquire);
+        while !node.is_null() {
+            unsafe {
+                let g = Box::from_raw(node);
+                (g.free)(g.ptr);
+                node = g.next;
+            }
+        }
+    }
+}
+
+// --- Treiber stack ---
+
+struct Node<T> {
+    value: ManuallyDrop<T>, // moved out by pop(); the node is freed later
+    next: *mut Node<T>,
+    // Under loom a node is only marked freed and never released, so a pop
+    // that reads a reclaimed node fails an assertion instead of being UB
+    #[cfg(loom)]
+    freed: sync::AtomicBool,
+}
+
+unsafe fn free_node<T>(p: *mut u8) {
+    #[cfg(not(loom))]
+    drop(Box::from_raw(p as *mut Node<T>));
+    #[cfg(loom)]
+    (*(p as *mut Node<T>)).freed.store(true, Ordering::SeqCst);
+}
+
+struct TreiberStack<T> {
+    head: AtomicPtr<Node<T>>,
+    collector: Collector,
+    _owns: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for TreiberStack<T> {}
+unsafe impl<T: Send> Sync for TreiberStack<T> {}
+
+impl<T> TreiberStack<T> {
+    fn new() -> Self {
+        TreiberStack {
+            head: AtomicPtr::new(ptr::null_mut()),
+            collector: Collector::new(),
+            _owns: PhantomData,
+        }
+    }
+
+    fn push(&self, value: T) {
+        let node = Box::into_raw(Box::new(Node {
+            value: ManuallyDrop::new(value),
+            next: ptr::null_mut(),
+            #[cfg(loom)]
+            freed: sync::AtomicBool::new(false),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // `node` isn't shared yet: plain writes are fine
+            unsafe { (*node).next = head };
+            // Release: the node's contents are visible to whoever pops it
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        let guard = self.collector.pin();
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if head.is_null() {
+                return None;
+            }
+            // Safe to read: we're pinned, so `head` can't have been freed
+            #[cfg(loom)]
+            assert!(
+                !unsafe { (*head).freed.load(Ordering::SeqCst) },
+                "pop read a reclaimed node"
+            );
+            let next = unsafe { (*head).next };
+            match self
+                .head
+                .compare_exchange_weak(head, next, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => unsafe {
+                    // We unlinked it: the value is ours, the node goes to EBR
+                    let value = ManuallyDrop::into_inner(ptr::read(&(*head).value));
+                    self.collector
+                        .retire(&guard, head as *mut u8, free_node::<T>);
+                    return Some(value);
+                },
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T> Drop for TreiberStack<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+        // then `collector` drops and frees the retired nodes
+    }
+}
+
+// --- Bounded MPMC queue ---
+
+struct Slot<T> {
+    // == position:      free, waiting for the producer of `position`
+    // == position + 1:  full, waiting for the consumer of `position`
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+struct ArrayQueue<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    enqueue_pos: AtomicUsize,
+    dequeue_pos: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for ArrayQueue<T> {}
+unsafe impl<T: Send> Sync for ArrayQueue<T> {}
+
+impl<T> ArrayQueue<T> {
+    // Capacity is rounded up to a power of two
+    fn new(capacity: usize) -> Self {
+        let cap = capacity.max(2).next_power_of_two();
+        ArrayQueue {
+            slots: (0..cap)
+                .map(|i| Slot {
+                    seq: AtomicUsize::new(i),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            mask: cap - 1,
+            enqueue_pos: AtomicUsize::new(0),
+            dequeue_pos: AtomicUsize::new(0),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+
+    // Err(value) when full
+    fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq as isize - pos as isize;
+            if diff == 0 {
+                // Our turn for this slot, if we win the position
+                match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        slot.value.with_mut(|p| unsafe { (*p).write(value) });
+                        slot.seq.store(pos + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(actual) => pos = actual,
+                }
+            } else if diff < 0 {
+                // The slot still holds last lap's value: full
+                return Err(value);
+            } else {
+                // Another producer took `pos`; catch up
+                sync::spin_loop();
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq as isize - (pos + 1) as isize;
+            if diff == 0 {
+                match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = slot.value.with_mut(|p| unsafe { (*p).assume_init_read() });
+                        // Free for the producer one lap later
+                        slot.seq.store(pos + self.mask + 1, Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(actual) => pos = actual,
+                }
+            } else if diff < 0 {
+                return None; // nothing written here yet: empty
+            } else {
+                sync::spin_loop();
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<T> Drop for ArrayQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+// --- Demo ---
+
+#[cfg(not(loom))]
+fn main() {
+    use std::sync::atomic::AtomicU64;
+    use std::thread;
+
+    // --- Treiber stack: LIFO ---
+    let stack = TreiberStack::new();
+    for i in 1..=3 {
+        stack.push(i);
+    }
+    println!("{:?} {:?}", stack.pop(), stack.pop()); // Some(3) Some(2)
+
+    // Many threads pushing and popping at once
+    let stack = TreiberStack::new();
+    let popped_sum = AtomicU64::new(0);
+    thread::scope(|s| {
+        for t in 0..4u64 {
+            let (stack, popped_sum) = (&stack, &popped_sum);
+            s.spawn(move || {
+                for i in 0..10_000 {
+                    stack.push(t * 10_000 + i);
+                    if let Some(v) = stack.pop() {
+                        popped_sum.fetch_add(v, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    while let Some(v) = stack.pop() {
+        popped_sum.fetch_add(v, Ordering::Relaxed);
+    }
+    let expected: u64 = (0..40_000).sum();
+    println!("{}", popped_sum.into_inner() == expected); // true
+    println!("{}", stack.is_empty()); // true
+
+    // Retired nodes wait for safe epochs; nothing is pinned now, so a few
+    // collections free them all
+    println!("{}", stack.collector.pending() > 0); // true
+    for _ in 0..4 {
+        stack.collector.collect();
+    }
+    println!("{}", stack.collector.pending()); // 0
+
+    // --- ArrayQueue: bounded FIFO ---
+    let q = ArrayQueue::new(3); // rounds up to 4
+    println!("{}", q.capacity()); // 4
+    for i in 0..4 {
+        q.push(i).unwrap();
+    }
+    println!("{:?}", q.push(99)); // Err(99)
+    println!("{:?} {:?}", q.pop(), q.pop()); // Some(0) Some(1)
+
+    // 2 producers, 2 consumers
+    let q = ArrayQueue::new(64);
+    let received = AtomicU64::new(0);
+    thread::scope(|s| {
+        for p in 0..2u64 {
+            let q = &q;
+            s.spawn(move || {
+                for i in 0..5_000 {
+                    let mut item = p * 5_000 + i;
+                    while let Err(back) = q.push(item) {
+                        item = back;
+                        thread::yield_now(); // full: let a consumer run
+                    }
+                }
+            });
+        }
+        for _ in 0..2 {
+            let (q, received) = (&q, &received);
+            s.spawn(move || {
+                let mut got = 0;
+                while got < 5_000 {
+                    match q.pop() {
+                        Some(v) => {
+                            received.fetch_add(v, Ordering::Relaxed);
+                            got += 1;
+                        }
+                        None => thread::yield_now(),
+                    }
+                }
+            });
+        }
+    });
+    println!("{}", received.into_inner() == (0..10_000).sum::<u64>()); // true
+
+    println!("lock free done"); // lock free done
+}
+
+#[cfg(loom)]
+fn main() {}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicUsize as StdAtomicUsize;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    // Counts drops so leaks and double frees show up
+    struct Tracked(u64, Arc<StdAtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn stack_is_lifo_and_drops_leftovers() {
+        let drops = Arc::new(StdAtomicUsize::new(0));
+        let stack = TreiberStack::new();
+        for i in 0..10 {
+            stack.push(Tracked(i, Arc::clone(&drops)));
+        }
+        assert_eq!(stack.pop().map(|t| t.0), Some(9));
+        assert_eq!(stack.pop().map(|t| t.0), Some(8));
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        drop(stack);
+        assert_eq!(drops.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn stack_stress_every_value_popped_exactly_once() {
+        let stack = TreiberStack::new();
+        let seen = Mutex::new(Vec::new());
+        thread::scope(|s| {
+            for t in 0..8u64 {
+                let (stack, seen) = (&stack, &seen);
+                s.spawn(move || {
+                    let mut mine = Vec::new();
+                    for i in 0..5_000 {
+                        stack.push(t * 1_000_000 + i);
+                        if i % 3 != 0 {
+                            mine.extend(stack.pop());
+                        }
+                    }
+                    seen.lock().unwrap().extend(mine);
+                });
+            }
+        });
+        let mut all = seen.into_inner().unwrap();
+        while let Some(v) = stack.pop() {
+            all.push(v);
+        }
+        assert_eq!(all.len(), 8 * 5_000);
+        let unique: HashSet<_> = all.iter().collect();
+        assert_eq!(unique.len(), all.len());
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn retired_nodes_wait_while_a_reader_is_pinned() {
+        let stack = TreiberStack::new();
+        for i in 0..100 {
+            stack.push(i);
+        }
+        let reader = stack.collector.pin(); // a slow reader, mid-operation
+        for _ in 0..100 {
+            stack.pop();
+        }
+        for _ in 0..10 {
+            stack.collector.collect();
+        }
+        // The epoch can move at most one step past the reader: nothing freed
+        assert_eq!(stack.collector.pending(), 100);
+        drop(reader);
+        for _ in 0..4 {
+            stack.collector.collect();
+        }
+        assert_eq!(stack.collector.pending(), 0);
+    }
+
+    #[test]
+    fn queue_is_fifo_and_bounded() {
+        let q = ArrayQueue::new(4);
+        for lap in 0..3 {
+            for i in 0..4 {
+                q.push(lap * 10 + i).unwrap();
+            }
+            assert_eq!(q.push(-1), Err(-1));
+            for i in 0..4 {
+                assert_eq!(q.pop(), Some(lap * 10 + i));
+            }
+            assert_eq!(q.pop(), None);
+        }
+    }
+
+    #[test]
+    fn queue_stress_mpmc() {
+        let drops = Arc::new(StdAtomicUsize::new(0));
+        let q = ArrayQueue::new(16);
+        let seen = Mutex::new(Vec::new());
+        let consumed = StdAtomicUsize::new(0);
+        const PER_PRODUCER: u64 = 20_000;
+        thread::scope(|s| {
+            for p in 0..4u64 {
+                let (q, drops) = (&q, Arc::clone(&drops));
+                s.spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        let mut item = Tracked(p * PER_PRODUCER + i, Arc::clone(&drops));
+                        while let Err(back) = q.push(item) {
+                            item = back;
+                            thread::yield_now();
+                        }
+                    }
+                });
+            }
+            for _ in 0..4 {
+                let (q, seen, consumed) = (&q, &seen, &consumed);
+                s.spawn(move || {
+                    let mut mine = Vec::new();
+                    while consumed.load(Ordering::SeqCst) < 4 * PER_PRODUCER as usize {
+                        match q.pop() {
+                            Some(t) => {
+                                mine.push(t.0);
+                                consumed.fetch_add(1, Ordering::SeqCst);
+                            }
+                            None => thread::yield_now(),
+                        }
+                    }
+                    seen.lock().unwrap().extend(mine);
+                });
+            }
+        });
+        let all = seen.into_inner().unwrap();
+        let unique: HashSet<_> = all.iter().collect();
+        assert_eq!(unique.len(), 4 * PER_PRODUCER as usize);
+        assert_eq!(drops.load(Ordering::SeqCst), 4 * PER_PRODUCER as usize);
+    }
+
+    #[test]
+    fn queue_drop_releases_unconsumed_items() {
+        let drops = Arc::new(StdAtomicUsize::new(0));
+        let q = ArrayQueue::new(8);
+        for i in 0..5 {
+            q.push(Tracked(i, Arc::clone(&drops))).ok().unwrap();
+        }
+        q.pop();
+        drop(q);
+        assert_eq!(drops.load(Ordering::SeqCst), 5);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    fn model(f: impl Fn() + Sync + Send + 'static) {
+        let mut builder = loom::model::Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(f);
+    }
+
+    #[test]
+    fn loom_stack_concurrent_push_pop() {
+        model(|| {
+            let stack = Arc::new(TreiberStack::new());
+            let handles: Vec<_> = (0..2)
+                .map(|i| {
+                    let stack = Arc::clone(&stack);
+                    thread::spawn(move || {
+                        stack.push(i);
+                        stack.pop()
+                    })
+                })
+                .collect();
+            let mut got: Vec<i32> = handles
+                .into_iter()
+                .map(|h| h.join().unwrap().expect("each thread pops something"))
+                .collect();
+            got.sort();
+            assert_eq!(got, vec![0, 1]);
+            assert!(stack.pop().is_none());
+        });
+    }
+
+    // One thread is pinned inside pop() while the other pops, retires and
+    // keeps collecting: the epoch has to advance past the reader before the
+    // node it may still be looking at is freed
+    #[test]
+    fn loom_reclaim_while_another_thread_is_pinned() {
+        model(|| {
+            let stack = Arc::new(TreiberStack::new());
+            stack.push(1);
+            stack.push(2);
+            let reader = {
+                let stack = Arc::clone(&stack);
+                thread::spawn(move || stack.pop())
+            };
+            let collector = {
+                let stack = Arc::clone(&stack);
+                thread::spawn(move || {
+                    let v = stack.pop(); // retire() collects right away under loom
+                    for _ in 0..3 {
+                        stack.collector.collect();
+                    }
+                    v
+                })
+            };
+            let mut got = vec![reader.join().unwrap(), collector.join().unwrap()];
+            got.sort();
+            assert_eq!(got, vec![Some(1), Some(2)]);
+
+            // Nobody is pinned now: three more advances free everything
+            for _ in 0..3 {
+                stack.collector.collect();
+            }
+            assert_eq!(stack.collector.pending(), 0);
+        });
+    }
+
+    #[test]
+    fn loom_queue_two_producers_one_consumer() {
+        model(|| {
+            let q = Arc::new(ArrayQueue::new(2));
+            let producers: Vec<_> = (0..2)
+                .map(|i| {
+                    let q = Arc::clone(&q);
+                    thread::spawn(move || q.push(i).unwrap())
+                })
+                .collect();
+            let mut got = Vec::new();
+            while got.len() < 2 {
+                match q.pop() {
+                    Some(v) => got.push(v),
+                    None => thread::yield_now(),
+                }
+            }
+            for p in producers {
+                p.join().unwrap();
+            }
+            got.sort();
+            assert_eq!(got, vec![0, 1]);
+        });
+    }
+}
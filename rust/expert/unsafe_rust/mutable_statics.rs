@@ -57,6 +57,7 @@ fn get_errno() -> i32 {
 
 // --- Safe abstraction over mutable static ---
 // Wrap in a struct with safe methods to minimize unsafe surface
+// (for counters other processes can read, see expert/performance/metrics.rs)
 struct GlobalCounter;
 
 impl GlobalCounter {
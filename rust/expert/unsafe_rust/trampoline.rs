@@ -0,0 +1,500 @@
+// Trampolines: passing Rust closures through C function pointers
+//
+// ffi.rs can only hand C a plain `extern "C" fn` — a closure has captured
+// state and no fixed address, so it has no C function pointer. Callback
+// APIs in C solve this with an extra `void *user_data` argument that they
+// pass back untouched:
+//   qsort_r(base, n, size, compar, arg)   → compar(a, b, arg)
+//   pthread_create(.., start, arg)         → start(arg)
+//
+// The trick: box the closure, pass the box's address as `user_data`, and
+// pass a generic `extern "C" fn` — the *trampoline* — that casts
+// `user_data` back to the closure type and calls it. One trampoline is
+// monomorphized per closure type, so C sees an ordinary function pointer.
+//
+// Three things must hold for that to be sound:
+//   - the box outlives every call          → the `Callback` guard owns it and
+//                                            the closure can't outlive its borrows
+//   - no panic unwinds into C              → each call runs under catch_unwind;
+//                                            the panic is stored and rethrown
+//                                            once control is back in Rust
+//   - no two `&mut` to the closure at once → a RefCell refuses re-entry
+//
+// After a panic (or a re-entry) the closure is not called again until the
+// panic is taken; C gets `R::default()` so it can finish what it was doing.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+
+// --- Real libc APIs ---
+
+// glibc/musl only: the BSDs and macOS have a qsort_r too, but with `arg`
+// before `compar` and a comparator taking it first — calling that one through
+// this declaration would be undefined behaviour, so elsewhere it doesn't exist
+#[cfg(any(target_env = "gnu", target_env = "musl"))]
+extern "C" {
+    fn qsort_r(
+        base: *mut c_void,
+        nmemb: usize,
+        size: usize,
+        compar: unsafe extern "C" fn(*const c_void, *const c_void, *mut c_void) -> c_int,
+        arg: *mut c_void,
+    );
+}
+
+extern "C" {
+    // No user data argument, but `key` reaches every comparison untouched,
+    // so it can carry the closure instead (declared `*mut`: same ABI as const)
+    fn bsearch(
+        key: *mut c_void,
+        base: *const c_void,
+        nmemb: usize,
+        size: usize,
+        compar: unsafe extern "C" fn(*mut c_void, *const c_void) -> c_int,
+    ) -> *mut c_void;
+}
+
+// --- The state behind user_data ---
+struct State<F> {
+    f: RefCell<F>,
+    panic: Cell<Option<Box<dyn Any + Send>>>,
+}
+
+impl<F> State<F> {
+    // Run the closure unless an earlier call panicked. Never unwinds.
+    fn invoke<R: Default>(&self, call: impl FnOnce(&mut F) -> R) -> R {
+        let earlier = self.panic.take();
+        if earlier.is_some() {
+            self.panic.set(earlier);
+            return R::default();
+        }
+        let Ok(mut f) = self.f.try_borrow_mut() else {
+            // C called back into us from inside the closure
+            self.panic
+                .set(Some(Box::new("callback re-entered while running")));
+            return R::default();
+        };
+        // AssertUnwindSafe: after a panic the closure is never called again
+        // until the caller has seen the payload
+        match panic::catch_unwind(AssertUnwindSafe(|| call(&mut f))) {
+            Ok(r) => r,
+            Err(payload) => {
+                self.panic.set(Some(payload));
+                R::default()
+            }
+        }
+    }
+}
+
+// --- Signatures: one trampoline per argument list ---
+// DataLast puts user_data after the arguments (qsort_r, most C libraries),
+// DataFirst before them (bsearch's key, GLib's "swapped" signals).
+trait Signature<Args> {
+    type DataLast: Copy;
+    type DataFirst: Copy;
+    fn data_last() -> Self::DataLast;
+    fn data_first() -> Self::DataFirst;
+}
+
+macro_rules! signatures {
+    ($( ($($arg:ident: $ty:ident),*) )*) => {$(
+        impl<F, R, $($ty,)*> Signature<($($ty,)*)> for F
+        where
+            F: FnMut($($ty),*) -> R,
+            R: Default,
+        {
+            type DataLast = unsafe extern "C" fn($($ty,)* *mut c_void) -> R;
+            type DataFirst = unsafe extern "C" fn(*mut c_void, $($ty),*) -> R;
+
+            fn data_last() -> Self::DataLast {
+                unsafe extern "C" fn last<F, R, $($ty,)*>($($arg: $ty,)* data: *mut c_void) -> R
+                where
+                    F: FnMut($($ty),*) -> R,
+                    R: Default,
+                {
+                    // SAFETY: `data` came from a `Callback<F>` that is still alive
+                    let state = unsafe { &*(data as *const State<F>) };
+                    state.invoke(|f| f($($arg),*))
+                }
+                last::<F, R, $($ty,)*>
+            }
+
+            fn data_first() -> Self::DataFirst {
+                unsafe extern "C" fn first<F, R, $($ty,)*>(data: *mut c_void, $($arg: $ty),*) -> R
+                where
+                    F: FnMut($($ty),*) -> R,
+                    R: Default,
+                {
+                    // SAFETY: as above
+                    let state = unsafe { &*(data as *const State<F>) };
+                    state.invoke(|f| f($($arg),*))
+                }
+                first::<F, R, $($ty,)*>
+            }
+        }
+    )*};
+}
+
+signatures! {
+    ()
+    (a: A)
+    (a: A, b: B)
+    (a: A, b: B, c: C)
+}
+
+// --- The guard ---
+// Owns the boxed closure; the raw pair is valid exactly as long as the guard.
+// Borrowed captures keep the guard from outliving them, and moving the guard
+// doesn't move the box, so user_data stays put.
+struct Callback<F> {
+    state: Box<State<F>>,
+}
+
+impl<F> Callback<F> {
+    fn new(f: F) -> Self {
+        Callback {
+            state: Box::new(State {
+                f: RefCell::new(f),
+                panic: Cell::new(None),
+            }),
+        }
+    }
+
+    fn user_data(&self) -> *mut c_void {
+        &*self.state as *const State<F> as *mut c_void
+    }
+
+    // (trampoline, user_data) for APIs that pass user_data last.
+    // C must stop calling it before the guard is dropped, and must not call
+    // it from two threads at once.
+    fn raw<Args>(&self) -> (F::DataLast, *mut c_void)
+    where
+        F: Signature<Args>,
+    {
+        (F::data_last(), self.user_data())
+    }
+
+    // Same, for APIs that pass user_data first
+    fn raw_data_first<Args>(&self) -> (F::DataFirst, *mut c_void)
+    where
+        F: Signature<Args>,
+    {
+        (F::data_first(), self.user_data())
+    }
+
+    // Clears the panic, so the next call runs the closure again
+    fn take_panic(&self) -> Option<Box<dyn Any + Send>> {
+        self.state.panic.take()
+    }
+
+    // Call after the C function returns: rethrows a panic from the closure
+    fn finish(self) {
+        if let Some(payload) = self.take_panic() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl<F> Drop for Callback<F> {
+    // A panic nobody took is rethrown here rather than silently lost
+    fn drop(&mut self) {
+        if let Some(payload) = self.state.panic.take() {
+            if !std::thread::panicking() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+// --- Safe wrappers over the libc functions ---
+
+// Sorts with libc's qsort_r and any comparison closure.
+// qsort_r moves elements bytewise, which is what a Rust move is; if `cmp`
+// panics it still finishes (every comparison after says "equal"), so the
+// slice is a permutation again before the panic is rethrown.
+#[cfg(any(target_env = "gnu", target_env = "musl"))]
+fn sort_by<T, F>(slice: &mut [T], mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    const {
+        assert!(
+            std::mem::size_of::<T>() != 0,
+            "zero-sized types are not supported"
+        )
+    };
+    let callback = Callback::new(|a: *const c_void, b: *const c_void| -> c_int {
+        // SAFETY: qsort_r passes pointers to two elements of `slice`
+        let (a, b) = unsafe { (&*(a as *const T), &*(b as *const T)) };
+        cmp(a, b) as c_int
+    });
+    let (compar, data) = callback.raw();
+    // SAFETY: base/len/size describe `slice`; `callback` outlives the call
+    unsafe {
+        qsort_r(
+            slice.as_mut_ptr().cast(),
+            slice.len(),
+            std::mem::size_of::<T>(),
+            compar,
+            data,
+        )
+    };
+    callback.finish();
+}
+
+// Binary search with libc's bsearch; `f` compares an element to the target,
+// like slice::binary_search_by.
+fn binary_search_by<T, F>(slice: &[T], mut f: F) -> Option<usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    // Element size 0 would make every element the same address (and
+    // offset_from below panics): reject at compile time
+    const {
+        assert!(
+            std::mem::size_of::<T>() != 0,
+            "zero-sized types are not supported"
+        )
+    };
+    // bsearch wants key <=> element; `f` gives element <=> target
+    let callback = Callback::new(|elem: *const c_void| -> c_int {
+        // SAFETY: bsearch passes a pointer to an element of `slice`
+        f(unsafe { &*(elem as *const T) }).reverse() as c_int
+    });
+    let (compar, key) = callback.raw_data_first();
+    // SAFETY: as in sort_by
+    let found = unsafe {
+        bsearch(
+            key,
+            slice.as_ptr().cast(),
+            slice.len(),
+            std::mem::size_of::<T>(),
+            compar,
+        )
+    };
+    callback.finish();
+    // SAFETY: a non-null result points into `slice`
+    (!found.is_null()).then(|| unsafe { (found as *const T).offset_from(slice.as_ptr()) } as usize)
+}
+
+// --- A C-style API of our own ---
+// filter_with from ffi.rs, the way a C library would write it
+type IntPredicate = unsafe extern "C" fn(c_int, *mut c_void) -> bool;
+
+extern "C" fn filter_ints(
+    nums: *const c_int,
+    len: usize,
+    out: *mut c_int,
+    pred: IntPredicate,
+    user_data: *mut c_void,
+) -> usize {
+    let mut kept = 0;
+    for i in 0..len {
+        // SAFETY: the caller passes `len` readable and writable ints
+        unsafe {
+            let n = *nums.add(i);
+            if pred(n, user_data) {
+                *out.add(kept) = n;
+                kept += 1;
+            }
+        }
+    }
+    kept
+}
+
+fn filter_with<F: FnMut(i32) -> bool>(nums: &[i32], pred: F) -> Vec<i32> {
+    let mut out = vec![0; nums.len()];
+    let callback = Callback::new(pred);
+    let (pred, data) = callback.raw();
+    let kept = filter_ints(nums.as_ptr(), nums.len(), out.as_mut_ptr(), pred, data);
+    callback.finish();
+    out.truncate(kept);
+    out
+}
+
+#[cfg(any(target_env = "gnu", target_env = "musl"))]
+fn demo_qsort_r() {
+    let mut words = vec!["pear", "fig", "banana", "kiwi", "apple"];
+    let mut comparisons = 0;
+    sort_by(&mut words, |a, b| {
+        comparisons += 1;
+        a.len().cmp(&b.len()).then(a.cmp(b))
+    });
+    println!("{:?}", words); // ["fig", "kiwi", "pear", "apple", "banana"]
+    println!("{}", comparisons > 0); // true
+}
+
+fn main() {
+    // --- Closures that capture state, called from C ---
+    let nums = [-3, -2, -1, 0, 1, 2, 3, 4];
+    let threshold = 1;
+    println!("{:?}", filter_with(&nums, |n| n > threshold)); // [2, 3, 4]
+
+    let mut seen = 0;
+    let evens = filter_with(&nums, |n| {
+        seen += 1;
+        n % 2 == 0
+    });
+    println!("{:?} after {} calls", evens, seen); // [-2, 0, 2, 4] after 8 calls
+
+    // --- qsort_r with a comparison closure ---
+    #[cfg(any(target_env = "gnu", target_env = "musl"))]
+    demo_qsort_r();
+
+    // --- bsearch ---
+    let primes = [2, 3, 5, 7, 11, 13];
+    println!("{:?}", binary_search_by(&primes, |p| p.cmp(&7))); // Some(3)
+    println!("{:?}", binary_search_by(&primes, |p| p.cmp(&8))); // None
+
+    // --- A panic stops at the boundary and is rethrown in Rust ---
+    let quiet = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut calls = 0;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        filter_with(&nums, |n| {
+            calls += 1;
+            if calls == 2 {
+                panic!("bad predicate");
+            }
+            n > 0
+        })
+    }));
+    panic::set_hook(quiet);
+    let msg = *result.unwrap_err().downcast::<&str>().unwrap();
+    println!("{} after {} calls", msg, calls); // bad predicate after 2 calls
+
+    println!("trampoline done"); // trampoline done
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panic_message(payload: Box<dyn Any + Send>) -> String {
+        match payload.downcast::<&str>() {
+            Ok(s) => s.to_string(),
+            Err(payload) => *payload.downcast::<String>().unwrap(),
+        }
+    }
+
+    #[test]
+    #[cfg(any(target_env = "gnu", target_env = "musl"))]
+    fn test_sort_by_matches_std() {
+        let mut v: Vec<i64> = (0..500).map(|i| (i * 7919) % 503 - 250).collect();
+        let mut expected = v.clone();
+        expected.sort_by(|a, b| b.cmp(a));
+        sort_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, expected);
+    }
+
+    #[test]
+    #[cfg(any(target_env = "gnu", target_env = "musl"))]
+    fn test_sort_by_moves_owned_values() {
+        let mut v: Vec<String> = ["delta", "alpha", "charlie", "bravo"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        sort_by(&mut v, |a, b| a.cmp(b));
+        assert_eq!(v, ["alpha", "bravo", "charlie", "delta"]);
+    }
+
+    #[test]
+    fn test_binary_search_by() {
+        let v: Vec<u32> = (0..100).map(|i| i * 3).collect();
+        for (i, x) in v.iter().enumerate() {
+            assert_eq!(binary_search_by(&v, |e| e.cmp(x)), Some(i));
+        }
+        assert_eq!(binary_search_by(&v, |e| e.cmp(&1)), None);
+        assert_eq!(binary_search_by(&v, |e| e.cmp(&1000)), None);
+        assert_eq!(binary_search_by(&[] as &[u32], |e| e.cmp(&0)), None);
+    }
+
+    #[test]
+    fn test_closure_state_survives_calls() {
+        let mut seen = Vec::new();
+        let kept = filter_with(&[1, 2, 3, 4, 5, 6], |n| {
+            seen.push(n);
+            n % 3 == 0
+        });
+        assert_eq!(kept, [3, 6]);
+        assert_eq!(seen, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    #[cfg(any(target_env = "gnu", target_env = "musl"))]
+    fn test_panic_is_caught_and_rethrown() {
+        let mut v: Vec<i32> = (0..200).rev().collect();
+        let mut calls = 0;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            sort_by(&mut v, |a, b| {
+                calls += 1;
+                if calls == 10 {
+                    panic!("comparison {} failed", calls);
+                }
+                a.cmp(b)
+            })
+        }));
+        assert_eq!(panic_message(result.unwrap_err()), "comparison 10 failed");
+        // qsort_r finished without the closure, and no element was lost
+        assert_eq!(calls, 10);
+        v.sort();
+        assert_eq!(v, (0..200).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_take_panic_rearms_the_closure() {
+        let mut calls = 0;
+        let callback = Callback::new(|n: c_int| -> c_int {
+            calls += 1;
+            if n < 0 {
+                panic!("negative");
+            }
+            n * 2
+        });
+        let (f, data) = callback.raw();
+        unsafe {
+            assert_eq!(f(-1, data), 0); // default after the panic
+            assert_eq!(f(5, data), 0); // not called while a panic is pending
+            assert_eq!(panic_message(callback.take_panic().unwrap()), "negative");
+            assert_eq!(f(5, data), 10);
+        }
+        callback.finish();
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_reentry_is_refused() {
+        type Raw = unsafe extern "C" fn(c_int, *mut c_void) -> c_int;
+        let again: Cell<Option<(Raw, *mut c_void)>> = Cell::new(None);
+        let callback = Callback::new(|n: c_int| -> c_int {
+            match again.get() {
+                // SAFETY: the guard is alive; the call itself is what we test
+                Some((f, data)) if n > 0 => unsafe { f(n - 1, data) + 1 },
+                _ => 100,
+            }
+        });
+        again.set(Some(callback.raw()));
+        let (f, data) = callback.raw();
+        assert_eq!(unsafe { f(3, data) }, 1); // inner call got the default
+        let msg = panic_message(callback.take_panic().unwrap());
+        assert_eq!(msg, "callback re-entered while running");
+    }
+
+    #[test]
+    fn test_drop_rethrows_untaken_panic() {
+        let result = panic::catch_unwind(|| {
+            let callback = Callback::new(|| -> bool { panic!("lost?") });
+            let (f, data) = callback.raw_data_first();
+            assert!(!unsafe { f(data) });
+        });
+        assert_eq!(panic_message(result.unwrap_err()), "lost?");
+    }
+}
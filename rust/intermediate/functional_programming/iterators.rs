@@ -1,5 +1,6 @@
 // The Iterator trait: anything that yields a sequence of values
 // Core method: fn next(&mut self) -> Option<Self::Item>
+// (parallel versions of these pipelines: advanced/concurrent_programming/par_iter.rs)
 
 // Implementing a custom iterator
 struct Counter {
@@ -75,6 +75,7 @@ fn main() {
     println!("{}", counts[&'c']); // 1
 
     // --- CHAINING ---
+    // (the same chains across all cores: par_iter() in advanced/concurrent_programming/par_iter.rs)
 
     // sum of squares of odd numbers
     let result: i32 = numbers